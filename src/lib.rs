@@ -1,20 +1,32 @@
 //! lib-bin split so that internal types e.g. ConfigFileRaw are available for integration tests
 
 mod luts;
+mod output;
 mod parse;
 mod pool_config;
+mod slippage;
+mod snapshot_store;
+mod squads;
 mod subcmd;
 mod tx_utils;
 mod update;
+mod update_journal;
 
 use clap::{builder::ValueParser, Parser};
 pub use luts::*;
+pub use output::*;
 pub use parse::*;
 pub use pool_config::*;
-use sanctum_solana_cli_utils::{ConfigWrapper, TxSendMode};
+use sanctum_solana_cli_utils::{parse_signer, ConfigWrapper, TxSendMode};
+pub use slippage::*;
+pub use snapshot_store::*;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{address_lookup_table::AddressLookupTableAccount, pubkey::Pubkey};
+pub use squads::*;
 pub use subcmd::*;
 pub use tx_utils::*;
 pub use update::*;
+pub use update_journal::*;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about = "Sanctum SPL Stake Pool CLI")]
@@ -53,6 +65,94 @@ This arg is the max priority fee the user will pay per transaction in lamports.
     )]
     pub fee_limit_cb: u64,
 
+    #[arg(
+        long,
+        help = "Squads v4 multisig whose vault is the pool's manager or staker.
+If set, manager/staker txs of sync, sync-pool, set-staker, sync-validator-list and sync-delegation
+are created as squads vault transaction proposals instead, signed and paid for by the payer,
+who must be a multisig member with initiate permissions.
+"
+    )]
+    pub squads_multisig: Option<Pubkey>,
+
+    #[arg(
+        long,
+        help = "Index of the squads multisig vault that is the pool's manager or staker",
+        default_value_t = 0
+    )]
+    pub squads_vault_index: u8,
+
+    #[arg(
+        long,
+        help = "Durable nonce account to use for transactions instead of a recent blockhash.
+Each transaction is prefixed with an advance nonce instruction.
+With dump-msg, only one dumped transaction can land per nonce advance,
+so commands that output multiple transactions must be rerun after each one lands.
+"
+    )]
+    pub nonce: Option<Pubkey>,
+
+    #[arg(
+        long,
+        help = "Authority of the durable nonce account. Defaults to payer if not provided",
+        requires = "nonce"
+    )]
+    pub nonce_authority: Option<String>,
+
+    #[arg(
+        long,
+        help = "Additional address lookup table to compile transactions against. Can be specified multiple times.
+The SRLUT is always used if it exists on the cluster.
+"
+    )]
+    pub lut: Vec<Pubkey>,
+
+    #[arg(
+        long,
+        help = "Format of informational output e.g. pool info and change summaries.
+Pool info and reports go to stdout, change summaries to stderr so that stdout remains usable with dump-msg.
+Plan files output by --plan are always toml.
+",
+        default_value_t = OutputFormat::default(),
+        value_enum,
+    )]
+    pub output: OutputFormat,
+
     #[command(subcommand)]
     pub subcmd: Subcmd,
 }
+
+impl Args {
+    pub async fn squads_proposer(&self, rpc: &RpcClient) -> Option<SquadsProposer> {
+        match self.squads_multisig {
+            Some(multisig) => {
+                Some(SquadsProposer::fetch(rpc, multisig, self.squads_vault_index).await)
+            }
+            None => None,
+        }
+    }
+
+    pub async fn luts(&self, rpc: &RpcClient) -> Vec<AddressLookupTableAccount> {
+        fetch_luts(rpc, &self.lut).await
+    }
+
+    pub fn tx_nonce(&self) -> Option<TxNonce> {
+        let nonce = self.nonce?;
+        let authority = match self.nonce_authority.as_ref() {
+            Some(s) => match self.send_mode {
+                TxSendMode::DumpMsg | TxSendMode::SimOnly => parse_signer_allow_pubkey(s).unwrap(),
+                TxSendMode::SendActual => parse_signer(s).unwrap(),
+            },
+            None => self.config.signer(),
+        };
+        Some(TxNonce { nonce, authority })
+    }
+
+    /// The squads vault can't sign, so allow pubkeys for manager/staker signers in squads mode
+    pub const fn authority_send_mode(&self) -> TxSendMode {
+        match self.squads_multisig {
+            Some(_) => TxSendMode::DumpMsg,
+            None => self.send_mode,
+        }
+    }
+}
@@ -1,16 +1,298 @@
+use std::{collections::HashSet, iter::once, num::NonZeroU32};
+
+use sanctum_spl_stake_pool_lib::{
+    FindTransientStakeAccount, FindTransientStakeAccountArgs, FindValidatorStakeAccount,
+    FindValidatorStakeAccountArgs, FindWithdrawAuthority,
+};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    address_lookup_table::{
+        state::{AddressLookupTable, LOOKUP_TABLE_MAX_ADDRESSES},
+        AddressLookupTableAccount,
+    },
+    clock::Slot,
+    pubkey::Pubkey,
+    stake, system_program, sysvar,
+};
+use spl_stake_pool_interface::{StakePool, ValidatorStakeInfo};
+
+use crate::TxNonce;
+
+/// Each address is 32 bytes of ix data, leaving room for the payer and authority signatures
+pub const MAX_EXTEND_LUT_ADDRESSES_PER_TX: usize = 24;
+
+/// For durable nonce txs, which are prefixed with an advance nonce ix
+/// and may need an additional signature from the nonce authority
+pub const MAX_EXTEND_LUT_ADDRESSES_PER_TX_NONCE: usize = 19;
+
+pub fn max_extend_lut_addresses_per_tx(nonce: Option<&TxNonce>) -> usize {
+    if nonce.is_some() {
+        MAX_EXTEND_LUT_ADDRESSES_PER_TX_NONCE
+    } else {
+        MAX_EXTEND_LUT_ADDRESSES_PER_TX
+    }
+}
+
 pub mod srlut {
     sanctum_macros::declare_program_keys!("KtrvWWkPkhSWM9VMqafZhgnTuozQiHzrBDT8oPcMj3T", []);
 }
 
-/*
-pub async fn fetch_srlut(rpc: &RpcClient) -> AddressLookupTableAccount {
-    let srlut = rpc.get_account(&srlut::ID).await.unwrap();
+/// Subset of the SRLUT's addresses relevant to the stake pool program's instructions,
+/// which the per-tx batching limits for txs compiled against the SRLUT were measured with
+pub const SRLUT_STAKE_POOL_ADDRESSES: [Pubkey; 5] = [
+    sysvar::clock::ID,
+    sysvar::rent::ID,
+    sysvar::stake_history::ID,
+    system_program::ID,
+    stake::program::ID,
+];
+
+/// Fetches the SRLUT followed by `additional` lookup tables.
+///
+/// The SRLUT is skipped if it does not exist on the cluster e.g. localnet.
+/// Panics if any of `additional` does not exist.
+pub async fn fetch_luts(rpc: &RpcClient, additional: &[Pubkey]) -> Vec<AddressLookupTableAccount> {
+    let keys: Vec<Pubkey> = once(srlut::ID)
+        .chain(additional.iter().copied().filter(|k| *k != srlut::ID))
+        .collect();
+    let fetched = rpc.get_multiple_accounts(&keys).await.unwrap();
+    keys.into_iter()
+        .zip(fetched)
+        .filter_map(|(key, acc)| {
+            let acc = match acc {
+                Some(acc) => acc,
+                None if key == srlut::ID => return None,
+                None => panic!("Lookup table {key} not found"),
+            };
+            let lut = AddressLookupTable::deserialize(&acc.data).unwrap();
+            if key == srlut::ID && lut.meta.deactivation_slot != Slot::MAX {
+                eprintln!("SRLUT {key} is deactivated, not using it");
+                return None;
+            }
+            Some(AddressLookupTableAccount {
+                key,
+                addresses: lut.addresses.into(),
+            })
+        })
+        .collect()
+}
+
+/// The per-tx batching limits for txs compiled against the SRLUT are only valid if it was loaded
+/// and contains all of [`SRLUT_STAKE_POOL_ADDRESSES`]
+pub fn has_srlut(luts: &[AddressLookupTableAccount]) -> bool {
+    luts.iter().any(|lut| {
+        lut.key == srlut::ID
+            && SRLUT_STAKE_POOL_ADDRESSES
+                .iter()
+                .all(|addr| lut.addresses.contains(addr))
+    })
+}
+
+/// The per-tx batching limits for txs compiled against a pool's lookup table are only valid
+/// if the loaded lookup tables contain all of `pool_lut_addresses`, e.g. from `sync-lut`
+pub fn has_pool_lut(luts: &[AddressLookupTableAccount], pool_lut_addresses: &[Pubkey]) -> bool {
+    let loaded: HashSet<&Pubkey> = luts.iter().flat_map(|lut| lut.addresses.iter()).collect();
+    pool_lut_addresses.iter().all(|addr| loaded.contains(addr))
+}
+
+/// All of a pool's static accounts and the validator and transient stake accounts of all its validators,
+/// in a deterministic order
+pub fn pool_lut_addresses(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    stake_pool: &StakePool,
+    validators: &[ValidatorStakeInfo],
+) -> Vec<Pubkey> {
+    let pool = *pool;
+    let (withdraw_auth, _bump) = FindWithdrawAuthority { pool }.run_for_prog(program_id);
+    let statics = [
+        pool,
+        stake_pool.validator_list,
+        stake_pool.reserve_stake,
+        withdraw_auth,
+        stake_pool.pool_mint,
+        stake_pool.manager_fee_account,
+        stake_pool.token_program,
+        sysvar::clock::ID,
+        sysvar::rent::ID,
+        sysvar::stake_history::ID,
+        #[allow(deprecated)]
+        stake::config::ID,
+        stake::program::ID,
+        system_program::ID,
+    ];
+    let validator_accs = validators.iter().flat_map(
+        |ValidatorStakeInfo {
+             vote_account_address,
+             validator_seed_suffix,
+             transient_seed_suffix,
+             ..
+         }| {
+            let vote = *vote_account_address;
+            let (vsa, _bump) = FindValidatorStakeAccount::new(FindValidatorStakeAccountArgs {
+                pool,
+                vote,
+                seed: NonZeroU32::new(*validator_seed_suffix),
+            })
+            .run_for_prog(program_id);
+            let (tsa, _bump) = FindTransientStakeAccount::new(FindTransientStakeAccountArgs {
+                pool,
+                vote,
+                seed: *transient_seed_suffix,
+            })
+            .run_for_prog(program_id);
+            [vote, vsa, tsa]
+        },
+    );
+    let mut seen = HashSet::new();
+    statics
+        .into_iter()
+        .chain(validator_accs)
+        .filter(|pk| seen.insert(*pk))
+        .collect()
+}
+
+/// Returns the addresses in `desired` not yet in `curr`, in order.
+///
+/// Panics if adding them would exceed the max number of addresses a lookup table can hold
+pub fn lut_addresses_to_extend(curr: &[Pubkey], desired: &[Pubkey]) -> Vec<Pubkey> {
+    let curr: HashSet<&Pubkey> = curr.iter().collect();
+    let res: Vec<Pubkey> = desired
+        .iter()
+        .filter(|pk| !curr.contains(pk))
+        .copied()
+        .collect();
+    if curr.len() + res.len() > LOOKUP_TABLE_MAX_ADDRESSES {
+        panic!(
+            "Lookup table can only hold {LOOKUP_TABLE_MAX_ADDRESSES} addresses, {} required",
+            curr.len() + res.len()
+        );
+    }
+    res
+}
+
+#[cfg(test)]
+pub(crate) fn mock_srlut() -> AddressLookupTableAccount {
     AddressLookupTableAccount {
         key: srlut::ID,
-        addresses: AddressLookupTable::deserialize(&srlut.data)
-            .unwrap()
-            .addresses
-            .into(),
+        addresses: SRLUT_STAKE_POOL_ADDRESSES.into(),
+    }
+}
+
+/// All non-signer accounts of `ixs` except `exclude`, like a pool's lookup table would hold them
+#[cfg(test)]
+pub(crate) fn mock_pool_lut(
+    ixs: &[solana_sdk::instruction::Instruction],
+    exclude: &[Pubkey],
+) -> AddressLookupTableAccount {
+    let mut addresses: Vec<Pubkey> = ixs
+        .iter()
+        .flat_map(|ix| ix.accounts.iter())
+        .filter(|meta| !meta.is_signer && !exclude.contains(&meta.pubkey))
+        .map(|meta| meta.pubkey)
+        .collect();
+    addresses.sort();
+    addresses.dedup();
+    AddressLookupTableAccount {
+        key: Pubkey::new_unique(),
+        addresses,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sanctum_solana_test_utils::assert_tx_with_cb_ixs_within_size_limits;
+    use solana_sdk::{
+        address_lookup_table::instruction::{create_lookup_table, extend_lookup_table},
+        system_instruction,
+    };
+
+    use super::*;
+
+    fn check_extend_lut_addresses_per_tx_limit(max: usize, nonce: bool) {
+        // worst-case: authority != payer, so 2 signers, and the tx also creates the lut
+        let payer = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let (create_ix, lut) = create_lookup_table(authority, payer, 0);
+        let extend_ix = extend_lookup_table(
+            lut,
+            authority,
+            Some(payer),
+            (0..max).map(|_| Pubkey::new_unique()).collect(),
+        );
+        // worst-case: nonce authority != payer, so 1 more signer
+        let advance_nonce_ix = nonce.then(|| {
+            system_instruction::advance_nonce_account(&Pubkey::new_unique(), &Pubkey::new_unique())
+        });
+        assert_tx_with_cb_ixs_within_size_limits(
+            &payer,
+            advance_nonce_ix.into_iter().chain([create_ix, extend_ix]),
+            &[],
+        );
+    }
+
+    #[test]
+    fn check_max_extend_lut_addresses_per_tx_limit() {
+        // size = 1188
+        check_extend_lut_addresses_per_tx_limit(MAX_EXTEND_LUT_ADDRESSES_PER_TX, false);
+    }
+
+    #[test]
+    fn check_max_extend_lut_addresses_per_tx_nonce_limit() {
+        // size = 1198
+        check_extend_lut_addresses_per_tx_limit(MAX_EXTEND_LUT_ADDRESSES_PER_TX_NONCE, true);
+    }
+
+    #[test]
+    fn srlut_must_have_all_stake_pool_addresses() {
+        let srlut = mock_srlut();
+        assert!(has_srlut(&[srlut.clone()]));
+
+        let truncated = AddressLookupTableAccount {
+            addresses: srlut.addresses[..srlut.addresses.len() - 1].into(),
+            ..srlut.clone()
+        };
+        assert!(!has_srlut(&[truncated]));
+
+        let other_lut = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            ..srlut
+        };
+        assert!(!has_srlut(&[other_lut]));
+    }
+
+    #[test]
+    fn pool_lut_must_have_all_addresses() {
+        let addresses: Vec<Pubkey> = (0..4).map(|_| Pubkey::new_unique()).collect();
+        let pool_lut = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: addresses[1..].into(),
+        };
+        assert!(has_pool_lut(&[pool_lut.clone()], &addresses[1..]));
+        assert!(!has_pool_lut(&[pool_lut.clone()], &addresses));
+        let other = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: vec![addresses[0]],
+        };
+        assert!(has_pool_lut(&[other, pool_lut], &addresses));
+    }
+
+    #[test]
+    fn extend_only_missing_addresses() {
+        let desired: Vec<Pubkey> = (0..5).map(|_| Pubkey::new_unique()).collect();
+        let curr = [desired[3], desired[0], Pubkey::new_unique()];
+        assert_eq!(
+            lut_addresses_to_extend(&curr, &desired),
+            [desired[1], desired[2], desired[4]]
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn extend_past_max_addresses_fails() {
+        let curr: Vec<Pubkey> = (0..LOOKUP_TABLE_MAX_ADDRESSES)
+            .map(|_| Pubkey::new_unique())
+            .collect();
+        lut_addresses_to_extend(&curr, &[Pubkey::new_unique()]);
     }
 }
- */
@@ -0,0 +1,209 @@
+use std::{collections::BTreeMap, fmt::Display};
+
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    #[default]
+    Toml,
+    Json,
+
+    /// Human-readable aligned columns. Not meant to be parsed.
+    Table,
+}
+
+impl Display for OutputFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Toml => f.write_str("toml"),
+            Self::Json => f.write_str("json"),
+            Self::Table => f.write_str("table"),
+        }
+    }
+}
+
+impl OutputFormat {
+    /// Formats `value` nested under `key`, e.g. as the `[pool]` table in toml
+    /// or the `"pool"` field of a json object
+    pub fn format<T: Serialize + ?Sized>(&self, key: &str, value: &T) -> String {
+        match self {
+            // serialize maps directly instead of through toml::Value/serde_json::Value
+            // to preserve struct field order
+            Self::Toml => toml::to_string_pretty(&BTreeMap::from([(key, value)])).unwrap(),
+            Self::Json => serde_json::to_string_pretty(&BTreeMap::from([(key, value)])).unwrap(),
+            Self::Table => {
+                let mut res = String::new();
+                write_table_value(&mut res, key, &serde_json::to_value(value).unwrap());
+                res
+            }
+        }
+    }
+
+    /// Prints `value` to stdout, see [`Self::format`]
+    pub fn print<T: Serialize + ?Sized>(&self, key: &str, value: &T) {
+        println!("{}", self.format(key, value));
+    }
+
+    /// Prints a changeset to stderr, keeping stdout free for dumped txs.
+    /// Prints nothing if there are no changes.
+    pub fn eprint_changes<T: Serialize>(&self, key: &str, changes: &[T]) {
+        if changes.is_empty() {
+            return;
+        }
+        eprintln!("{}", self.format(key, changes));
+    }
+}
+
+/// Objects are written as a field-value table followed by a table for each array-of-objects field,
+/// arrays as a table with a row per element
+fn write_table_value(res: &mut String, title: &str, value: &Value) {
+    match value {
+        Value::Object(obj) => {
+            let (arrays, fields): (Vec<_>, Vec<_>) = obj
+                .iter()
+                .partition(|(_, v)| matches!(v, Value::Array(a) if a.iter().any(Value::is_object)));
+            let rows: Vec<Vec<String>> = fields
+                .into_iter()
+                .map(|(k, v)| vec![k.clone(), cell(v)])
+                .collect();
+            if !rows.is_empty() {
+                write_rows(res, title, &["field".to_owned(), "value".to_owned()], &rows);
+            }
+            for (k, v) in arrays {
+                write_table_value(res, &format!("{title}.{k}"), v);
+            }
+        }
+        Value::Array(arr) if arr.iter().any(Value::is_object) => {
+            let mut columns: Vec<String> = vec![];
+            for k in arr.iter().filter_map(Value::as_object).flat_map(Map::keys) {
+                if !columns.contains(k) {
+                    columns.push(k.clone());
+                }
+            }
+            let rows: Vec<Vec<String>> = arr
+                .iter()
+                .map(|v| match v {
+                    Value::Object(obj) => columns
+                        .iter()
+                        .map(|c| obj.get(c).map_or_else(|| "-".to_owned(), cell))
+                        .collect(),
+                    _ => vec![cell(v)],
+                })
+                .collect();
+            write_rows(res, title, &columns, &rows);
+        }
+        Value::Array(arr) => {
+            let rows: Vec<Vec<String>> = arr.iter().map(|v| vec![cell(v)]).collect();
+            write_rows(res, title, &[title.to_owned()], &rows);
+        }
+        _ => write_rows(res, title, &[title.to_owned()], &[vec![cell(value)]]),
+    }
+}
+
+fn cell(value: &Value) -> String {
+    match value {
+        Value::Null => "-".to_owned(),
+        Value::String(s) => s.clone(),
+        Value::Array(arr) if !arr.iter().any(Value::is_object) => {
+            arr.iter().map(cell).collect::<Vec<_>>().join(", ")
+        }
+        _ => value.to_string(),
+    }
+}
+
+fn write_rows(res: &mut String, title: &str, columns: &[String], rows: &[Vec<String>]) {
+    let widths: Vec<usize> = columns
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            rows.iter()
+                .filter_map(|r| r.get(i))
+                .map(String::len)
+                .chain(std::iter::once(c.len()))
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+    let mut write_row = |row: &[String]| {
+        let line = row
+            .iter()
+            .zip(widths.iter())
+            .map(|(s, w)| format!("{s:<w$}"))
+            .collect::<Vec<_>>()
+            .join("  ");
+        res.push_str(line.trim_end());
+        res.push('\n');
+    };
+    res.push_str(&format!("{title}:\n"));
+    write_row(columns);
+    for row in rows {
+        write_row(row);
+    }
+    res.push('\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "kebab-case")]
+    struct Row {
+        vote: String,
+        lamports: Option<u64>,
+    }
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "kebab-case")]
+    struct Info {
+        epoch: u64,
+        rows: Vec<Row>,
+    }
+
+    fn info() -> Info {
+        Info {
+            epoch: 5,
+            rows: vec![
+                Row {
+                    vote: "abc".to_owned(),
+                    lamports: Some(10),
+                },
+                Row {
+                    vote: "defgh".to_owned(),
+                    lamports: None,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn formats_nested_under_key() {
+        let toml = OutputFormat::Toml.format("info", &info());
+        assert!(toml.starts_with("[info]\nepoch = 5\n"));
+        assert!(toml.contains("[[info.rows]]"));
+
+        let json: Value =
+            serde_json::from_str(&OutputFormat::Json.format("info", &info())).unwrap();
+        assert_eq!(json["info"]["rows"][1]["vote"], "defgh");
+        assert_eq!(json["info"]["rows"][1]["lamports"], Value::Null);
+    }
+
+    #[test]
+    fn table_format() {
+        assert_eq!(
+            OutputFormat::Table.format("info", &info()),
+            "info:
+field  value
+epoch  5
+
+info.rows:
+vote   lamports
+abc    10
+defgh  -
+
+"
+        );
+    }
+}
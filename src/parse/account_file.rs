@@ -0,0 +1,100 @@
+use std::{error::Error, fs::read_to_string, path::Path, str::FromStr};
+
+use data_encoding::BASE64;
+use serde::Deserialize;
+use solana_account_decoder::UiAccount;
+use solana_sdk::{account::Account, pubkey::Pubkey};
+
+/// Account data read from a file in one of the following formats:
+/// - `{ "pubkey": ..., "account": <UiAccount> }` json, as output by `solana account --output json`
+///   and used in `test-fixtures/`
+/// - bare `UiAccount` json, as returned by `getAccountInfo`
+/// - base64-encoded account data
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AccountFile {
+    /// Not available for bare `UiAccount`s and base64 data
+    pub pubkey: Option<Pubkey>,
+
+    /// Not available for base64 data
+    pub owner: Option<Pubkey>,
+
+    pub data: Vec<u8>,
+}
+
+#[derive(Deserialize)]
+struct KeyedUiAccount {
+    pubkey: String,
+    account: UiAccount,
+}
+
+impl AccountFile {
+    pub fn read_from_path<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        Self::parse(&read_to_string(path)?)
+    }
+
+    pub fn parse(s: &str) -> Result<Self, Box<dyn Error>> {
+        if let Ok(KeyedUiAccount { pubkey, account }) = serde_json::from_str(s) {
+            return Ok(Self {
+                pubkey: Some(Pubkey::from_str(&pubkey)?),
+                ..Self::from_ui_account(&account)?
+            });
+        }
+        if let Ok(account) = serde_json::from_str::<UiAccount>(s) {
+            return Self::from_ui_account(&account);
+        }
+        let data = BASE64.decode(s.trim().as_bytes())?;
+        Ok(Self {
+            pubkey: None,
+            owner: None,
+            data,
+        })
+    }
+
+    fn from_ui_account(ui_account: &UiAccount) -> Result<Self, Box<dyn Error>> {
+        let Account { owner, data, .. } = ui_account
+            .decode()
+            .ok_or("Could not decode account data, only base58 and base64 encodings supported")?;
+        Ok(Self {
+            pubkey: None,
+            owner: Some(owner),
+            data,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sanctum_solana_test_utils::test_fixtures_dir;
+    use solana_sdk::address_lookup_table;
+
+    use super::*;
+
+    #[test]
+    fn parse_keyed_ui_account_fixture() {
+        let AccountFile {
+            pubkey,
+            owner,
+            data,
+        } = AccountFile::read_from_path(test_fixtures_dir().join("srlut.json")).unwrap();
+        assert_eq!(
+            pubkey,
+            Some(Pubkey::from_str("KtrvWWkPkhSWM9VMqafZhgnTuozQiHzrBDT8oPcMj3T").unwrap())
+        );
+        assert_eq!(owner, Some(address_lookup_table::program::ID));
+        assert!(!data.is_empty());
+    }
+
+    #[test]
+    fn parse_base64() {
+        let data = vec![1, 2, 3, 4, 5];
+        let s = format!("{}\n", BASE64.encode(&data));
+        assert_eq!(
+            AccountFile::parse(&s).unwrap(),
+            AccountFile {
+                pubkey: None,
+                owner: None,
+                data,
+            }
+        );
+    }
+}
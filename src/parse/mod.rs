@@ -1,7 +1,9 @@
+mod account_file;
 mod signer;
 mod spl_stake_pool_program;
 mod stake_deposit_auth;
 
+pub use account_file::*;
 pub use signer::*;
 pub use spl_stake_pool_program::*;
 pub use stake_deposit_auth::*;
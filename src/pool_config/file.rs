@@ -1,4 +1,4 @@
-use std::{error::Error, fs::read_to_string, num::NonZeroU32, path::Path};
+use std::{cmp::Ordering, error::Error, fs::read_to_string, num::NonZeroU32, path::Path};
 
 use sanctum_solana_cli_utils::PubkeySrc;
 use sanctum_spl_stake_pool_lib::{
@@ -7,7 +7,7 @@ use sanctum_spl_stake_pool_lib::{
 };
 use serde::{Deserialize, Serialize};
 use solana_readonly_account::{ReadonlyAccountLamports, ReadonlyAccountPubkey};
-use solana_sdk::pubkey::Pubkey;
+use solana_sdk::{pubkey::Pubkey, rent::Rent, stake::state::StakeStateV2};
 use spl_stake_pool_interface::{Fee, FutureEpochFee, StakeStatus, ValidatorStakeInfo};
 
 use crate::SplStakePoolProgram;
@@ -25,6 +25,9 @@ pub struct ConfigRaw {
     pub program: Option<SplStakePoolProgram>,
     pub mint: Option<String>,
     pub token_program: Option<String>,
+    pub name: Option<String>, // token metadata, only used by sync-token-metadata
+    pub symbol: Option<String>, // token metadata, only used by sync-token-metadata
+    pub uri: Option<String>,  // token metadata, only used by sync-token-metadata
     pub pool: Option<String>,
     pub validator_list: Option<String>,
     pub manager: Option<String>,
@@ -62,6 +65,7 @@ pub struct ConfigRaw {
 #[serde(rename_all = "kebab-case")]
 pub struct ValidatorConfigRaw {
     pub vote: String,
+    pub target: Option<ValidatorDelegationTarget>, // only used by sync
     pub active_stake_lamports: Option<u64>,
     pub transient_stake_lamports: Option<u64>,
     pub last_update_epoch: Option<u64>,
@@ -90,6 +94,7 @@ impl ValidatorConfigRaw {
         let validator_seed_suffix = NonZeroU32::new(*validator_seed_suffix);
         Self {
             vote: vote_account_address.to_string(),
+            target: None,
             active_stake_lamports: Some(*active_stake_lamports),
             transient_stake_lamports: Some(*transient_stake_lamports),
             last_update_epoch: Some(*last_update_epoch),
@@ -219,23 +224,151 @@ impl TryFrom<ValidatorDelegationRaw> for ValidatorDelegation {
 #[serde(rename_all = "kebab-case")]
 pub enum ValidatorDelegationTarget {
     Lamports(u64),
+
+    /// Percentage of the pool's [`delegatable_lamports`]
+    Percent(f64),
+
+    /// Share of the pool's [`delegatable_lamports`] left after all lamports and percent targets,
+    /// split among all weight targets in proportion to their weights
+    Weight(u64),
     Remainder,
 }
 
+/// Moves the Remainder entry to the end, preserving the order of all other entries
+// TODO: kinda jank to rely on this sorting behaviour to ensure
+// correct handling of Remainder
+pub fn sort_delegation_scheme(delegation_scheme: &mut [ValidatorDelegation]) {
+    delegation_scheme.sort_by(|a, b| {
+        if matches!(a.target, ValidatorDelegationTarget::Remainder) {
+            Ordering::Greater
+        } else if matches!(b.target, ValidatorDelegationTarget::Remainder) {
+            Ordering::Less
+        } else {
+            // preserve og order
+            Ordering::Equal
+        }
+    });
+}
+
+/// Allowance for floating point error in percent targets that should sum to 100
+const PERCENT_SUM_TOLERANCE: f64 = 1e-9;
+
 pub fn is_delegation_scheme_valid<'a>(
     targets: impl Iterator<Item = &'a ValidatorDelegationTarget>,
 ) -> Result<(), &'static str> {
     let mut has_remainder = false;
+    let mut total_percent = 0.0;
+    let mut total_weight: Option<u64> = None;
     for target in targets {
-        if matches!(target, ValidatorDelegationTarget::Remainder) {
-            if has_remainder {
-                return Err("Can only have at most one validator with target=remainder");
-            } else {
-                has_remainder = true;
+        match target {
+            ValidatorDelegationTarget::Lamports(_) => (),
+            ValidatorDelegationTarget::Percent(percent) => {
+                if !(0.0..=100.0).contains(percent) {
+                    return Err("target=percent must be between 0 and 100");
+                }
+                total_percent += percent;
+            }
+            ValidatorDelegationTarget::Weight(weight) => {
+                total_weight = Some(
+                    total_weight
+                        .unwrap_or_default()
+                        .checked_add(*weight)
+                        .ok_or("Sum of target=weight overflows")?,
+                );
+            }
+            ValidatorDelegationTarget::Remainder => {
+                if has_remainder {
+                    return Err("Can only have at most one validator with target=remainder");
+                } else {
+                    has_remainder = true;
+                }
             }
         }
     }
-    Ok(())
+    if total_percent > 100.0 + PERCENT_SUM_TOLERANCE {
+        return Err("Sum of target=percent must be at most 100");
+    }
+    match total_weight {
+        Some(0) => Err("Sum of target=weight must be more than 0"),
+        Some(_) if has_remainder => {
+            Err("Cannot have both target=remainder and target=weight since both split the remaining stake")
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Lamports of the pool that can be delegated to validators:
+/// `total_lamports` minus the reserve's rent-exempt minimum, which must always stay in the reserve.
+///
+/// Does not depend on the number of validators so that targets resolve to the same amounts
+/// before and after validators are added.
+pub fn delegatable_lamports(total_lamports: u64, rent: &Rent) -> u64 {
+    total_lamports.saturating_sub(rent.minimum_balance(std::mem::size_of::<StakeStateV2>()))
+}
+
+/// Converts all percent and weight targets in a valid delegation scheme into lamports targets.
+///
+/// Must be called with the full delegation scheme, before filtering out any validators,
+/// since weight targets depend on all other targets.
+pub fn resolve_delegation_scheme(
+    delegation_scheme: &[ValidatorDelegation],
+    delegatable_lamports: u64,
+) -> Vec<ValidatorDelegation> {
+    // lamport precision loss is fine for percentages
+    let percent_lamports = |percent: f64| (delegatable_lamports as f64 * percent / 100.0) as u64;
+    let (fixed_lamports, total_weight) = delegation_scheme.iter().fold(
+        (0u64, 0u64),
+        |(fixed, weights), ValidatorDelegation { target, .. }| match target {
+            ValidatorDelegationTarget::Lamports(lamports) => {
+                (fixed.saturating_add(*lamports), weights)
+            }
+            ValidatorDelegationTarget::Percent(percent) => {
+                (fixed.saturating_add(percent_lamports(*percent)), weights)
+            }
+            ValidatorDelegationTarget::Weight(weight) => (fixed, weights.saturating_add(*weight)),
+            ValidatorDelegationTarget::Remainder => (fixed, weights),
+        },
+    );
+    let remaining_lamports = delegatable_lamports.saturating_sub(fixed_lamports);
+    delegation_scheme
+        .iter()
+        .map(|ValidatorDelegation { vote, target }| ValidatorDelegation {
+            vote: *vote,
+            target: match target {
+                ValidatorDelegationTarget::Percent(percent) => {
+                    ValidatorDelegationTarget::Lamports(percent_lamports(*percent))
+                }
+                ValidatorDelegationTarget::Weight(weight) => ValidatorDelegationTarget::Lamports(
+                    // as-safety: result <= remaining_lamports since weight <= total_weight
+                    (u128::from(remaining_lamports) * u128::from(*weight)
+                        / u128::from(total_weight)) as u64,
+                ),
+                t => *t,
+            },
+        })
+        .collect()
+}
+
+/// Used to serialize and deserialize crank config toml files
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct CrankConfigTomlFile<T> {
+    pub crank: T,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct CrankConfigToml {
+    /// Pools to run the update crank for every epoch
+    pub pools: Vec<String>,
+}
+
+impl CrankConfigToml {
+    pub fn read_from_path<P: AsRef<Path>>(path: P) -> Result<Self, std::io::Error> {
+        // toml crate only handles strings, not io::Read lol
+        let s = read_to_string(path)?;
+        let CrankConfigTomlFile { crank } = toml::from_str(&s).map_err(std::io::Error::other)?;
+        Ok(crank)
+    }
 }
 
 #[cfg(test)]
@@ -283,4 +416,122 @@ mod tests {
             toml::to_string_pretty(&SyncDelegationConfigTomlFile { pool }).unwrap()
         )
     }
+
+    #[test]
+    fn deser_example_sync_config() {
+        let example_path = test_fixtures_dir().join("example-sync-config.toml");
+        let res = ConfigRaw::read_from_path(example_path).unwrap();
+        let validators = res.validators.as_ref().unwrap();
+        assert_eq!(validators.len(), 2);
+        assert!(matches!(
+            validators[0].target,
+            Some(ValidatorDelegationTarget::Lamports(100))
+        ));
+        assert!(matches!(
+            validators[1].target,
+            Some(ValidatorDelegationTarget::Remainder)
+        ));
+        is_delegation_scheme_valid(validators.iter().filter_map(|v| v.target.as_ref())).unwrap();
+
+        eprintln!("{}", ConfigTomlFile { pool: &res });
+    }
+
+    #[test]
+    fn deser_percent_weight_targets() {
+        let SyncDelegationConfigTomlFile { pool } = toml::from_str(
+            r#"
+[pool]
+pool = "9hRBuRJs1du6T2GGhW9AtUovidvcinMPm674vHNsgy61"
+
+[[pool.validators]]
+vote = "FnAPJkzf19s87sm24Qhv6bHZMZvZ43gjNUBRgjwXpD4v"
+[pool.validators.target]
+percent = 40
+
+[[pool.validators]]
+vote = "BLADE1qNA1uNjRgER6DtUFf7FU3c1TWLLdpPeEcKatZ2"
+[pool.validators.target]
+weight = 3
+"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            pool.validators[0].target,
+            ValidatorDelegationTarget::Percent(p) if p == 40.0
+        ));
+        assert!(matches!(
+            pool.validators[1].target,
+            ValidatorDelegationTarget::Weight(3)
+        ));
+    }
+
+    #[test]
+    fn delegation_scheme_validity() {
+        use ValidatorDelegationTarget::*;
+
+        for valid in [
+            vec![Percent(40.0), Percent(60.0)],
+            vec![
+                Percent(100.0 / 3.0),
+                Percent(100.0 / 3.0),
+                Percent(100.0 / 3.0),
+            ],
+            vec![Lamports(100), Percent(50.0), Weight(1), Weight(0)],
+            vec![Percent(10.0), Remainder],
+        ] {
+            is_delegation_scheme_valid(valid.iter()).unwrap();
+        }
+        for invalid in [
+            vec![Remainder, Remainder],
+            vec![Percent(40.0), Percent(60.1)],
+            vec![Percent(-1.0)],
+            vec![Percent(f64::NAN)],
+            vec![Weight(0), Weight(0)],
+            vec![Weight(1), Remainder],
+            vec![Weight(u64::MAX), Weight(1)],
+        ] {
+            assert!(
+                is_delegation_scheme_valid(invalid.iter()).is_err(),
+                "{invalid:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn resolve_percent_weight_targets() {
+        use ValidatorDelegationTarget::*;
+
+        let scheme: Vec<ValidatorDelegation> = [Lamports(100), Percent(40.0), Weight(1), Weight(3)]
+            .into_iter()
+            .map(|target| ValidatorDelegation {
+                vote: Pubkey::new_unique(),
+                target,
+            })
+            .collect();
+        let resolved = resolve_delegation_scheme(&scheme, 1_000);
+        let lamports: Vec<u64> = resolved
+            .iter()
+            .map(|ValidatorDelegation { target, .. }| match target {
+                Lamports(l) => *l,
+                t => panic!("unresolved target {t:?}"),
+            })
+            .collect();
+        // 1000 - 100 - 400 = 500 split 1:3
+        assert_eq!(lamports, [100, 400, 125, 375]);
+        assert!(resolved
+            .iter()
+            .zip(scheme.iter())
+            .all(|(r, s)| r.vote == s.vote));
+    }
+
+    #[test]
+    fn delegatable_lamports_excludes_reserve_minimum() {
+        let rent = Rent::default();
+        let stake_acc_rent = rent.minimum_balance(std::mem::size_of::<StakeStateV2>());
+        assert_eq!(
+            delegatable_lamports(10_000_000_000, &rent),
+            10_000_000_000 - stake_acc_rent
+        );
+        assert_eq!(delegatable_lamports(0, &rent), 0);
+    }
 }
@@ -0,0 +1,147 @@
+//! Minimal Metaplex token metadata account layouts and instructions,
+//! just enough to read and update a pool token's name, symbol and uri.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey,
+    pubkey::Pubkey,
+};
+
+pub const METAPLEX_PROGRAM_ID: Pubkey = pubkey!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
+
+const UPDATE_METADATA_ACCOUNT_V2_IX_DISCM: u8 = 15;
+
+/// Size metadata accounts are allocated with on creation
+pub const METAPLEX_METADATA_MAX_LEN: usize = 679;
+
+pub fn find_metaplex_metadata(mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[b"metadata", METAPLEX_PROGRAM_ID.as_ref(), mint.as_ref()],
+        &METAPLEX_PROGRAM_ID,
+    )
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, BorshDeserialize, BorshSerialize)]
+pub struct MetaplexCreator {
+    pub address: Pubkey,
+    pub verified: bool,
+    pub share: u8,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, BorshDeserialize, BorshSerialize)]
+pub struct MetaplexCollection {
+    pub verified: bool,
+    pub key: Pubkey,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, BorshDeserialize, BorshSerialize)]
+pub struct MetaplexUses {
+    pub use_method: u8,
+    pub remaining: u64,
+    pub total: u64,
+}
+
+/// Prefix of the metadata account up to the last field required to rebuild `DataV2`.
+/// The remaining fields are not read.
+#[derive(Clone, Debug, PartialEq, Eq, BorshDeserialize, BorshSerialize)]
+pub struct MetaplexMetadata {
+    pub key: u8,
+    pub update_authority: Pubkey,
+    pub mint: Pubkey,
+    /// null-padded, use [`Self::name`]
+    pub name: String,
+    /// null-padded, use [`Self::symbol`]
+    pub symbol: String,
+    /// null-padded, use [`Self::uri`]
+    pub uri: String,
+    pub seller_fee_basis_points: u16,
+    pub creators: Option<Vec<MetaplexCreator>>,
+    pub primary_sale_happened: bool,
+    pub is_mutable: bool,
+    pub edition_nonce: Option<u8>,
+    pub token_standard: Option<u8>,
+    pub collection: Option<MetaplexCollection>,
+    pub uses: Option<MetaplexUses>,
+}
+
+impl MetaplexMetadata {
+    pub fn deserialize_account_data(mut data: &[u8]) -> std::io::Result<Self> {
+        // account data is zero-padded to a fixed size, so dont use try_from_slice()
+        <Self as BorshDeserialize>::deserialize(&mut data)
+    }
+
+    pub fn name(&self) -> &str {
+        self.name.trim_end_matches('\0')
+    }
+
+    pub fn symbol(&self) -> &str {
+        self.symbol.trim_end_matches('\0')
+    }
+
+    pub fn uri(&self) -> &str {
+        self.uri.trim_end_matches('\0')
+    }
+}
+
+#[derive(Clone, Debug, BorshSerialize)]
+struct DataV2 {
+    name: String,
+    symbol: String,
+    uri: String,
+    seller_fee_basis_points: u16,
+    creators: Option<Vec<MetaplexCreator>>,
+    collection: Option<MetaplexCollection>,
+    uses: Option<MetaplexUses>,
+}
+
+#[derive(Clone, Debug, BorshSerialize)]
+struct UpdateMetadataAccountV2Args {
+    data: Option<DataV2>,
+    update_authority: Option<Pubkey>,
+    primary_sale_happened: Option<bool>,
+    is_mutable: Option<bool>,
+}
+
+/// Sets name, symbol and uri of `curr`, keeping all other fields unchanged.
+/// Must be signed by `curr.update_authority`
+pub fn update_metaplex_metadata_ix(
+    metadata: Pubkey,
+    curr: &MetaplexMetadata,
+    name: String,
+    symbol: String,
+    uri: String,
+) -> std::io::Result<Instruction> {
+    let mut data = vec![UPDATE_METADATA_ACCOUNT_V2_IX_DISCM];
+    UpdateMetadataAccountV2Args {
+        data: Some(DataV2 {
+            name,
+            symbol,
+            uri,
+            seller_fee_basis_points: curr.seller_fee_basis_points,
+            creators: curr.creators.clone(),
+            collection: curr.collection.clone(),
+            uses: curr.uses.clone(),
+        }),
+        update_authority: None,
+        primary_sale_happened: None,
+        is_mutable: None,
+    }
+    .serialize(&mut data)?;
+    Ok(Instruction {
+        program_id: METAPLEX_PROGRAM_ID,
+        accounts: vec![
+            AccountMeta {
+                pubkey: metadata,
+                is_signer: false,
+                is_writable: true,
+            },
+            AccountMeta {
+                pubkey: curr.update_authority,
+                is_signer: true,
+                is_writable: false,
+            },
+        ],
+        data,
+    })
+}
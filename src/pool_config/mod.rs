@@ -1,13 +1,25 @@
 mod create;
 mod file;
+mod metaplex;
+mod plan;
+mod quote;
 mod read;
+mod stats;
 mod sync_delegation;
 mod sync_pool;
+mod sync_token_metadata;
 mod sync_validator_list;
 mod utils;
+mod withdraw_stake;
 
 pub use create::*;
 pub use file::*;
+pub use metaplex::*;
+pub use plan::*;
+pub use quote::*;
+pub use stats::*;
 pub use sync_delegation::*;
 pub use sync_pool::*;
+pub use sync_token_metadata::*;
 pub use sync_validator_list::*;
+pub use withdraw_stake::*;
@@ -0,0 +1,309 @@
+use std::{collections::HashSet, fmt::Display, fs::read_to_string, path::Path};
+
+use sanctum_solana_cli_utils::TxSendMode;
+use serde::{Deserialize, Serialize};
+use solana_sdk::{pubkey::Pubkey, signer::Signer};
+
+use super::{ConfigRaw, SyncDelegationConfigToml};
+
+/// Base fee per signature, excludes priority fees
+pub const LAMPORTS_PER_SIGNATURE: u64 = 5000;
+
+/// Machine-readable summary of all the changes a sync command would make.
+/// Output by `--plan` instead of building and sending any transactions,
+/// and executed by `apply`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Plan {
+    pub pool: String,
+    pub payer: String,
+    pub est_tx_count: u64,
+    pub est_tx_fee_lamports: u64,
+    pub changes: Vec<PlanChange>,
+    pub src: PlanSrc, // put this last so it gets outputted last in toml Serialize
+}
+
+/// The sync command and config file the plan was generated with,
+/// used by `apply` to recompute the changes to check for drift before executing them.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PlanSrc {
+    Sync(ConfigRaw),
+    SyncDelegation(SyncDelegationConfigToml),
+    SyncPool(ConfigRaw),
+    SyncTokenMetadata(ConfigRaw),
+    SyncValidatorList(ConfigRaw),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct PlanChange {
+    pub kind: PlanChangeKind,
+
+    /// The pool attribute or validator vote account being changed
+    pub target: String,
+
+    pub old: Option<String>,
+    pub new: Option<String>,
+
+    /// Authorities that must sign for this change, excluding the payer
+    pub signers: Vec<String>,
+
+    /// Lamports spent from the payer or pool reserve, excluding tx fees
+    pub est_lamport_cost: u64,
+}
+
+impl Display for PlanChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:?} {}: {} -> {}",
+            self.kind,
+            self.target,
+            self.old.as_deref().unwrap_or("-"),
+            self.new.as_deref().unwrap_or("-")
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PlanChangeKind {
+    UpdatePool,
+    PoolParam,
+    TokenMetadata,
+    RemoveValidator,
+    AddValidator,
+    PreferredValidator,
+    DecreaseStake,
+    IncreaseStake,
+
+    /// Delegation change of a validator that can only be computed after the update crank
+    /// or after the validator is added, so its amount is not known yet
+    PendingStake,
+}
+
+impl Plan {
+    pub fn new(pool: &Pubkey, payer: &Pubkey, src: PlanSrc) -> Self {
+        Self {
+            pool: pool.to_string(),
+            payer: payer.to_string(),
+            est_tx_count: 0,
+            est_tx_fee_lamports: 0,
+            changes: vec![],
+            src,
+        }
+    }
+
+    pub fn read_from_path<P: AsRef<Path>>(path: P) -> Result<Self, std::io::Error> {
+        // toml crate only handles strings, not io::Read lol
+        let s = read_to_string(path)?;
+        let PlanTomlFileOwned { plan } = toml::from_str(&s).map_err(std::io::Error::other)?;
+        Ok(plan)
+    }
+
+    /// Adds the base fees of the transactions required to send `n_ixs` instructions
+    /// batched `max_ixs_per_tx` per tx, each signed by all of `signers_maybe_dup`
+    pub fn add_txs(
+        &mut self,
+        n_ixs: usize,
+        max_ixs_per_tx: usize,
+        signers_maybe_dup: &[&dyn Signer],
+    ) {
+        let n_txs = n_ixs.div_ceil(max_ixs_per_tx);
+        let n_signers = signers_maybe_dup
+            .iter()
+            .map(|s| s.pubkey())
+            .collect::<HashSet<_>>()
+            .len();
+        // as-safety: usize always fits in u64
+        self.est_tx_count += n_txs as u64;
+        self.est_tx_fee_lamports += n_txs as u64 * n_signers as u64 * LAMPORTS_PER_SIGNATURE;
+    }
+
+    /// Adds the update crank as a change if the pool has not yet been updated for `curr_epoch`.
+    ///
+    /// Tx fees are not included since they depend on the number of validators that need updating.
+    pub fn add_update_pool_if_needed(&mut self, last_update_epoch: u64, curr_epoch: u64) {
+        if last_update_epoch >= curr_epoch {
+            return;
+        }
+        self.changes.push(PlanChange {
+            kind: PlanChangeKind::UpdatePool,
+            target: "last update epoch".to_owned(),
+            old: Some(last_update_epoch.to_string()),
+            new: Some(curr_epoch.to_string()),
+            signers: vec![],
+            est_lamport_cost: 0,
+        });
+    }
+}
+
+/// Owned version of [`PlanTomlFile`].
+/// Used to deserialize input plan toml files
+#[derive(Debug, Deserialize, Serialize)]
+struct PlanTomlFileOwned {
+    pub plan: Plan,
+}
+
+/// Used to serialize output plan tomls
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct PlanTomlFile<'a> {
+    pub plan: &'a Plan,
+}
+
+impl std::fmt::Display for PlanTomlFile<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&toml::to_string_pretty(self).unwrap())
+    }
+}
+
+/// What a sync command does with the changes it computed
+#[derive(Clone, Copy, Debug)]
+pub enum SyncMode<'a> {
+    /// Build and send txs for the changes
+    Send,
+
+    /// Output a plan toml of the changes to stdout instead of building and sending any txs
+    Plan,
+
+    /// Build and send txs for the changes only if they're exactly the planned changes
+    Apply(&'a [PlanChange]),
+}
+
+impl SyncMode<'_> {
+    pub const fn from_plan_flag(plan: bool) -> Self {
+        if plan {
+            Self::Plan
+        } else {
+            Self::Send
+        }
+    }
+
+    /// Plan mode doesnt sign anything, so allow pubkeys for signers
+    pub const fn signer_send_mode(&self, send_mode: TxSendMode) -> TxSendMode {
+        match self {
+            Self::Plan => TxSendMode::DumpMsg,
+            Self::Send | Self::Apply(_) => send_mode,
+        }
+    }
+
+    /// Returns true if the sync command should return without sending anything.
+    /// `plan` is not evaluated in send mode.
+    ///
+    /// Panics in apply mode if the changes in `plan` are not exactly the planned changes
+    pub fn handle_plan(&self, plan: impl FnOnce() -> Plan) -> bool {
+        match self {
+            Self::Send => false,
+            Self::Plan => {
+                println!("{}", PlanTomlFile { plan: &plan() });
+                true
+            }
+            Self::Apply(planned) => {
+                check_plan_drift(planned, &plan().changes);
+                false
+            }
+        }
+    }
+}
+
+/// Panics if `changes` are not exactly the `planned` changes, in any order
+pub fn check_plan_drift(planned: &[PlanChange], changes: &[PlanChange]) {
+    if sorted(changes) == sorted(planned) {
+        return;
+    }
+    for change in planned.iter().filter(|c| !changes.contains(c)) {
+        eprintln!("Planned change no longer valid: {change}");
+    }
+    for change in changes.iter().filter(|c| !planned.contains(c)) {
+        eprintln!("Unplanned change: {change}");
+    }
+    panic!("On-chain state has drifted from plan, aborting");
+}
+
+fn sorted(changes: &[PlanChange]) -> Vec<&PlanChange> {
+    let mut res: Vec<_> = changes.iter().collect();
+    res.sort();
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::signature::Keypair;
+
+    use super::*;
+
+    #[test]
+    fn add_txs_dedups_signers() {
+        let payer = Keypair::new();
+        let staker = Keypair::new();
+        let mut plan = Plan::new(
+            &Pubkey::new_unique(),
+            &payer.pubkey(),
+            PlanSrc::SyncPool(ConfigRaw::default()),
+        );
+        plan.add_txs(7, 3, &[&payer, &staker]);
+        plan.add_txs(1, usize::MAX, &[&payer, &payer]);
+        plan.add_txs(0, usize::MAX, &[&payer, &staker]);
+        assert_eq!(plan.est_tx_count, 4);
+        assert_eq!(plan.est_tx_fee_lamports, 7 * LAMPORTS_PER_SIGNATURE);
+    }
+
+    #[test]
+    fn plan_toml_round_trip() {
+        let pool = Pubkey::new_unique();
+        let mut plan = Plan::new(
+            &pool,
+            &Pubkey::new_unique(),
+            PlanSrc::SyncPool(ConfigRaw {
+                pool: Some(pool.to_string()),
+                ..Default::default()
+            }),
+        );
+        plan.add_update_pool_if_needed(1, 2);
+        plan.changes.push(PlanChange {
+            kind: PlanChangeKind::AddValidator,
+            target: Pubkey::new_unique().to_string(),
+            old: None,
+            new: None,
+            signers: vec![Pubkey::new_unique().to_string()],
+            est_lamport_cost: 1_002_282_880,
+        });
+        let s = PlanTomlFile { plan: &plan }.to_string();
+        eprintln!("{s}");
+        let PlanTomlFileOwned { plan: deser } = toml::from_str(&s).unwrap();
+        assert_eq!(deser.changes, plan.changes);
+        match deser.src {
+            PlanSrc::SyncPool(ConfigRaw { pool: src_pool, .. }) => {
+                assert_eq!(src_pool, Some(pool.to_string()))
+            }
+            _ => panic!("wrong plan src"),
+        }
+    }
+
+    fn add_validator_change(vote: &Pubkey) -> PlanChange {
+        PlanChange {
+            kind: PlanChangeKind::AddValidator,
+            target: vote.to_string(),
+            old: None,
+            new: None,
+            signers: vec![],
+            est_lamport_cost: 0,
+        }
+    }
+
+    #[test]
+    fn plan_drift_ignores_order() {
+        let planned = [0; 3].map(|_| add_validator_change(&Pubkey::new_unique()));
+        let mut changes = planned.clone();
+        changes.reverse();
+        check_plan_drift(&planned, &changes);
+    }
+
+    #[test]
+    #[should_panic(expected = "drifted")]
+    fn plan_drift_counts_duplicates() {
+        let change = add_validator_change(&Pubkey::new_unique());
+        check_plan_drift(&[change.clone(), change.clone()], &[change]);
+    }
+}
@@ -0,0 +1,335 @@
+//! Pure deposit/withdraw quote calculations, ported from the spl stake pool program
+//! so that they round the same way as onchain
+
+use std::fmt::Display;
+
+use sanctum_solana_cli_utils::TokenAmt;
+use serde::Serialize;
+use solana_sdk::pubkey::Pubkey;
+use spl_stake_pool_interface::{Fee, FutureEpochFee, StakePool};
+
+/// Snapshot of the [`StakePool`] fields that affect quotes,
+/// with fee changes scheduled for the current epoch applied
+/// if the pool has not been updated for it yet.
+#[derive(Clone, Debug)]
+pub struct PoolQuoter {
+    pub total_lamports: u64,
+    pub pool_token_supply: u64,
+    pub manager_fee_account: Pubkey,
+    pub sol_deposit_fee: Fee,
+    pub stake_deposit_fee: Fee,
+    pub sol_referral_fee: u8,
+    pub stake_referral_fee: u8,
+    pub sol_withdrawal_fee: Fee,
+    pub stake_withdrawal_fee: Fee,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct DepositQuote {
+    /// Total pool tokens minted by the deposit, including fees
+    pub pool_tokens_minted: u64,
+    pub deposit_fee: u64,
+    /// Portion of `deposit_fee` paid to the referrer
+    pub referral_fee: u64,
+    /// Portion of `deposit_fee` paid to the manager fee account
+    pub manager_fee: u64,
+    /// Pool tokens minted to the depositor, excluding any referral fee paid back to the depositor
+    pub pool_tokens_out: u64,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct WithdrawQuote {
+    pub pool_tokens_in: u64,
+    pub withdrawal_fee: u64,
+    /// Withdrawal fees are transferred to the manager fee account in full
+    pub manager_fee: u64,
+    pub pool_tokens_burnt: u64,
+    pub lamports_out: u64,
+}
+
+impl PoolQuoter {
+    pub fn new(
+        StakePool {
+            total_lamports,
+            pool_token_supply,
+            last_update_epoch,
+            manager_fee_account,
+            sol_deposit_fee,
+            stake_deposit_fee,
+            sol_referral_fee,
+            stake_referral_fee,
+            sol_withdrawal_fee,
+            next_sol_withdrawal_fee,
+            stake_withdrawal_fee,
+            next_stake_withdrawal_fee,
+            ..
+        }: &StakePool,
+        current_epoch: u64,
+    ) -> Self {
+        // UpdateStakePoolBalance replaces the withdrawal fees with
+        // FutureEpochFee::One when crossing an epoch boundary
+        let is_stale = *last_update_epoch < current_epoch;
+        let [sol_withdrawal_fee, stake_withdrawal_fee] = [
+            (sol_withdrawal_fee, next_sol_withdrawal_fee),
+            (stake_withdrawal_fee, next_stake_withdrawal_fee),
+        ]
+        .map(|(curr, next)| match next {
+            FutureEpochFee::One { fee } if is_stale => fee.clone(),
+            _ => curr.clone(),
+        });
+        Self {
+            total_lamports: *total_lamports,
+            pool_token_supply: *pool_token_supply,
+            manager_fee_account: *manager_fee_account,
+            sol_deposit_fee: sol_deposit_fee.clone(),
+            stake_deposit_fee: stake_deposit_fee.clone(),
+            sol_referral_fee: *sol_referral_fee,
+            stake_referral_fee: *stake_referral_fee,
+            sol_withdrawal_fee,
+            stake_withdrawal_fee,
+        }
+    }
+
+    pub fn deposit_sol(&self, lamports: u64) -> DepositQuote {
+        let pool_tokens_minted = self.pool_tokens_for_deposit(lamports);
+        let deposit_fee = apply_fee(&self.sol_deposit_fee, pool_tokens_minted);
+        let referral_fee = referral_fee(deposit_fee, self.sol_referral_fee);
+        DepositQuote {
+            pool_tokens_minted,
+            deposit_fee,
+            referral_fee,
+            manager_fee: deposit_fee - referral_fee,
+            pool_tokens_out: pool_tokens_minted.saturating_sub(deposit_fee),
+        }
+    }
+
+    /// `stake_lamports` is the delegated stake of the deposited stake account,
+    /// `total_lamports` is its account balance. The difference is charged as a SOL deposit.
+    pub fn deposit_stake(&self, stake_lamports: u64, total_lamports: u64) -> DepositQuote {
+        let pool_tokens_minted = self.pool_tokens_for_deposit(total_lamports);
+        let pool_tokens_from_stake = self.pool_tokens_for_deposit(stake_lamports);
+        let pool_tokens_from_sol = pool_tokens_minted.saturating_sub(pool_tokens_from_stake);
+        let deposit_fee = apply_fee(&self.stake_deposit_fee, pool_tokens_from_stake)
+            .saturating_add(apply_fee(&self.sol_deposit_fee, pool_tokens_from_sol));
+        let referral_fee = referral_fee(deposit_fee, self.stake_referral_fee);
+        DepositQuote {
+            pool_tokens_minted,
+            deposit_fee,
+            referral_fee,
+            manager_fee: deposit_fee - referral_fee,
+            pool_tokens_out: pool_tokens_minted.saturating_sub(deposit_fee),
+        }
+    }
+
+    pub fn withdraw_sol(&self, pool_tokens_in: u64, burn_from: &Pubkey) -> WithdrawQuote {
+        self.withdraw(&self.sol_withdrawal_fee, pool_tokens_in, burn_from)
+    }
+
+    pub fn withdraw_stake(&self, pool_tokens_in: u64, burn_from: &Pubkey) -> WithdrawQuote {
+        self.withdraw(&self.stake_withdrawal_fee, pool_tokens_in, burn_from)
+    }
+
+    fn withdraw(&self, fee: &Fee, pool_tokens_in: u64, burn_from: &Pubkey) -> WithdrawQuote {
+        // manager fee account is exempt from withdrawal fees
+        let withdrawal_fee = if *burn_from == self.manager_fee_account {
+            0
+        } else {
+            apply_fee(fee, pool_tokens_in)
+        };
+        let pool_tokens_burnt = pool_tokens_in.saturating_sub(withdrawal_fee);
+        WithdrawQuote {
+            pool_tokens_in,
+            withdrawal_fee,
+            manager_fee: withdrawal_fee,
+            pool_tokens_burnt,
+            lamports_out: self.lamports_for_withdraw(pool_tokens_burnt),
+        }
+    }
+
+    /// `StakePool::get_lamports_per_pool_token`, rounds up
+    pub fn lamports_per_pool_token(&self) -> u64 {
+        if self.pool_token_supply == 0 {
+            return 1;
+        }
+        let supply = u128::from(self.pool_token_supply);
+        ((u128::from(self.total_lamports) + supply - 1) / supply)
+            .try_into()
+            .unwrap_or(u64::MAX)
+    }
+
+    /// Max pool tokens that can be redeemed via WithdrawStake
+    /// for at most `max_lamports_out` lamports
+    pub fn max_withdraw_stake_tokens(&self, max_lamports_out: u64, burn_from: &Pubkey) -> u64 {
+        // binary search: lamports_out is non-decreasing with pool_tokens_in
+        let (mut lo, mut hi) = (0, self.pool_token_supply);
+        while lo < hi {
+            let mid = lo + (hi - lo + 1) / 2;
+            if self.withdraw_stake(mid, burn_from).lamports_out <= max_lamports_out {
+                lo = mid;
+            } else {
+                hi = mid - 1;
+            }
+        }
+        lo
+    }
+
+    /// `StakePool::calc_pool_tokens_for_deposit`
+    fn pool_tokens_for_deposit(&self, lamports: u64) -> u64 {
+        if self.total_lamports == 0 || self.pool_token_supply == 0 {
+            return lamports;
+        }
+        (u128::from(lamports) * u128::from(self.pool_token_supply)
+            / u128::from(self.total_lamports))
+        .try_into()
+        .unwrap_or(u64::MAX)
+    }
+
+    /// `StakePool::calc_lamports_withdraw_amount`
+    fn lamports_for_withdraw(&self, pool_tokens: u64) -> u64 {
+        let numerator = u128::from(pool_tokens) * u128::from(self.total_lamports);
+        let denominator = u128::from(self.pool_token_supply);
+        if numerator < denominator || denominator == 0 {
+            return 0;
+        }
+        (numerator / denominator).try_into().unwrap_or(u64::MAX)
+    }
+}
+
+/// `Fee::apply`, rounds up
+pub(crate) fn apply_fee(
+    Fee {
+        denominator,
+        numerator,
+    }: &Fee,
+    amt: u64,
+) -> u64 {
+    if *denominator == 0 {
+        return 0;
+    }
+    let denominator = u128::from(*denominator);
+    ((u128::from(amt) * u128::from(*numerator) + denominator - 1) / denominator)
+        .try_into()
+        .unwrap_or(u64::MAX)
+}
+
+/// `StakePool::calc_pool_tokens_*_referral_fee`, rounds down
+fn referral_fee(deposit_fee: u64, referral_fee_pct: u8) -> u64 {
+    // as-safety: result <= deposit_fee if referral_fee_pct <= 100
+    (u128::from(deposit_fee) * u128::from(referral_fee_pct) / 100) as u64
+}
+
+fn lst_display(amt: u64) -> TokenAmt {
+    TokenAmt { amt, decimals: 9 }
+}
+
+impl Display for DepositQuote {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} pool tokens out ({} minted, {} deposit fee: {} to manager, {} to referrer)",
+            lst_display(self.pool_tokens_out),
+            lst_display(self.pool_tokens_minted),
+            lst_display(self.deposit_fee),
+            lst_display(self.manager_fee),
+            lst_display(self.referral_fee),
+        )
+    }
+}
+
+impl Display for WithdrawQuote {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} SOL out ({} pool tokens in, {} withdrawal fee to manager, {} burnt)",
+            lst_display(self.lamports_out),
+            lst_display(self.pool_tokens_in),
+            lst_display(self.withdrawal_fee),
+            lst_display(self.pool_tokens_burnt),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sanctum_spl_stake_pool_lib::ZERO_FEE;
+
+    use super::*;
+
+    fn quoter(total_lamports: u64, pool_token_supply: u64) -> PoolQuoter {
+        PoolQuoter {
+            total_lamports,
+            pool_token_supply,
+            manager_fee_account: Pubkey::new_unique(),
+            sol_deposit_fee: ZERO_FEE,
+            stake_deposit_fee: ZERO_FEE,
+            sol_referral_fee: 0,
+            stake_referral_fee: 0,
+            sol_withdrawal_fee: ZERO_FEE,
+            stake_withdrawal_fee: ZERO_FEE,
+        }
+    }
+
+    #[test]
+    fn deposit_sol_fees_round_up_referral_rounds_down() {
+        let mut q = quoter(1_100, 1_000);
+        q.sol_deposit_fee = Fee {
+            denominator: 1_000,
+            numerator: 3,
+        };
+        q.sol_referral_fee = 50;
+        let quote = q.deposit_sol(1_000);
+        // 1000 * 1000 / 1100 = 909.09.. -> 909
+        assert_eq!(quote.pool_tokens_minted, 909);
+        // 909 * 3 / 1000 = 2.727 -> 3
+        assert_eq!(quote.deposit_fee, 3);
+        // 3 * 50 / 100 = 1.5 -> 1
+        assert_eq!(quote.referral_fee, 1);
+        assert_eq!(quote.manager_fee, 2);
+        assert_eq!(quote.pool_tokens_out, 906);
+    }
+
+    #[test]
+    fn deposit_stake_rent_charged_as_sol_deposit() {
+        let mut q = quoter(1_000, 1_000);
+        q.stake_deposit_fee = Fee {
+            denominator: 100,
+            numerator: 1,
+        };
+        q.sol_deposit_fee = Fee {
+            denominator: 10,
+            numerator: 1,
+        };
+        let quote = q.deposit_stake(1_000, 1_010);
+        assert_eq!(quote.pool_tokens_minted, 1_010);
+        // 10 stake fee + 1 sol fee
+        assert_eq!(quote.deposit_fee, 11);
+        assert_eq!(quote.pool_tokens_out, 999);
+    }
+
+    #[test]
+    fn withdraw_fee_exempt_for_manager_fee_account() {
+        let mut q = quoter(2_000, 1_000);
+        q.stake_withdrawal_fee = Fee {
+            denominator: 1_000,
+            numerator: 1,
+        };
+        let quote = q.withdraw_stake(500, &Pubkey::new_unique());
+        assert_eq!(quote.withdrawal_fee, 1);
+        assert_eq!(quote.pool_tokens_burnt, 499);
+        assert_eq!(quote.lamports_out, 998);
+
+        let manager_fee_account = q.manager_fee_account;
+        let quote = q.withdraw_stake(500, &manager_fee_account);
+        assert_eq!(quote.withdrawal_fee, 0);
+        assert_eq!(quote.lamports_out, 1_000);
+    }
+
+    #[test]
+    fn empty_pool_one_to_one() {
+        let q = quoter(0, 0);
+        assert_eq!(q.deposit_sol(1_000).pool_tokens_out, 1_000);
+        assert_eq!(q.withdraw_sol(1_000, &Pubkey::new_unique()).lamports_out, 0);
+    }
+}
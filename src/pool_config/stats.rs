@@ -0,0 +1,353 @@
+//! Exchange rate, yield and fee metrics derived from [`StakePool`] state
+
+use serde::Serialize;
+use solana_sdk::{clock::Clock, epoch_schedule::EpochSchedule};
+use spl_stake_pool_interface::{Fee, StakePool};
+
+use super::apply_fee;
+
+/// 365.25 days
+const SECONDS_PER_YEAR: f64 = 31_557_600.0;
+
+/// Fallback for when slot duration cannot be derived from the clock,
+/// e.g. at the very first slot of an epoch
+const DEFAULT_SECONDS_PER_SLOT: f64 = 0.4;
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct PoolStats {
+    pub current_epoch: u64,
+    pub last_update_epoch: u64,
+    pub total_lamports: u64,
+    pub pool_token_supply: u64,
+
+    /// SOL per pool token
+    pub exchange_rate: Option<f64>,
+
+    /// SOL per pool token before the pool's last update
+    pub last_epoch_exchange_rate: Option<f64>,
+
+    /// Change in exchange rate from `last_epoch_exchange_rate` to `exchange_rate`, in %
+    pub epoch_rate_change_pct: Option<f64>,
+
+    /// Estimated from the average slot duration of the current epoch so far
+    pub epoch_duration_secs: f64,
+
+    /// `epoch_rate_change_pct` compounded over a year of epochs of `epoch_duration_secs`, in %
+    pub apy_pct: Option<f64>,
+    pub epoch_fee: Fee,
+
+    /// Staking rewards of the last update, before fees.
+    /// Derived from the exchange rate change so that deposits and withdrawals
+    /// made since the update are not counted
+    pub epoch_rewards_lamports: u64,
+
+    /// Portion of `epoch_rewards_lamports` taken by the manager
+    pub manager_fee_lamports: u64,
+
+    /// Pool tokens minted to the manager fee account for `manager_fee_lamports`
+    pub manager_fee_pool_tokens: u64,
+}
+
+impl PoolStats {
+    pub fn new(
+        StakePool {
+            total_lamports,
+            pool_token_supply,
+            last_update_epoch,
+            last_epoch_total_lamports,
+            last_epoch_pool_token_supply,
+            epoch_fee,
+            ..
+        }: &StakePool,
+        clock: &Clock,
+        epoch_schedule: &EpochSchedule,
+    ) -> Self {
+        let exchange_rate = sol_per_pool_token(*total_lamports, *pool_token_supply);
+        let last_epoch_exchange_rate =
+            sol_per_pool_token(*last_epoch_total_lamports, *last_epoch_pool_token_supply);
+        let epoch_rate_change = match (exchange_rate, last_epoch_exchange_rate) {
+            (Some(curr), Some(last)) => Some(curr / last - 1.0),
+            _ => None,
+        };
+        let epoch_duration_secs = est_epoch_duration_secs(clock, epoch_schedule);
+
+        // UpdateStakePoolBalance takes the epoch fee out of the rewards,
+        // so holders of last_epoch_pool_token_supply only get the rewards after fees
+        let epoch_rewards_lamports = rewards_before_fee(
+            epoch_fee,
+            holder_rewards_lamports(
+                *total_lamports,
+                *pool_token_supply,
+                *last_epoch_total_lamports,
+                *last_epoch_pool_token_supply,
+            ),
+        );
+        let manager_fee_lamports = apply_fee(epoch_fee, epoch_rewards_lamports);
+        let manager_fee_pool_tokens = epoch_fee_pool_tokens(
+            *last_epoch_pool_token_supply,
+            last_epoch_total_lamports.saturating_add(epoch_rewards_lamports),
+            epoch_rewards_lamports,
+            manager_fee_lamports,
+        );
+
+        Self {
+            current_epoch: clock.epoch,
+            last_update_epoch: *last_update_epoch,
+            total_lamports: *total_lamports,
+            pool_token_supply: *pool_token_supply,
+            exchange_rate,
+            last_epoch_exchange_rate,
+            epoch_rate_change_pct: epoch_rate_change.map(|r| r * 100.0),
+            epoch_duration_secs,
+            apy_pct: epoch_rate_change.map(|r| annualize(r, epoch_duration_secs) * 100.0),
+            epoch_fee: epoch_fee.clone(),
+            epoch_rewards_lamports,
+            manager_fee_lamports,
+            manager_fee_pool_tokens,
+        }
+    }
+}
+
+/// SOL per pool token, `None` if the pool has no tokens
+pub(crate) fn sol_per_pool_token(total_lamports: u64, pool_token_supply: u64) -> Option<f64> {
+    if pool_token_supply == 0 {
+        return None;
+    }
+    // both have 9 decimals so the ratio of atomics is the ratio of UI amounts
+    Some(total_lamports as f64 / pool_token_supply as f64)
+}
+
+fn est_epoch_duration_secs(
+    Clock {
+        slot,
+        epoch,
+        epoch_start_timestamp,
+        unix_timestamp,
+        ..
+    }: &Clock,
+    epoch_schedule: &EpochSchedule,
+) -> f64 {
+    let slots_elapsed = slot.saturating_sub(epoch_schedule.get_first_slot_in_epoch(*epoch));
+    let secs_elapsed = unix_timestamp.saturating_sub(*epoch_start_timestamp);
+    let secs_per_slot = if slots_elapsed == 0 || secs_elapsed <= 0 {
+        DEFAULT_SECONDS_PER_SLOT
+    } else {
+        secs_elapsed as f64 / slots_elapsed as f64
+    };
+    secs_per_slot * epoch_schedule.get_slots_in_epoch(*epoch) as f64
+}
+
+/// Compounds `epoch_rate_change` over a year of epochs of `epoch_duration_secs`
+fn annualize(epoch_rate_change: f64, epoch_duration_secs: f64) -> f64 {
+    (1.0 + epoch_rate_change).powf(SECONDS_PER_YEAR / epoch_duration_secs) - 1.0
+}
+
+/// Exchange rate change over the last update times `last_epoch_pool_token_supply`,
+/// i.e. the rewards after fees of the pool tokens that existed before the update.
+///
+/// Deposits and withdrawals since the update do not change the exchange rate,
+/// unlike `total_lamports - last_epoch_total_lamports`
+fn holder_rewards_lamports(
+    total_lamports: u64,
+    pool_token_supply: u64,
+    last_epoch_total_lamports: u64,
+    last_epoch_pool_token_supply: u64,
+) -> u64 {
+    if pool_token_supply == 0 {
+        return 0;
+    }
+    let last_epoch_tokens_value = u128::from(total_lamports)
+        * u128::from(last_epoch_pool_token_supply)
+        / u128::from(pool_token_supply);
+    u64::try_from(last_epoch_tokens_value)
+        .unwrap_or(u64::MAX)
+        .saturating_sub(last_epoch_total_lamports)
+}
+
+/// Rewards before `epoch_fee` was taken out of them, given the rewards after fees.
+///
+/// Returns `holder_rewards_lamports` for fees >= 100%, since rewards after fees are always 0 then
+fn rewards_before_fee(
+    Fee {
+        denominator,
+        numerator,
+    }: &Fee,
+    holder_rewards_lamports: u64,
+) -> u64 {
+    if *denominator == 0 || numerator >= denominator {
+        return holder_rewards_lamports;
+    }
+    (u128::from(holder_rewards_lamports) * u128::from(*denominator)
+        / u128::from(denominator - numerator))
+    .try_into()
+    .unwrap_or(u64::MAX)
+}
+
+/// `StakePool::calc_epoch_fee_amount`, with the pool's pre-update total lamports
+/// being `total_lamports - reward_lamports`
+fn epoch_fee_pool_tokens(
+    last_epoch_pool_token_supply: u64,
+    total_lamports: u64,
+    reward_lamports: u64,
+    fee_lamports: u64,
+) -> u64 {
+    if reward_lamports == 0 {
+        return 0;
+    }
+    if total_lamports == fee_lamports || last_epoch_pool_token_supply == 0 {
+        return reward_lamports;
+    }
+    (u128::from(last_epoch_pool_token_supply) * u128::from(fee_lamports)
+        / u128::from(total_lamports - fee_lamports))
+    .try_into()
+    .unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use sanctum_spl_stake_pool_lib::ZERO_FEE;
+    use solana_sdk::pubkey::Pubkey;
+    use spl_stake_pool_interface::{AccountType, FutureEpochFee, Lockup};
+
+    use super::*;
+
+    fn stake_pool(
+        total_lamports: u64,
+        pool_token_supply: u64,
+        last_epoch_total_lamports: u64,
+        last_epoch_pool_token_supply: u64,
+        epoch_fee: Fee,
+    ) -> StakePool {
+        StakePool {
+            total_lamports,
+            pool_token_supply,
+            last_epoch_total_lamports,
+            last_epoch_pool_token_supply,
+            epoch_fee,
+            last_update_epoch: 100,
+            // dont cares
+            account_type: AccountType::StakePool,
+            manager: Pubkey::default(),
+            staker: Pubkey::default(),
+            stake_deposit_authority: Pubkey::default(),
+            stake_withdraw_bump_seed: 0,
+            validator_list: Pubkey::default(),
+            reserve_stake: Pubkey::default(),
+            pool_mint: Pubkey::default(),
+            manager_fee_account: Pubkey::default(),
+            token_program: Pubkey::default(),
+            lockup: Lockup {
+                unix_timestamp: 0,
+                epoch: 0,
+                custodian: Pubkey::default(),
+            },
+            next_epoch_fee: FutureEpochFee::None,
+            preferred_deposit_validator_vote_address: None,
+            preferred_withdraw_validator_vote_address: None,
+            stake_deposit_fee: ZERO_FEE,
+            stake_withdrawal_fee: ZERO_FEE,
+            next_stake_withdrawal_fee: FutureEpochFee::None,
+            stake_referral_fee: 0,
+            sol_deposit_authority: None,
+            sol_deposit_fee: ZERO_FEE,
+            sol_referral_fee: 0,
+            sol_withdraw_authority: None,
+            sol_withdrawal_fee: ZERO_FEE,
+            next_sol_withdrawal_fee: FutureEpochFee::None,
+        }
+    }
+
+    /// 2 days into a 432_000 slot epoch at exactly 0.5s per slot
+    fn clock_and_schedule() -> (Clock, EpochSchedule) {
+        let epoch_schedule = EpochSchedule::without_warmup();
+        let epoch = 100;
+        let first_slot = epoch_schedule.get_first_slot_in_epoch(epoch);
+        let clock = Clock {
+            slot: first_slot + 345_600,
+            epoch,
+            epoch_start_timestamp: 1_000_000,
+            unix_timestamp: 1_000_000 + 172_800,
+            leader_schedule_epoch: epoch + 1,
+        };
+        (clock, epoch_schedule)
+    }
+
+    #[test]
+    fn epoch_duration_from_clock() {
+        let (clock, epoch_schedule) = clock_and_schedule();
+        assert_eq!(est_epoch_duration_secs(&clock, &epoch_schedule), 216_000.0);
+
+        let clock = Clock {
+            slot: epoch_schedule.get_first_slot_in_epoch(clock.epoch),
+            ..clock
+        };
+        assert_eq!(
+            est_epoch_duration_secs(&clock, &epoch_schedule),
+            432_000.0 * DEFAULT_SECONDS_PER_SLOT
+        );
+    }
+
+    #[test]
+    fn stats_with_epoch_fee() {
+        let (clock, epoch_schedule) = clock_and_schedule();
+        // 1000 SOL pool earning 1 SOL with a 10% epoch fee
+        let stats = PoolStats::new(
+            &stake_pool(
+                1_001_000_000_000,
+                1_000_099_910_080,
+                1_000_000_000_000,
+                1_000_000_000_000,
+                Fee {
+                    denominator: 10,
+                    numerator: 1,
+                },
+            ),
+            &clock,
+            &epoch_schedule,
+        );
+        assert_eq!(stats.epoch_rewards_lamports, 1_000_000_000);
+        assert_eq!(stats.manager_fee_lamports, 100_000_000);
+        assert_eq!(stats.manager_fee_pool_tokens, 99_910_080);
+        // holders get 0.09% of the 0.1%
+        let change = stats.epoch_rate_change_pct.unwrap();
+        assert!((change - 0.09).abs() < 1e-6, "{change}");
+        let epochs_per_year = SECONDS_PER_YEAR / 216_000.0;
+        let expected_apy = (1.0009f64.powf(epochs_per_year) - 1.0) * 100.0;
+        assert!((stats.apy_pct.unwrap() - expected_apy).abs() < 1e-6);
+    }
+
+    #[test]
+    fn stats_exclude_deposit_after_update() {
+        let (clock, epoch_schedule) = clock_and_schedule();
+        // same pool as stats_with_epoch_fee, then 100 SOL deposited after the update
+        let stats = PoolStats::new(
+            &stake_pool(
+                1_101_000_000_000,
+                1_100_009_991_007,
+                1_000_000_000_000,
+                1_000_000_000_000,
+                Fee {
+                    denominator: 10,
+                    numerator: 1,
+                },
+            ),
+            &clock,
+            &epoch_schedule,
+        );
+        assert_eq!(stats.epoch_rewards_lamports, 1_000_000_000);
+        assert_eq!(stats.manager_fee_lamports, 100_000_000);
+        assert_eq!(stats.manager_fee_pool_tokens, 99_910_080);
+        let change = stats.epoch_rate_change_pct.unwrap();
+        assert!((change - 0.09).abs() < 1e-6, "{change}");
+    }
+
+    #[test]
+    fn stats_empty_pool() {
+        let (clock, epoch_schedule) = clock_and_schedule();
+        let stats = PoolStats::new(&stake_pool(0, 0, 0, 0, ZERO_FEE), &clock, &epoch_schedule);
+        assert_eq!(stats.exchange_rate, None);
+        assert_eq!(stats.apy_pct, None);
+        assert_eq!(stats.manager_fee_pool_tokens, 0);
+    }
+}
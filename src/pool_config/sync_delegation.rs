@@ -1,11 +1,11 @@
 use std::cmp::Ordering;
 
-use sanctum_solana_cli_utils::TokenAmt;
 use sanctum_spl_stake_pool_lib::{
     FindEphemeralStakeAccount, FindEphemeralStakeAccountArgs, FindTransientStakeAccount,
     FindTransientStakeAccountArgs, FindValidatorStakeAccount, FindWithdrawAuthority,
     MIN_ACTIVE_STAKE,
 };
+use serde::{ser::SerializeStruct, Serialize, Serializer};
 use solana_sdk::{
     instruction::Instruction,
     pubkey::Pubkey,
@@ -22,7 +22,10 @@ use spl_stake_pool_interface::{
     ValidatorStakeInfo,
 };
 
-use crate::pool_config::utils::lamports_for_new_vsa;
+use crate::{
+    pool_config::{utils::lamports_for_new_vsa, PlanChange, PlanChangeKind},
+    OutputFormat,
+};
 
 /// All generated ixs must be signed by staker only.
 #[derive(Debug)]
@@ -66,6 +69,37 @@ pub enum ValidatorDelegationChangeTy {
     ValidatorBeingRemoved,
 }
 
+/// Serialized as `{ vote, change, lamports, shortfall }`,
+/// `lamports` being the amount of stake increased or decreased
+impl Serialize for ValidatorDelegationChange {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let (change, lamports, shortfall) = match self.ty {
+            ValidatorDelegationChangeTy::DecreaseStake(dec) => ("decrease-stake", Some(dec), None),
+            ValidatorDelegationChangeTy::IncreaseStake(inc) => ("increase-stake", Some(inc), None),
+            ValidatorDelegationChangeTy::PartialIncreaseStake {
+                increase,
+                shortfall,
+            } => ("partial-increase-stake", Some(increase), Some(shortfall)),
+            ValidatorDelegationChangeTy::InsufficientReserveLamports => {
+                ("insufficient-reserve-lamports", None, None)
+            }
+            ValidatorDelegationChangeTy::NoChange => ("no-change", None, None),
+            ValidatorDelegationChangeTy::TransientWrongState => {
+                ("transient-wrong-state", None, None)
+            }
+            ValidatorDelegationChangeTy::ValidatorBeingRemoved => {
+                ("validator-being-removed", None, None)
+            }
+        };
+        let mut s = serializer.serialize_struct("ValidatorDelegationChange", 4)?;
+        s.serialize_field("vote", &self.vote.to_string())?;
+        s.serialize_field("change", change)?;
+        s.serialize_field("lamports", &lamports)?;
+        s.serialize_field("shortfall", &shortfall)?;
+        s.end()
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TransientStakeAccStatus {
     Deactivating,
@@ -124,106 +158,73 @@ impl<'a, D: Iterator<Item = ValidatorChangeSrc<'a>>> DelegationChangeset<D> {
             rent,
         }
     }
+}
 
-    pub fn print_decrease_stakes(self) {
-        self.print_summary(
-            "Decreasing stake:",
-            |ValidatorDelegationChange { ty, vote, .. }| match ty {
-                ValidatorDelegationChangeTy::DecreaseStake(dec) => Some(format!(
-                    "{} SOL from {vote}",
-                    TokenAmt {
-                        amt: dec,
-                        decimals: 9
-                    }
-                )),
-                _ => None,
-            },
-        );
-    }
-
-    pub fn print_increase_stakes(self) {
-        self.print_summary(
-            "Increasing stake:",
-            |ValidatorDelegationChange { ty, vote, .. }| match ty {
-                ValidatorDelegationChangeTy::IncreaseStake(inc) => Some(format!(
-                    "{} SOL to {vote}",
-                    TokenAmt {
-                        amt: inc,
-                        decimals: 9
-                    }
-                )),
-                _ => None,
-            },
-        );
-    }
-
-    pub fn print_partial_increase_stakes(self) {
-        self.print_summary(
-            "Partially increasing stake:",
-            |ValidatorDelegationChange { ty, vote, .. }| match ty {
-                ValidatorDelegationChangeTy::PartialIncreaseStake {
-                    increase,
-                    shortfall,
-                } => Some(format!(
-                    "{} SOL to {vote} ({} SOL shortfall)",
-                    TokenAmt {
-                        amt: increase,
-                        decimals: 9
-                    },
-                    TokenAmt {
-                        amt: shortfall,
-                        decimals: 9
-                    }
-                )),
-                _ => None,
-            },
-        );
-    }
-
-    pub fn print_insufficient_reserve_lamports(self) {
-        self.print_summary(
-            "Insufficient reserve lamports to increase/decrease stake for:",
-            |ValidatorDelegationChange { ty, vote, .. }| match ty {
-                ValidatorDelegationChangeTy::InsufficientReserveLamports => Some(format!("{vote}")),
-                _ => None,
-            },
-        );
-    }
-
-    pub fn print_transient_wrong_states(self) {
-        self.print_summary(
-            "Transient stake account in wrong state to make changes for:",
-            |ValidatorDelegationChange { ty, vote, .. }| match ty {
-                ValidatorDelegationChangeTy::TransientWrongState => Some(format!("{vote}")),
-                _ => None,
-            },
-        );
-    }
-
-    fn print_summary(
-        self,
-        header: &str,
-        filter_map_fn: fn(ValidatorDelegationChange) -> Option<String>,
-    ) {
-        let mut itr = self.filter_map(filter_map_fn).peekable();
-        if itr.peek().is_none() {
-            return;
-        }
-        eprint!("{header} ");
-        for msg in itr {
-            eprint!("{msg}, ");
-        }
-        eprintln!();
+impl<'a, D: Iterator<Item = ValidatorChangeSrc<'a>> + Clone> DelegationChangeset<D> {
+    /// Prints all changes except validators with no change or being removed
+    pub fn print_all_changes(&self, output: OutputFormat) {
+        let changes: Vec<ValidatorDelegationChange> = self
+            .clone()
+            .filter(|ValidatorDelegationChange { ty, .. }| {
+                !matches!(
+                    ty,
+                    ValidatorDelegationChangeTy::NoChange
+                        | ValidatorDelegationChangeTy::ValidatorBeingRemoved
+                )
+            })
+            .collect();
+        output.eprint_changes("delegation-changes", &changes);
     }
 }
 
 impl<'a, D: Iterator<Item = ValidatorChangeSrc<'a>> + Clone> DelegationChangeset<D> {
-    pub fn print_all_changes(&self) {
-        self.clone().print_decrease_stakes();
-        self.clone().print_increase_stakes();
-        self.clone().print_partial_increase_stakes();
-        self.clone().print_insufficient_reserve_lamports();
-        self.clone().print_transient_wrong_states();
+    /// old and new values are the validator's next epoch stake in lamports.
+    /// Changes that cannot be made e.g. due to insufficient reserve lamports are excluded.
+    pub fn plan_changes(&self, staker: &Pubkey) -> Vec<PlanChange> {
+        let sa_rent_lamports = lamports_for_new_vsa(&self.rent);
+        // self yields exactly one change per delegation, in order
+        self.delegations
+            .clone()
+            .zip(self.clone())
+            .filter_map(
+                |((_vsi, vsa, tsa, _desired), ValidatorDelegationChange { vote, ty, .. })| {
+                    let (next_epoch_stake, tsa_status) =
+                        next_epoch_stake_and_transient_status(vsa, tsa, self.curr_epoch);
+                    // reserve funds rent for tsa if it doesnt exist yet
+                    let tsa_rent = match tsa_status {
+                        TransientStakeAccStatus::None => sa_rent_lamports,
+                        _ => 0,
+                    };
+                    let (kind, new_stake, est_lamport_cost) = match ty {
+                        ValidatorDelegationChangeTy::DecreaseStake(dec) => (
+                            PlanChangeKind::DecreaseStake,
+                            next_epoch_stake.saturating_sub(dec),
+                            tsa_rent,
+                        ),
+                        ValidatorDelegationChangeTy::IncreaseStake(inc)
+                        | ValidatorDelegationChangeTy::PartialIncreaseStake {
+                            increase: inc, ..
+                        } => (
+                            PlanChangeKind::IncreaseStake,
+                            next_epoch_stake.saturating_add(inc),
+                            inc.saturating_add(tsa_rent),
+                        ),
+                        ValidatorDelegationChangeTy::InsufficientReserveLamports
+                        | ValidatorDelegationChangeTy::NoChange
+                        | ValidatorDelegationChangeTy::TransientWrongState
+                        | ValidatorDelegationChangeTy::ValidatorBeingRemoved => return None,
+                    };
+                    Some(PlanChange {
+                        kind,
+                        target: vote.to_string(),
+                        old: Some(next_epoch_stake.to_string()),
+                        new: Some(new_stake.to_string()),
+                        signers: vec![staker.to_string()],
+                        est_lamport_cost,
+                    })
+                },
+            )
+            .collect()
     }
 }
 
@@ -1,6 +1,8 @@
 use std::fmt::Display;
 
+use sanctum_solana_cli_utils::PubkeySrc;
 use sanctum_spl_stake_pool_lib::{CmpFee, EqFeeType, FindDepositAuthority};
+use serde::{ser::SerializeStruct, Serialize, Serializer};
 use solana_sdk::{instruction::Instruction, pubkey::Pubkey, signer::Signer};
 use spl_stake_pool_interface::{
     set_fee_ix_with_program_id, set_funding_authority_ix_with_program_id,
@@ -11,7 +13,7 @@ use spl_stake_pool_interface::{
 
 use crate::parse::filter_default_stake_deposit_auth;
 
-use super::utils::pubkey_opt_display;
+use super::{utils::pubkey_opt_display, ConfigRaw, PlanChange, PlanChangeKind};
 
 /// All generated ixs must be signed by manager only
 #[derive(Debug)]
@@ -72,6 +74,17 @@ impl Display for SyncPoolChange {
     }
 }
 
+/// Serialized as `{ attr, old, new }` with the same values as [`Display`]
+impl Serialize for SyncPoolChange {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("SyncPoolChange", 3)?;
+        s.serialize_field("attr", self.attr_name())?;
+        s.serialize_field("old", &self.old_val_display())?;
+        s.serialize_field("new", &self.new_val_display())?;
+        s.end()
+    }
+}
+
 impl SyncPoolChange {
     fn attr_name(&self) -> &'static str {
         match self {
@@ -134,6 +147,84 @@ impl SyncPoolChange {
 }
 
 impl<'a> SyncPoolConfig<'a> {
+    /// Settings not in `config` are left as `stake_pool`'s current values
+    pub fn from_config_raw(
+        ConfigRaw {
+            manager_fee_account,
+            staker,
+            stake_deposit_auth,
+            sol_deposit_auth,
+            sol_withdraw_auth,
+            stake_deposit_referral_fee,
+            sol_deposit_referral_fee,
+            epoch_fee,
+            stake_withdrawal_fee,
+            sol_withdrawal_fee,
+            stake_deposit_fee,
+            sol_deposit_fee,
+            ..
+        }: &ConfigRaw,
+        program_id: Pubkey,
+        pool: Pubkey,
+        stake_pool: &StakePool,
+        payer: &'a (dyn Signer + 'static),
+        manager: &'a (dyn Signer + 'static),
+        new_manager: &'a (dyn Signer + 'static),
+    ) -> Self {
+        let [manager_fee_account, staker] = [
+            (manager_fee_account, stake_pool.manager_fee_account),
+            (staker, stake_pool.staker),
+        ]
+        .map(|(file_opt, stake_pool_val)| {
+            file_opt
+                .as_ref()
+                .map_or(stake_pool_val, |s| PubkeySrc::parse(s).unwrap().pubkey())
+        });
+
+        let [sol_deposit_auth, sol_withdraw_auth, stake_deposit_auth] =
+            [sol_deposit_auth, sol_withdraw_auth, stake_deposit_auth].map(|string_opt| {
+                string_opt
+                    .as_ref()
+                    .map(|s| PubkeySrc::parse(s).unwrap().pubkey())
+            });
+
+        let [sol_deposit_referral_fee, stake_deposit_referral_fee] = [
+            (sol_deposit_referral_fee, stake_pool.sol_referral_fee),
+            (stake_deposit_referral_fee, stake_pool.stake_referral_fee),
+        ]
+        .map(|(opt, stake_pool_val)| opt.unwrap_or(stake_pool_val));
+
+        let [epoch_fee, stake_withdrawal_fee, sol_withdrawal_fee, stake_deposit_fee, sol_deposit_fee] =
+            [
+                (epoch_fee, &stake_pool.epoch_fee),
+                (stake_withdrawal_fee, &stake_pool.stake_withdrawal_fee),
+                (sol_withdrawal_fee, &stake_pool.sol_withdrawal_fee),
+                (stake_deposit_fee, &stake_pool.stake_deposit_fee),
+                (sol_deposit_fee, &stake_pool.sol_deposit_fee),
+            ]
+            .map(|(opt, stake_pool_val)| opt.as_ref().unwrap_or(stake_pool_val).clone());
+
+        Self {
+            program_id,
+            pool,
+            payer,
+            manager,
+            new_manager,
+            staker,
+            manager_fee_account,
+            sol_deposit_auth,
+            stake_deposit_auth,
+            sol_withdraw_auth,
+            epoch_fee,
+            stake_deposit_referral_fee,
+            sol_deposit_referral_fee,
+            stake_withdrawal_fee,
+            sol_withdrawal_fee,
+            stake_deposit_fee,
+            sol_deposit_fee,
+        }
+    }
+
     pub fn signers_maybe_dup(&self) -> [&'a dyn Signer; 3] {
         [self.payer, self.manager, self.new_manager]
     }
@@ -307,6 +398,27 @@ impl<'a> SyncPoolConfig<'a> {
         res
     }
 
+    pub fn plan_changes(&self, changeset: &[SyncPoolChange]) -> Vec<PlanChange> {
+        changeset
+            .iter()
+            .map(|c| PlanChange {
+                kind: PlanChangeKind::PoolParam,
+                target: c.attr_name().to_owned(),
+                old: Some(c.old_val_display()),
+                new: Some(c.new_val_display()),
+                signers: match c {
+                    // SetManager requires new manager's signature too
+                    SyncPoolChange::Manager { .. } => vec![
+                        self.manager.pubkey().to_string(),
+                        self.new_manager.pubkey().to_string(),
+                    ],
+                    _ => vec![self.manager.pubkey().to_string()],
+                },
+                est_lamport_cost: 0,
+            })
+            .collect()
+    }
+
     pub fn changeset_ixs(&self, changeset: &[SyncPoolChange]) -> std::io::Result<Vec<Instruction>> {
         changeset.iter().map(|c| self.change_ix(c)).collect()
     }
@@ -0,0 +1,524 @@
+use std::{error::Error, fmt::Display};
+
+use borsh::BorshSerialize;
+use sanctum_spl_stake_pool_lib::FindWithdrawAuthority;
+use serde::{ser::SerializeStruct, Serialize, Serializer};
+use solana_sdk::{
+    account::Account,
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    rent::Rent,
+    signer::Signer,
+    system_instruction, system_program,
+};
+use spl_token_2022::{
+    extension::{metadata_pointer::MetadataPointer, BaseStateWithExtensions, StateWithExtensions},
+    state::Mint,
+};
+use spl_token_metadata_interface::{
+    instruction::update_field,
+    state::{Field, TokenMetadata},
+};
+
+use crate::pool_config::{
+    find_metaplex_metadata, update_metaplex_metadata_ix, utils::pubkey_opt_display,
+    MetaplexMetadata, PlanChange, PlanChangeKind, METAPLEX_METADATA_MAX_LEN, METAPLEX_PROGRAM_ID,
+};
+
+const CREATE_TOKEN_METADATA_IX_DISCM: u8 = 17;
+
+const UPDATE_TOKEN_METADATA_IX_DISCM: u8 = 18;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TokenMetadataFields {
+    pub name: String,
+    pub symbol: String,
+    pub uri: String,
+}
+
+/// The pool token's current metadata
+#[derive(Clone, Debug)]
+pub enum OnchainTokenMetadata {
+    /// Metaplex metadata PDA. Used by Tokenkeg mints and Token-2022 mints without
+    /// a token-metadata extension, since the extension can only be initialized by the mint authority,
+    /// which is the pool's withdraw authority after pool creation.
+    Metaplex {
+        address: Pubkey,
+        metadata: Option<MetaplexMetadata>,
+    },
+
+    /// Token-2022 mints: token-metadata extension on the mint itself
+    Token2022 {
+        metadata: TokenMetadata,
+        mint_lamports: u64,
+        mint_data_len: usize,
+    },
+}
+
+impl OnchainTokenMetadata {
+    /// `metaplex_metadata` is the fetched account at [`find_metaplex_metadata`],
+    /// only used if the mint does not have a token-metadata extension
+    pub fn from_fetched(
+        mint_pk: &Pubkey,
+        mint: &Account,
+        metaplex_metadata: Option<&Account>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let metaplex_address = find_metaplex_metadata(mint_pk).0;
+        if mint.owner == spl_token_2022::ID {
+            let state = StateWithExtensions::<Mint>::unpack(&mint.data)?;
+            if let Ok(MetadataPointer {
+                metadata_address, ..
+            }) = state.get_extension::<MetadataPointer>()
+            {
+                let metadata_address: Option<Pubkey> = (*metadata_address).into();
+                if metadata_address.is_some_and(|a| a != *mint_pk && a != metaplex_address) {
+                    return Err(format!(
+                        "Mint metadata pointer points to {}, only metadata stored on the mint itself or metaplex metadata is supported",
+                        pubkey_opt_display(&metadata_address)
+                    )
+                    .into());
+                }
+            }
+            if let Ok(metadata) = state.get_variable_len_extension::<TokenMetadata>() {
+                return Ok(Self::Token2022 {
+                    metadata,
+                    mint_lamports: mint.lamports,
+                    mint_data_len: mint.data.len(),
+                });
+            }
+        }
+        Ok(Self::Metaplex {
+            address: metaplex_address,
+            metadata: metaplex_metadata
+                .filter(|a| a.owner == METAPLEX_PROGRAM_ID)
+                .map(|a| MetaplexMetadata::deserialize_account_data(&a.data))
+                .transpose()?,
+        })
+    }
+
+    pub fn fields(&self) -> Option<TokenMetadataFields> {
+        match self {
+            Self::Metaplex { metadata, .. } => metadata.as_ref().map(|m| TokenMetadataFields {
+                name: m.name().to_owned(),
+                symbol: m.symbol().to_owned(),
+                uri: m.uri().to_owned(),
+            }),
+            Self::Token2022 { metadata, .. } => Some(TokenMetadataFields {
+                name: metadata.name.clone(),
+                symbol: metadata.symbol.clone(),
+                uri: metadata.uri.clone(),
+            }),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenMetadataField {
+    Name,
+    Symbol,
+    Uri,
+}
+
+impl TokenMetadataField {
+    fn attr_name(&self) -> &'static str {
+        match self {
+            Self::Name => "token name",
+            Self::Symbol => "token symbol",
+            Self::Uri => "token uri",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SyncTokenMetadataChange {
+    Create(TokenMetadataFields),
+    Update {
+        field: TokenMetadataField,
+        old: String,
+        new: String,
+    },
+}
+
+impl Display for SyncTokenMetadataChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Create(TokenMetadataFields { name, symbol, uri }) => write!(
+                f,
+                "Create token metadata with name {name:?}, symbol {symbol:?}, uri {uri:?}"
+            ),
+            Self::Update { field, old, new } => {
+                write!(f, "Change {} from {old:?} to {new:?}", field.attr_name())
+            }
+        }
+    }
+}
+
+/// Serialized as `{ attr, old, new }`, creation having no `old`
+impl Serialize for SyncTokenMetadataChange {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let (attr, old, new) = match self {
+            Self::Create(TokenMetadataFields { name, symbol, uri }) => (
+                "token metadata",
+                None,
+                format!("name {name:?}, symbol {symbol:?}, uri {uri:?}"),
+            ),
+            Self::Update { field, old, new } => (field.attr_name(), Some(old), new.clone()),
+        };
+        let mut s = serializer.serialize_struct("SyncTokenMetadataChange", 3)?;
+        s.serialize_field("attr", attr)?;
+        s.serialize_field("old", &old)?;
+        s.serialize_field("new", &new)?;
+        s.end()
+    }
+}
+
+/// All generated ixs must be signed by manager only
+#[derive(Debug)]
+pub struct SyncTokenMetadataConfig<'a> {
+    pub program_id: Pubkey,
+    pub pool: Pubkey,
+    pub mint: Pubkey,
+    pub payer: &'a (dyn Signer + 'static),
+    pub manager: &'a (dyn Signer + 'static),
+    pub fields: TokenMetadataFields,
+    pub rent: &'a Rent,
+}
+
+impl<'a> SyncTokenMetadataConfig<'a> {
+    pub fn signers_maybe_dup(&self) -> [&'a dyn Signer; 2] {
+        [self.payer, self.manager]
+    }
+
+    fn withdraw_auth(&self) -> Pubkey {
+        FindWithdrawAuthority { pool: self.pool }
+            .run_for_prog(&self.program_id)
+            .0
+    }
+
+    pub fn changeset(&self, onchain: &OnchainTokenMetadata) -> Vec<SyncTokenMetadataChange> {
+        let TokenMetadataFields { name, symbol, uri } = match onchain.fields() {
+            None => return vec![SyncTokenMetadataChange::Create(self.fields.clone())],
+            Some(f) => f,
+        };
+        [
+            (TokenMetadataField::Name, name, &self.fields.name),
+            (TokenMetadataField::Symbol, symbol, &self.fields.symbol),
+            (TokenMetadataField::Uri, uri, &self.fields.uri),
+        ]
+        .into_iter()
+        .filter(|(_, old, new)| old != *new)
+        .map(|(field, old, new)| SyncTokenMetadataChange::Update {
+            field,
+            old,
+            new: new.clone(),
+        })
+        .collect()
+    }
+
+    pub fn plan_changes(
+        &self,
+        onchain: &OnchainTokenMetadata,
+        changeset: &[SyncTokenMetadataChange],
+    ) -> Vec<PlanChange> {
+        let rent_lamports = match onchain {
+            OnchainTokenMetadata::Metaplex { metadata: None, .. } => {
+                self.rent.minimum_balance(METAPLEX_METADATA_MAX_LEN)
+            }
+            OnchainTokenMetadata::Metaplex { .. } => 0,
+            OnchainTokenMetadata::Token2022 {
+                mint_lamports,
+                mint_data_len,
+                ..
+            } => self.token_2022_rent_shortfall(*mint_lamports, *mint_data_len, changeset),
+        };
+        changeset
+            .iter()
+            .flat_map(|change| match change {
+                SyncTokenMetadataChange::Create(TokenMetadataFields { name, symbol, uri }) => vec![
+                    (TokenMetadataField::Name, None, name.clone()),
+                    (TokenMetadataField::Symbol, None, symbol.clone()),
+                    (TokenMetadataField::Uri, None, uri.clone()),
+                ],
+                SyncTokenMetadataChange::Update { field, old, new } => {
+                    vec![(*field, Some(old.clone()), new.clone())]
+                }
+            })
+            .enumerate()
+            .map(|(i, (field, old, new))| PlanChange {
+                kind: PlanChangeKind::TokenMetadata,
+                target: field.attr_name().to_owned(),
+                old,
+                new: Some(new),
+                signers: vec![self.manager.pubkey().to_string()],
+                // all rent is paid by the payer upfront in the first ix
+                est_lamport_cost: if i == 0 { rent_lamports } else { 0 },
+            })
+            .collect()
+    }
+
+    pub fn changeset_ixs(
+        &self,
+        onchain: &OnchainTokenMetadata,
+        changeset: &[SyncTokenMetadataChange],
+    ) -> Result<Vec<Instruction>, Box<dyn Error>> {
+        if changeset.is_empty() {
+            return Ok(vec![]);
+        }
+        match onchain {
+            OnchainTokenMetadata::Metaplex { address, metadata } => {
+                Ok(vec![self.metaplex_ix(*address, metadata.as_ref())?])
+            }
+            OnchainTokenMetadata::Token2022 {
+                metadata,
+                mint_lamports,
+                mint_data_len,
+            } => self.token_2022_ixs(metadata, *mint_lamports, *mint_data_len, changeset),
+        }
+    }
+
+    /// Metaplex metadata is either
+    /// - created by the pool program, with the pool's withdraw authority as update authority
+    /// - created before pool creation, with the update authority retained by the manager
+    fn metaplex_ix(
+        &self,
+        address: Pubkey,
+        metadata: Option<&MetaplexMetadata>,
+    ) -> Result<Instruction, Box<dyn Error>> {
+        let TokenMetadataFields { name, symbol, uri } = self.fields.clone();
+        let metadata = match metadata {
+            None => {
+                return Ok(self.pool_token_metadata_ix(CREATE_TOKEN_METADATA_IX_DISCM, address)?)
+            }
+            Some(m) => m,
+        };
+        if !metadata.is_mutable {
+            return Err("Token metadata is immutable".into());
+        }
+        if metadata.update_authority == self.withdraw_auth() {
+            Ok(self.pool_token_metadata_ix(UPDATE_TOKEN_METADATA_IX_DISCM, address)?)
+        } else if metadata.update_authority == self.manager.pubkey() {
+            Ok(update_metaplex_metadata_ix(
+                address, metadata, name, symbol, uri,
+            )?)
+        } else {
+            Err(format!(
+                "Token metadata update authority {} is neither the pool's withdraw authority nor the manager",
+                metadata.update_authority
+            )
+            .into())
+        }
+    }
+
+    /// CreateTokenMetadata and UpdateTokenMetadata
+    /// have the same args but different accounts
+    fn pool_token_metadata_ix(&self, discm: u8, metadata: Pubkey) -> std::io::Result<Instruction> {
+        let TokenMetadataFields { name, symbol, uri } = self.fields.clone();
+        let mut data = vec![discm];
+        (name, symbol, uri).serialize(&mut data)?;
+        let mut accounts = vec![
+            AccountMeta::new_readonly(self.pool, false),
+            AccountMeta::new_readonly(self.manager.pubkey(), true),
+            AccountMeta::new_readonly(self.withdraw_auth(), false),
+        ];
+        if discm == CREATE_TOKEN_METADATA_IX_DISCM {
+            accounts.extend([
+                AccountMeta::new_readonly(self.mint, false),
+                AccountMeta::new(self.payer.pubkey(), true),
+                AccountMeta::new(metadata, false),
+                AccountMeta::new_readonly(METAPLEX_PROGRAM_ID, false),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ]);
+        } else {
+            accounts.extend([
+                AccountMeta::new(metadata, false),
+                AccountMeta::new_readonly(METAPLEX_PROGRAM_ID, false),
+            ]);
+        }
+        Ok(Instruction {
+            program_id: self.program_id,
+            accounts,
+            data,
+        })
+    }
+
+    fn token_2022_ixs(
+        &self,
+        metadata: &TokenMetadata,
+        mint_lamports: u64,
+        mint_data_len: usize,
+        changeset: &[SyncTokenMetadataChange],
+    ) -> Result<Vec<Instruction>, Box<dyn Error>> {
+        let update_authority: Option<Pubkey> = metadata.update_authority.into();
+        if update_authority != Some(self.manager.pubkey()) {
+            return Err(format!(
+                "Token metadata update authority {} is not the manager",
+                pubkey_opt_display(&update_authority)
+            )
+            .into());
+        }
+        let mut ixs = vec![];
+        for change in changeset {
+            let (field, new) = match change {
+                SyncTokenMetadataChange::Update { field, new, .. } => (field, new),
+                SyncTokenMetadataChange::Create(_) => unreachable!(),
+            };
+            ixs.push(update_field(
+                &spl_token_2022::ID,
+                &self.mint,
+                &self.manager.pubkey(),
+                match field {
+                    TokenMetadataField::Name => Field::Name,
+                    TokenMetadataField::Symbol => Field::Symbol,
+                    TokenMetadataField::Uri => Field::Uri,
+                },
+                new.clone(),
+            ));
+        }
+        let rent_shortfall =
+            self.token_2022_rent_shortfall(mint_lamports, mint_data_len, changeset);
+        if rent_shortfall > 0 {
+            ixs.insert(
+                0,
+                system_instruction::transfer(&self.payer.pubkey(), &self.mint, rent_shortfall),
+            );
+        }
+        Ok(ixs)
+    }
+
+    /// token-2022 reallocs the mint for longer fields but does not fund the additional rent
+    fn token_2022_rent_shortfall(
+        &self,
+        mint_lamports: u64,
+        mint_data_len: usize,
+        changeset: &[SyncTokenMetadataChange],
+    ) -> u64 {
+        let new_data_len = changeset
+            .iter()
+            .fold(mint_data_len, |len, change| match change {
+                SyncTokenMetadataChange::Update { old, new, .. } => {
+                    (len + new.len()).saturating_sub(old.len())
+                }
+                SyncTokenMetadataChange::Create(_) => len,
+            });
+        self.rent
+            .minimum_balance(new_data_len)
+            .saturating_sub(mint_lamports)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::{program_option::COption, program_pack::Pack, signature::Keypair};
+
+    use super::*;
+
+    fn test_fields() -> TokenMetadataFields {
+        TokenMetadataFields {
+            name: "Sanctum Pool SOL".to_owned(),
+            symbol: "spSOL".to_owned(),
+            uri: "https://example.com/spsol.json".to_owned(),
+        }
+    }
+
+    #[test]
+    fn changeset_only_includes_changed_fields() {
+        let payer = Keypair::new();
+        let manager = Keypair::new();
+        let fields = test_fields();
+        let config = SyncTokenMetadataConfig {
+            program_id: Pubkey::new_unique(),
+            pool: Pubkey::new_unique(),
+            mint: Pubkey::new_unique(),
+            payer: &payer,
+            manager: &manager,
+            fields: fields.clone(),
+            rent: &Rent::default(),
+        };
+
+        let onchain = OnchainTokenMetadata::Metaplex {
+            address: Pubkey::new_unique(),
+            metadata: None,
+        };
+        assert_eq!(
+            config.changeset(&onchain),
+            [SyncTokenMetadataChange::Create(fields.clone())]
+        );
+
+        let onchain = OnchainTokenMetadata::Metaplex {
+            address: Pubkey::new_unique(),
+            metadata: Some(MetaplexMetadata {
+                key: 4,
+                update_authority: manager.pubkey(),
+                mint: config.mint,
+                // metaplex null-pads strings
+                name: format!("{:\0<32}", fields.name),
+                symbol: format!("{:\0<10}", "oldSOL"),
+                uri: format!("{:\0<200}", fields.uri),
+                seller_fee_basis_points: 0,
+                creators: None,
+                primary_sale_happened: false,
+                is_mutable: true,
+                edition_nonce: None,
+                token_standard: None,
+                collection: None,
+                uses: None,
+            }),
+        };
+        assert_eq!(
+            config.changeset(&onchain),
+            [SyncTokenMetadataChange::Update {
+                field: TokenMetadataField::Symbol,
+                old: "oldSOL".to_owned(),
+                new: fields.symbol,
+            }]
+        );
+    }
+
+    #[test]
+    fn token_2022_mint_without_metadata_ext_creates_through_pool_program() {
+        let payer = Keypair::new();
+        let manager = Keypair::new();
+        let program_id = Pubkey::new_unique();
+        let pool = Pubkey::new_unique();
+        let mint_pk = Pubkey::new_unique();
+        let withdraw_auth = FindWithdrawAuthority { pool }.run_for_prog(&program_id).0;
+
+        let mut data = vec![0; Mint::LEN];
+        Mint {
+            mint_authority: COption::Some(withdraw_auth),
+            supply: 0,
+            decimals: 9,
+            is_initialized: true,
+            freeze_authority: COption::None,
+        }
+        .pack_into_slice(&mut data);
+        let mint = Account {
+            data,
+            owner: spl_token_2022::ID,
+            ..Default::default()
+        };
+
+        let onchain = OnchainTokenMetadata::from_fetched(&mint_pk, &mint, None).unwrap();
+        let (metaplex_address, _bump) = find_metaplex_metadata(&mint_pk);
+        assert!(matches!(
+            onchain,
+            OnchainTokenMetadata::Metaplex { address, metadata: None } if address == metaplex_address
+        ));
+
+        let config = SyncTokenMetadataConfig {
+            program_id,
+            pool,
+            mint: mint_pk,
+            payer: &payer,
+            manager: &manager,
+            fields: test_fields(),
+            rent: &Rent::default(),
+        };
+        let changeset = config.changeset(&onchain);
+        let ixs = config.changeset_ixs(&onchain, &changeset).unwrap();
+        assert_eq!(ixs.len(), 1);
+        assert_eq!(ixs[0].program_id, program_id);
+        assert_eq!(ixs[0].data[0], CREATE_TOKEN_METADATA_IX_DISCM);
+        assert_eq!(ixs[0].accounts[5].pubkey, metaplex_address);
+    }
+}
@@ -1,10 +1,12 @@
-use std::{collections::HashSet, fmt::Display, iter::Flatten, num::NonZeroU32};
+use std::{collections::BTreeSet, fmt::Display, iter::Flatten, num::NonZeroU32};
 
+use sanctum_solana_cli_utils::PubkeySrc;
 use sanctum_spl_stake_pool_lib::{
     FindEphemeralStakeAccount, FindEphemeralStakeAccountArgs, FindTransientStakeAccount,
     FindTransientStakeAccountArgs, FindValidatorStakeAccount, FindValidatorStakeAccountArgs,
     FindWithdrawAuthority,
 };
+use serde::{ser::SerializeStruct, Serialize, Serializer};
 use solana_sdk::{
     instruction::Instruction,
     pubkey::Pubkey,
@@ -23,7 +25,13 @@ use spl_stake_pool_interface::{
     SetPreferredValidatorKeys, StakePool, StakeStatus, ValidatorStakeInfo,
 };
 
-use crate::pool_config::utils::{lamports_for_new_vsa, pubkey_opt_display};
+use crate::{
+    pool_config::{
+        utils::{lamports_for_new_vsa, pubkey_opt_display},
+        ConfigRaw, PlanChange, PlanChangeKind,
+    },
+    OutputFormat,
+};
 
 /// All generated ixs must be signed by staker only.
 /// Adds and removes validators from the list to match `self.validators`
@@ -35,7 +43,7 @@ pub struct SyncValidatorListConfig<'a> {
     pub pool: Pubkey,
     pub validator_list: Pubkey,
     pub reserve: Pubkey,
-    pub validators: HashSet<Pubkey>,
+    pub validators: BTreeSet<Pubkey>,
     pub preferred_deposit_validator: Option<Pubkey>,
     pub preferred_withdraw_validator: Option<Pubkey>,
     pub rent: &'a Rent,
@@ -50,20 +58,73 @@ pub struct PreferredValidatorChange {
 
 impl Display for PreferredValidatorChange {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let attr = match self.ty {
-            PreferredValidatorType::Deposit => "deposit",
-            PreferredValidatorType::Withdraw => "withdraw",
-        };
         write!(
             f,
-            "Change preferred {attr} validator from {} to {}",
+            "Change {} from {} to {}",
+            self.attr_name(),
             pubkey_opt_display(&self.old),
             pubkey_opt_display(&self.new)
         )
     }
 }
 
+/// Serialized as `{ attr, old, new }`
+impl Serialize for PreferredValidatorChange {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut s = serializer.serialize_struct("PreferredValidatorChange", 3)?;
+        s.serialize_field("attr", self.attr_name())?;
+        s.serialize_field("old", &self.old.map(|pk| pk.to_string()))?;
+        s.serialize_field("new", &self.new.map(|pk| pk.to_string()))?;
+        s.end()
+    }
+}
+
+impl PreferredValidatorChange {
+    fn attr_name(&self) -> &'static str {
+        match self.ty {
+            PreferredValidatorType::Deposit => "preferred deposit validator",
+            PreferredValidatorType::Withdraw => "preferred withdraw validator",
+        }
+    }
+}
+
 impl<'a> SyncValidatorListConfig<'a> {
+    /// Validators not in `config` are removed from the pool
+    pub fn from_config_raw(
+        ConfigRaw {
+            validators,
+            preferred_deposit_validator,
+            preferred_withdraw_validator,
+            ..
+        }: &ConfigRaw,
+        program_id: Pubkey,
+        pool: Pubkey,
+        stake_pool: &StakePool,
+        payer: &'a (dyn Signer + 'static),
+        staker: &'a (dyn Signer + 'static),
+        rent: &'a Rent,
+    ) -> Self {
+        let [preferred_deposit_validator, preferred_withdraw_validator] =
+            [preferred_deposit_validator, preferred_withdraw_validator]
+                .map(|opt| opt.as_ref().map(|s| PubkeySrc::parse(s).unwrap().pubkey()));
+        Self {
+            program_id,
+            payer,
+            staker,
+            pool,
+            validator_list: stake_pool.validator_list,
+            reserve: stake_pool.reserve_stake,
+            validators: validators
+                .iter()
+                .flatten()
+                .map(|v| PubkeySrc::parse(&v.vote).unwrap().pubkey())
+                .collect(),
+            preferred_deposit_validator,
+            preferred_withdraw_validator,
+            rent,
+        }
+    }
+
     pub fn signers_maybe_dup(&self) -> [&'a dyn Signer; 2] {
         [self.payer, self.staker]
     }
@@ -155,6 +216,15 @@ impl SyncValidatorListConfig<'_> {
         )
     }
 
+    /// Lamports taken from the reserve to fund the new validator stake accounts of `add`
+    pub fn add_validators_reserve_lamports<'b>(
+        &self,
+        add: impl Iterator<Item = &'b Pubkey>,
+    ) -> u64 {
+        // as-safety: usize always fits in u64
+        lamports_for_new_vsa(self.rent).saturating_mul(add.count() as u64)
+    }
+
     pub fn add_validators_ixs<'b>(
         &self,
         add: impl Iterator<Item = &'b Pubkey>,
@@ -292,6 +362,46 @@ impl SyncValidatorListConfig<'_> {
     }
 }
 
+// plan
+impl SyncValidatorListConfig<'_> {
+    pub fn plan_changes<'b>(
+        &self,
+        add: impl Iterator<Item = &'b Pubkey>,
+        remove: impl Iterator<Item = &'b ValidatorStakeInfo>,
+        preferred_validator_changes: impl Iterator<Item = PreferredValidatorChange>,
+    ) -> Vec<PlanChange> {
+        let staker = vec![self.staker.pubkey().to_string()];
+        // same order as execution: remove, add, then preferred validators
+        remove
+            .map(|vsi| PlanChange {
+                kind: PlanChangeKind::RemoveValidator,
+                target: vsi.vote_account_address.to_string(),
+                old: None,
+                new: None,
+                signers: staker.clone(),
+                est_lamport_cost: 0,
+            })
+            .chain(add.map(|vote| PlanChange {
+                kind: PlanChangeKind::AddValidator,
+                target: vote.to_string(),
+                old: None,
+                new: None,
+                signers: staker.clone(),
+                // new validator stake account is funded from the reserve
+                est_lamport_cost: lamports_for_new_vsa(self.rent),
+            }))
+            .chain(preferred_validator_changes.map(|c| PlanChange {
+                kind: PlanChangeKind::PreferredValidator,
+                target: c.attr_name().to_owned(),
+                old: Some(pubkey_opt_display(&c.old)),
+                new: Some(pubkey_opt_display(&c.new)),
+                signers: staker.clone(),
+                est_lamport_cost: 0,
+            }))
+            .collect()
+    }
+}
+
 #[derive(Debug)]
 pub enum RemoveValidatorIxs {
     WithDecreaseStake(Instruction, Instruction),
@@ -311,26 +421,20 @@ impl IntoIterator for RemoveValidatorIxs {
     }
 }
 
-pub fn print_removing_validators_msg<'a>(remove: impl Iterator<Item = &'a ValidatorStakeInfo>) {
-    let mut remove = remove.peekable();
-    if remove.peek().is_none() {
-        return;
-    }
-    eprint!("Removing validators: ");
-    for to_remove in remove {
-        eprint!("{}, ", to_remove.vote_account_address);
-    }
-    eprintln!();
+pub fn print_removing_validators_msg<'a>(
+    output: OutputFormat,
+    remove: impl Iterator<Item = &'a ValidatorStakeInfo>,
+) {
+    let remove: Vec<String> = remove
+        .map(|vsi| vsi.vote_account_address.to_string())
+        .collect();
+    output.eprint_changes("remove-validators", &remove);
 }
 
-pub fn print_adding_validators_msg<'a>(add: impl Iterator<Item = &'a Pubkey>) {
-    let mut add = add.peekable();
-    if add.peek().is_none() {
-        return;
-    }
-    eprint!("Adding validators: ");
-    for to_add in add {
-        eprint!("{to_add}, ");
-    }
-    eprintln!();
+pub fn print_adding_validators_msg<'a>(
+    output: OutputFormat,
+    add: impl Iterator<Item = &'a Pubkey>,
+) {
+    let add: Vec<String> = add.map(|vote| vote.to_string()).collect();
+    output.eprint_changes("add-validators", &add);
 }
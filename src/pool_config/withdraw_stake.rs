@@ -0,0 +1,536 @@
+//! Splitting a single withdraw stake across multiple stake accounts of the pool,
+//! in the order the spl stake pool program enforces:
+//! preferred validator, then other active stake, then transient stake, then the reserve.
+//! A withdrawal that is not split is made from the first stake account allowed by this order.
+
+use std::fmt::Display;
+
+use sanctum_spl_stake_pool_lib::{
+    account_resolvers::WithdrawStakeWithSlippage, min_reserve_lamports, FindTransientStakeAccount,
+    FindTransientStakeAccountArgs,
+};
+use solana_readonly_account::keyed::Keyed;
+use solana_sdk::{
+    instruction::Instruction,
+    pubkey::Pubkey,
+    rent::Rent,
+    stake::{self, state::StakeStateV2},
+    system_instruction,
+};
+use spl_stake_pool_interface::{
+    withdraw_stake_with_slippage_ix_with_program_id, StakePool, StakeStatus, ValidatorStakeInfo,
+    WithdrawStakeWithSlippageIxArgs,
+};
+
+use crate::{
+    pool_config::utils::{lamports_for_new_vsa, min_delegation},
+    PoolQuoter, WithdrawQuote,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum WithdrawStakeSrc {
+    Validator(ValidatorStakeInfo),
+    Transient(ValidatorStakeInfo),
+    Reserve,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct WithdrawStakeSplit {
+    pub src: WithdrawStakeSrc,
+    pub quote: WithdrawQuote,
+}
+
+/// A pool validator's stake accounts, as currently onchain
+#[derive(Clone, Debug)]
+pub struct ValidatorStakeAccs<'a> {
+    pub vsi: &'a ValidatorStakeInfo,
+
+    /// `(lamports, state)` of the validator stake account, if it exists.
+    /// Validators being removed may no longer have one.
+    pub vsa: Option<(u64, StakeStateV2)>,
+
+    /// `(lamports, state)` of the transient stake account, if it exists
+    pub tsa: Option<(u64, StakeStateV2)>,
+}
+
+#[derive(Debug)]
+pub struct WithdrawStakeSplitConfig<'a> {
+    pub program_id: Pubkey,
+    pub pool: Pubkey,
+    pub stake_pool: &'a StakePool,
+    pub payer: Pubkey,
+    pub authority: Pubkey,
+    pub burn_from: Pubkey,
+    pub beneficiary: Pubkey,
+
+    /// Note: this is accountinfo.lamports and includes rent-exempt lamports
+    pub reserve_lamports: u64,
+
+    pub curr_epoch: u64,
+    pub rent: &'a Rent,
+}
+
+impl<'a> WithdrawStakeSplitConfig<'a> {
+    /// Returns the splits required to redeem `pool_tokens_in`
+    /// and the pool tokens that could not be serviced by the pool, if any.
+    ///
+    /// `validators` should be the validator list entries after the pool has been updated for this epoch.
+    pub fn splits(
+        &self,
+        validators: &[ValidatorStakeInfo],
+        pool_tokens_in: u64,
+    ) -> (Vec<WithdrawStakeSplit>, u64) {
+        // spl stake pool program requirement:
+        // stake accounts must be left with at least rent + min delegation
+        let min_stake_lamports = lamports_for_new_vsa(self.rent);
+        let mut quoter = PoolQuoter::new(self.stake_pool, self.curr_epoch);
+        // The program only considers a stake account to have withdrawable stake
+        // if it has more than min_stake_lamports + lamports_per_pool_token.
+        // Stake accounts at or below this threshold are skipped
+        let threshold = min_stake_lamports.saturating_add(quoter.lamports_per_pool_token());
+        let preferred = self.stake_pool.preferred_withdraw_validator_vote_address;
+
+        let mut active: Vec<&ValidatorStakeInfo> = validators
+            .iter()
+            .filter(|vsi| {
+                vsi.status == StakeStatus::Active && vsi.active_stake_lamports > threshold
+            })
+            .collect();
+        // preferred validator must be drained first, then largest first to minimize splits
+        active.sort_by_key(|vsi| {
+            (
+                Some(vsi.vote_account_address) != preferred,
+                std::cmp::Reverse(vsi.active_stake_lamports),
+            )
+        });
+        let mut transient: Vec<&ValidatorStakeInfo> = validators
+            .iter()
+            .filter(|vsi| {
+                vsi.status == StakeStatus::Active && vsi.transient_stake_lamports > threshold
+            })
+            .collect();
+        transient.sort_by_key(|vsi| std::cmp::Reverse(vsi.transient_stake_lamports));
+
+        let srcs = active
+            .into_iter()
+            .map(|vsi| {
+                (
+                    WithdrawStakeSrc::Validator(vsi.clone()),
+                    vsi.active_stake_lamports - min_stake_lamports,
+                )
+            })
+            .chain(transient.into_iter().map(|vsi| {
+                (
+                    WithdrawStakeSrc::Transient(vsi.clone()),
+                    vsi.transient_stake_lamports - min_stake_lamports,
+                )
+            }))
+            .chain(std::iter::once((
+                WithdrawStakeSrc::Reserve,
+                self.reserve_lamports
+                    .saturating_sub(min_reserve_lamports(self.rent)),
+            )));
+
+        let mut remaining = pool_tokens_in;
+        let mut res = Vec::new();
+        for (src, available_lamports) in srcs {
+            if remaining == 0 {
+                break;
+            }
+            let max_tokens = quoter.max_withdraw_stake_tokens(available_lamports, &self.burn_from);
+            let quote = quoter.withdraw_stake(remaining.min(max_tokens), &self.burn_from);
+            if quote.lamports_out == 0 {
+                continue;
+            }
+            remaining -= quote.pool_tokens_in;
+            // apply the withdrawal so that the next split is quoted against
+            // the same exchange rate the program will see
+            quoter.total_lamports -= quote.lamports_out;
+            quoter.pool_token_supply -= quote.pool_tokens_burnt;
+            res.push(WithdrawStakeSplit { src, quote });
+        }
+        (res, remaining)
+    }
+
+    /// Returns the stake account a withdrawal that is not split
+    /// must be made from, and the max lamports that can be withdrawn from it.
+    ///
+    /// The program only allows withdrawing from transient stake accounts once all
+    /// validator stake accounts are at minimum, and only allows withdrawing from the reserve
+    /// once all transient stake accounts are at minimum too.
+    ///
+    /// `validator` is the validator requested by the user, if any.
+    /// Defaults to the pool's preferred withdraw validator if it has withdrawable stake,
+    /// or the validator with the most withdrawable stake otherwise.
+    pub fn single_src(
+        &self,
+        accs: &[ValidatorStakeAccs],
+        validator: Option<Pubkey>,
+    ) -> (WithdrawStakeSrc, u64) {
+        let tolerance = PoolQuoter::new(self.stake_pool, self.curr_epoch).lamports_per_pool_token();
+        let active: Vec<(&ValidatorStakeInfo, u64)> = accs
+            .iter()
+            .filter(|a| a.vsi.status == StakeStatus::Active)
+            .filter_map(|a| {
+                let (lamports, state) = a.vsa.as_ref()?;
+                withdrawable_lamports(*lamports, state, tolerance).map(|l| (a.vsi, l))
+            })
+            .collect();
+        let transient: Vec<(&ValidatorStakeInfo, u64)> = accs
+            .iter()
+            .filter(|a| a.vsi.status == StakeStatus::Active)
+            .filter_map(|a| {
+                let (lamports, state) = a.tsa.as_ref()?;
+                withdrawable_lamports(*lamports, state, tolerance).map(|l| (a.vsi, l))
+            })
+            .collect();
+
+        let select = |srcs: Vec<(&ValidatorStakeInfo, u64)>| match validator {
+            Some(v) => srcs
+                .into_iter()
+                .find(|(vsi, _)| vsi.vote_account_address == v)
+                .map_or_else(
+                    || {
+                        let vsi = accs
+                            .iter()
+                            .find(|a| a.vsi.vote_account_address == v)
+                            .unwrap_or_else(|| panic!("Validator {v} not part of pool"))
+                            .vsi;
+                        (vsi.clone(), 0)
+                    },
+                    |(vsi, l)| (vsi.clone(), l),
+                ),
+            None => {
+                let preferred = srcs.iter().find(|(vsi, _)| {
+                    Some(vsi.vote_account_address)
+                        == self.stake_pool.preferred_withdraw_validator_vote_address
+                });
+                let (vsi, l) = preferred
+                    .or_else(|| srcs.iter().max_by_key(|(_, l)| *l))
+                    .unwrap();
+                ((*vsi).clone(), *l)
+            }
+        };
+
+        if !active.is_empty() {
+            let (vsi, l) = select(active);
+            (WithdrawStakeSrc::Validator(vsi), l)
+        } else if !transient.is_empty() {
+            let (vsi, l) = select(transient);
+            (WithdrawStakeSrc::Transient(vsi), l)
+        } else {
+            (
+                WithdrawStakeSrc::Reserve,
+                self.reserve_lamports
+                    .saturating_sub(min_reserve_lamports(self.rent)),
+            )
+        }
+    }
+
+    /// Returns `[create_account_with_seed, withdraw_stake_with_slippage]`
+    /// for withdrawing `split` to a new stake account at `split_to`,
+    /// created with `seed` and `self.authority` as base.
+    pub fn split_ixs(
+        &self,
+        WithdrawStakeSplit { src, quote }: &WithdrawStakeSplit,
+        split_to: Pubkey,
+        seed: &str,
+        min_lamports_out: u64,
+    ) -> std::io::Result<[Instruction; 2]> {
+        let resolve = WithdrawStakeWithSlippage {
+            pool: Keyed {
+                pubkey: self.pool,
+                account: self.stake_pool,
+            },
+            burn_from: self.burn_from,
+            transfer_authority: self.authority,
+            beneficiary: self.beneficiary,
+            split_to,
+        };
+        let (vote, validator_seed_suffix) = match src {
+            WithdrawStakeSrc::Validator(vsi) | WithdrawStakeSrc::Transient(vsi) => {
+                (vsi.vote_account_address, vsi.validator_seed_suffix)
+            }
+            // dont care, stake_to_split is replaced with the reserve below
+            WithdrawStakeSrc::Reserve => (Pubkey::default(), Default::default()),
+        };
+        let mut keys = resolve.resolve_with_computed_keys(resolve.compute_keys_for_vsa(
+            &self.program_id,
+            vote,
+            validator_seed_suffix,
+        ));
+        match src {
+            WithdrawStakeSrc::Validator(_) => (),
+            WithdrawStakeSrc::Transient(vsi) => {
+                keys.stake_to_split =
+                    FindTransientStakeAccount::new(FindTransientStakeAccountArgs {
+                        pool: self.pool,
+                        vote: vsi.vote_account_address,
+                        seed: vsi.transient_seed_suffix,
+                    })
+                    .run_for_prog(&self.program_id)
+                    .0;
+            }
+            WithdrawStakeSrc::Reserve => keys.stake_to_split = self.stake_pool.reserve_stake,
+        }
+        Ok([
+            system_instruction::create_account_with_seed(
+                &self.payer,
+                &split_to,
+                &self.authority,
+                seed,
+                self.rent.minimum_balance(StakeStateV2::size_of()),
+                StakeStateV2::size_of() as u64,
+                &stake::program::ID,
+            ),
+            withdraw_stake_with_slippage_ix_with_program_id(
+                self.program_id,
+                keys,
+                WithdrawStakeWithSlippageIxArgs {
+                    pool_tokens_in: quote.pool_tokens_in,
+                    min_lamports_out,
+                },
+            )?,
+        ])
+    }
+}
+
+/// Returns the lamports withdrawable from a pool stake account
+/// if the program considers it to have withdrawable stake, None otherwise.
+///
+/// `tolerance` is the pool's lamports per pool token
+fn withdrawable_lamports(lamports: u64, state: &StakeStateV2, tolerance: u64) -> Option<u64> {
+    // spl stake pool program's minimum_stake_lamports()
+    let required = state
+        .meta()?
+        .rent_exempt_reserve
+        .saturating_add(min_delegation());
+    (lamports > required.saturating_add(tolerance)).then(|| lamports - required)
+}
+
+impl Display for WithdrawStakeSrc {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Validator(vsi) => write!(f, "validator {}", vsi.vote_account_address),
+            Self::Transient(vsi) => write!(
+                f,
+                "transient stake of validator {}",
+                vsi.vote_account_address
+            ),
+            Self::Reserve => f.write_str("reserve"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sanctum_spl_stake_pool_lib::ZERO_FEE;
+    use solana_sdk::stake::state::Meta;
+    use spl_stake_pool_interface::{AccountType, FutureEpochFee, Lockup};
+
+    use super::*;
+
+    const SOL: u64 = 1_000_000_000;
+
+    fn mock_stake_pool(total_lamports: u64, preferred: Option<Pubkey>) -> StakePool {
+        StakePool {
+            total_lamports,
+            pool_token_supply: total_lamports,
+            preferred_withdraw_validator_vote_address: preferred,
+            // dont cares:
+            validator_list: Pubkey::new_unique(),
+            reserve_stake: Pubkey::new_unique(),
+            account_type: AccountType::StakePool,
+            manager: Pubkey::new_unique(),
+            staker: Pubkey::new_unique(),
+            stake_deposit_authority: Pubkey::new_unique(),
+            manager_fee_account: Pubkey::new_unique(),
+            epoch_fee: ZERO_FEE,
+            next_epoch_fee: FutureEpochFee::None,
+            stake_deposit_fee: ZERO_FEE,
+            stake_withdrawal_fee: ZERO_FEE,
+            next_stake_withdrawal_fee: FutureEpochFee::None,
+            stake_referral_fee: 0,
+            sol_deposit_authority: None,
+            sol_deposit_fee: ZERO_FEE,
+            sol_referral_fee: 0,
+            sol_withdraw_authority: None,
+            sol_withdrawal_fee: ZERO_FEE,
+            next_sol_withdrawal_fee: FutureEpochFee::None,
+            token_program: Pubkey::new_unique(),
+            pool_mint: Pubkey::new_unique(),
+            preferred_deposit_validator_vote_address: None,
+            lockup: Lockup {
+                unix_timestamp: 0,
+                epoch: 0,
+                custodian: Pubkey::default(),
+            },
+            last_update_epoch: 0,
+            stake_withdraw_bump_seed: 255,
+            last_epoch_pool_token_supply: 0,
+            last_epoch_total_lamports: 0,
+        }
+    }
+
+    fn mock_vsi(active_stake_lamports: u64, transient_stake_lamports: u64) -> ValidatorStakeInfo {
+        ValidatorStakeInfo {
+            active_stake_lamports,
+            transient_stake_lamports,
+            vote_account_address: Pubkey::new_unique(),
+            status: StakeStatus::Active,
+            // dont care
+            last_update_epoch: 0,
+            transient_seed_suffix: 0,
+            unused: 0,
+            validator_seed_suffix: 0,
+        }
+    }
+
+    #[test]
+    fn splits_drain_preferred_then_active_then_transient_then_reserve() {
+        let rent = Rent::default();
+        let min = lamports_for_new_vsa(&rent);
+        let reserve_lamports = min_reserve_lamports(&rent) + 5 * SOL;
+        let validators = [
+            mock_vsi(min + 10 * SOL, 0),
+            mock_vsi(min + 3 * SOL, min + 2 * SOL),
+            mock_vsi(min + 20 * SOL, 0),
+        ];
+        let stake_pool = mock_stake_pool(
+            validators
+                .iter()
+                .map(|v| v.active_stake_lamports + v.transient_stake_lamports)
+                .sum::<u64>()
+                + reserve_lamports,
+            Some(validators[1].vote_account_address),
+        );
+        let config = WithdrawStakeSplitConfig {
+            program_id: Pubkey::new_unique(),
+            pool: Pubkey::new_unique(),
+            stake_pool: &stake_pool,
+            payer: Pubkey::new_unique(),
+            authority: Pubkey::new_unique(),
+            burn_from: Pubkey::new_unique(),
+            beneficiary: Pubkey::new_unique(),
+            reserve_lamports,
+            curr_epoch: 0,
+            rent: &rent,
+        };
+
+        let (splits, shortfall) = config.splits(&validators, 36 * SOL);
+        assert_eq!(shortfall, 0);
+        let srcs_and_amts: Vec<_> = splits
+            .iter()
+            .map(|s| (s.src.clone(), s.quote.lamports_out))
+            .collect();
+        assert_eq!(
+            srcs_and_amts,
+            [
+                (WithdrawStakeSrc::Validator(validators[1].clone()), 3 * SOL),
+                (WithdrawStakeSrc::Validator(validators[2].clone()), 20 * SOL),
+                (WithdrawStakeSrc::Validator(validators[0].clone()), 10 * SOL),
+                (WithdrawStakeSrc::Transient(validators[1].clone()), 2 * SOL),
+                (WithdrawStakeSrc::Reserve, SOL),
+            ]
+        );
+
+        let (_splits, shortfall) = config.splits(&validators, 41 * SOL);
+        assert_eq!(shortfall, SOL);
+    }
+
+    #[test]
+    fn single_src_falls_back_to_transient_then_reserve() {
+        let rent = Rent::default();
+        let min = lamports_for_new_vsa(&rent);
+        let stake_state = StakeStateV2::Stake(
+            Meta {
+                rent_exempt_reserve: rent.minimum_balance(StakeStateV2::size_of()),
+                ..Default::default()
+            },
+            Default::default(),
+            Default::default(),
+        );
+        let reserve_lamports = min_reserve_lamports(&rent) + 5 * SOL;
+        let validators = [mock_vsi(min, 0), mock_vsi(min, min + 2 * SOL)];
+        let stake_pool = mock_stake_pool(2 * min + min + 2 * SOL + reserve_lamports, None);
+        let config = WithdrawStakeSplitConfig {
+            program_id: Pubkey::new_unique(),
+            pool: Pubkey::new_unique(),
+            stake_pool: &stake_pool,
+            payer: Pubkey::new_unique(),
+            authority: Pubkey::new_unique(),
+            burn_from: Pubkey::new_unique(),
+            beneficiary: Pubkey::new_unique(),
+            reserve_lamports,
+            curr_epoch: 0,
+            rent: &rent,
+        };
+
+        // all vsas at minimum: must withdraw from tsa
+        let mut accs: Vec<_> = validators
+            .iter()
+            .map(|vsi| ValidatorStakeAccs {
+                vsi,
+                vsa: Some((vsi.active_stake_lamports, stake_state.clone())),
+                tsa: (vsi.transient_stake_lamports > 0)
+                    .then(|| (vsi.transient_stake_lamports, stake_state.clone())),
+            })
+            .collect();
+        assert_eq!(
+            config.single_src(&accs, None),
+            (WithdrawStakeSrc::Transient(validators[1].clone()), 2 * SOL)
+        );
+
+        // all vsas and tsas at minimum: must withdraw from reserve
+        accs[1].tsa = None;
+        assert_eq!(
+            config.single_src(&accs, None),
+            (WithdrawStakeSrc::Reserve, 5 * SOL)
+        );
+    }
+
+    #[test]
+    fn single_src_skips_validators_without_vsa() {
+        let rent = Rent::default();
+        let min = lamports_for_new_vsa(&rent);
+        let stake_state = StakeStateV2::Stake(
+            Meta {
+                rent_exempt_reserve: rent.minimum_balance(StakeStateV2::size_of()),
+                ..Default::default()
+            },
+            Default::default(),
+            Default::default(),
+        );
+        let reserve_lamports = min_reserve_lamports(&rent);
+        let mut removed = mock_vsi(min + 10 * SOL, 0);
+        removed.status = StakeStatus::ReadyForRemoval;
+        let validators = [removed, mock_vsi(min + 3 * SOL, 0)];
+        let stake_pool = mock_stake_pool(2 * min + 13 * SOL + reserve_lamports, None);
+        let config = WithdrawStakeSplitConfig {
+            program_id: Pubkey::new_unique(),
+            pool: Pubkey::new_unique(),
+            stake_pool: &stake_pool,
+            payer: Pubkey::new_unique(),
+            authority: Pubkey::new_unique(),
+            burn_from: Pubkey::new_unique(),
+            beneficiary: Pubkey::new_unique(),
+            reserve_lamports,
+            curr_epoch: 0,
+            rent: &rent,
+        };
+        let accs = [
+            ValidatorStakeAccs {
+                vsi: &validators[0],
+                vsa: None,
+                tsa: None,
+            },
+            ValidatorStakeAccs {
+                vsi: &validators[1],
+                vsa: Some((validators[1].active_stake_lamports, stake_state)),
+                tsa: None,
+            },
+        ];
+        let (src, _available) = config.single_src(&accs, None);
+        assert_eq!(src, WithdrawStakeSrc::Validator(validators[1].clone()));
+    }
+}
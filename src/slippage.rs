@@ -0,0 +1,39 @@
+use clap::{
+    builder::{StringValueParser, TypedValueParser},
+    Args,
+};
+use sanctum_solana_cli_utils::{TokenAmt, TokenAmtParser};
+
+const BPS_DENOM: u64 = 10_000;
+
+#[derive(Args, Debug)]
+pub struct SlippageArgs {
+    #[arg(
+        long,
+        help = "Max slippage in bps from the output amount expected from the pool's current exchange rate. Ignored if --min-out is set.",
+        default_value_t = 10
+    )]
+    pub max_slippage_bps: u16,
+
+    #[arg(
+        long,
+        help = "Explicit minimum output amount. Transaction fails if output falls below this amount.",
+        value_parser = StringValueParser::new().map(|s| TokenAmtParser::new(9).parse(&s).unwrap()),
+    )]
+    pub min_out: Option<TokenAmt>,
+}
+
+impl SlippageArgs {
+    /// Returns the min amount out to pass to the `*WithSlippage` instructions
+    pub fn min_out(&self, expected_out: u64) -> u64 {
+        match &self.min_out {
+            Some(TokenAmt { amt, .. }) => *amt,
+            None => {
+                let bps = u64::from(self.max_slippage_bps).min(BPS_DENOM);
+                // as-safety: result <= expected_out
+                (u128::from(expected_out) * u128::from(BPS_DENOM - bps) / u128::from(BPS_DENOM))
+                    as u64
+            }
+        }
+    }
+}
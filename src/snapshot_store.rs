@@ -0,0 +1,117 @@
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fs::{create_dir_all, read_to_string, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::pool_config::ConfigRaw;
+
+/// A pool's state at a point in time, as recorded by `snapshot`
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct PoolSnapshot {
+    pub epoch: u64,
+    pub slot: u64,
+    pub unix_timestamp: i64,
+
+    /// Same info as `list --verbose`
+    pub pool: ConfigRaw,
+}
+
+/// Append-only json-lines file of a pool's [`PoolSnapshot`]s, one per line.
+///
+/// Chain RPC does not serve past epochs' account states so history must be accumulated locally.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SnapshotStore {
+    pub path: PathBuf,
+}
+
+impl SnapshotStore {
+    /// `<dir>/<pool>.jsonl`, `dir` defaulting to `~/.config/splsp/snapshots`,
+    /// or in the temp dir if no home dir
+    pub fn for_pool(dir: Option<&Path>, pool: &Pubkey) -> Self {
+        let dir = dir.map_or_else(
+            || {
+                std::env::var_os("HOME").map_or_else(
+                    || std::env::temp_dir().join("splsp-snapshots"),
+                    |home| PathBuf::from(home).join(".config/splsp/snapshots"),
+                )
+            },
+            Path::to_path_buf,
+        );
+        Self {
+            path: dir.join(format!("{pool}.jsonl")),
+        }
+    }
+
+    pub fn append(&self, snapshot: &PoolSnapshot) -> std::io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            create_dir_all(dir)?;
+        }
+        let mut line = serde_json::to_string(snapshot).map_err(std::io::Error::other)?;
+        line.push('\n');
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?
+            .write_all(line.as_bytes())
+    }
+
+    /// Returns all snapshots in the store keyed by epoch.
+    /// If an epoch was snapshotted more than once, the latest one is returned.
+    pub fn read_by_epoch(&self) -> Result<BTreeMap<u64, PoolSnapshot>, Box<dyn Error>> {
+        let s = read_to_string(&self.path)?;
+        let mut res = BTreeMap::new();
+        for (i, line) in s.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let snapshot: PoolSnapshot = serde_json::from_str(line)
+                .map_err(|e| format!("{} line {}: {e}", self.path.display(), i + 1))?;
+            res.insert(snapshot.epoch, snapshot);
+        }
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn snapshot(epoch: u64, slot: u64, total_lamports: u64) -> PoolSnapshot {
+        PoolSnapshot {
+            epoch,
+            slot,
+            unix_timestamp: 0,
+            pool: ConfigRaw {
+                total_lamports: Some(total_lamports),
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn store_round_trip_latest_per_epoch() {
+        let dir = TempDir::new().unwrap();
+        let pool = Pubkey::new_unique();
+        let store = SnapshotStore::for_pool(Some(&dir.path().join("nested")), &pool);
+        assert!(store.read_by_epoch().is_err());
+
+        store.append(&snapshot(5, 100, 1)).unwrap();
+        store.append(&snapshot(6, 200, 2)).unwrap();
+        store.append(&snapshot(5, 150, 3)).unwrap();
+
+        let by_epoch = store.read_by_epoch().unwrap();
+        assert_eq!(by_epoch.keys().copied().collect::<Vec<_>>(), [5, 6]);
+        assert_eq!(by_epoch[&5].slot, 150);
+        assert_eq!(by_epoch[&5].pool.total_lamports, Some(3));
+        assert_eq!(by_epoch[&6].pool.total_lamports, Some(2));
+    }
+}
@@ -0,0 +1,299 @@
+//! Minimal Squads v4 multisig account layouts and instructions,
+//! just enough to create vault transactions and their proposals.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    hash::Hash,
+    instruction::{AccountMeta, Instruction},
+    message::v0::Message,
+    pubkey::Pubkey,
+    system_program,
+};
+
+pub mod squads_program {
+    sanctum_macros::declare_program_keys!("SQDS4ep65T869zMMBKyuUq6aD6EgTu8psMjkvj52pCf", []);
+}
+
+const SEED_PREFIX: &[u8] = b"multisig";
+const SEED_VAULT: &[u8] = b"vault";
+const SEED_TRANSACTION: &[u8] = b"transaction";
+const SEED_PROPOSAL: &[u8] = b"proposal";
+
+/// anchor discriminators: sha256("global:<ix_name>")[..8]
+const VAULT_TRANSACTION_CREATE_IX_DISCM: [u8; 8] = [0x30, 0xfa, 0x4e, 0xa8, 0xd0, 0xe2, 0xda, 0xd3];
+const PROPOSAL_CREATE_IX_DISCM: [u8; 8] = [0xdc, 0x3c, 0x49, 0xe0, 0x1e, 0x6c, 0x4f, 0x9f];
+
+/// sha256("account:Multisig")[..8]
+const MULTISIG_ACCOUNT_DISCM: [u8; 8] = [0xe0, 0x74, 0x79, 0xba, 0x44, 0xa1, 0x4f, 0xec];
+
+pub fn find_squads_vault(multisig: &Pubkey, vault_index: u8) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[SEED_PREFIX, multisig.as_ref(), SEED_VAULT, &[vault_index]],
+        &squads_program::ID,
+    )
+}
+
+pub fn find_squads_transaction(multisig: &Pubkey, transaction_index: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            SEED_PREFIX,
+            multisig.as_ref(),
+            SEED_TRANSACTION,
+            &transaction_index.to_le_bytes(),
+        ],
+        &squads_program::ID,
+    )
+}
+
+pub fn find_squads_proposal(multisig: &Pubkey, transaction_index: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            SEED_PREFIX,
+            multisig.as_ref(),
+            SEED_TRANSACTION,
+            &transaction_index.to_le_bytes(),
+            SEED_PROPOSAL,
+        ],
+        &squads_program::ID,
+    )
+}
+
+/// Prefix of the multisig account up to `transaction_index`.
+/// The remaining fields are not read.
+#[derive(Clone, Debug, PartialEq, Eq, BorshDeserialize, BorshSerialize)]
+pub struct SquadsMultisig {
+    pub discm: [u8; 8],
+    pub create_key: Pubkey,
+    pub config_authority: Pubkey,
+    pub threshold: u16,
+    pub time_lock: u32,
+    /// Index of the last transaction created
+    pub transaction_index: u64,
+}
+
+impl SquadsMultisig {
+    pub fn deserialize_account_data(mut data: &[u8]) -> std::io::Result<Self> {
+        // only reading a prefix, so dont use try_from_slice()
+        let res = <Self as BorshDeserialize>::deserialize(&mut data)?;
+        if res.discm != MULTISIG_ACCOUNT_DISCM {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "not a squads multisig account",
+            ));
+        }
+        Ok(res)
+    }
+}
+
+#[derive(Clone, Debug, BorshSerialize)]
+struct VaultTransactionCreateArgs {
+    vault_index: u8,
+    ephemeral_signers: u8,
+    transaction_message: Vec<u8>,
+    memo: Option<String>,
+}
+
+#[derive(Clone, Debug, BorshSerialize)]
+struct ProposalCreateArgs {
+    transaction_index: u64,
+    draft: bool,
+}
+
+/// Serializes `ixs` into squads' compact `TransactionMessage` format, with `vault` as payer.
+///
+/// Panics if `ixs` require any signer other than `vault`,
+/// since only the vault can sign when the vault transaction is executed
+pub fn squads_transaction_message(vault: &Pubkey, ixs: &[Instruction]) -> Vec<u8> {
+    let Message {
+        header,
+        account_keys,
+        instructions,
+        ..
+    } = Message::try_compile(vault, ixs, &[], Hash::default()).unwrap();
+    let num_signers = header.num_required_signatures;
+    if num_signers > 1 {
+        panic!(
+            "Only the squads vault {vault} can sign multisig transactions, but {} must also sign",
+            account_keys[1..usize::from(num_signers)]
+                .iter()
+                .map(|pk| pk.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+    // as-safety: legacy/v0 messages are limited to 256 accounts
+    let num_keys = account_keys.len() as u8;
+    let num_writable_signers = num_signers - header.num_readonly_signed_accounts;
+    let num_writable_non_signers = num_keys - num_signers - header.num_readonly_unsigned_accounts;
+
+    let mut res = vec![
+        num_signers,
+        num_writable_signers,
+        num_writable_non_signers,
+        num_keys,
+    ];
+    for key in account_keys.iter() {
+        res.extend(key.as_ref());
+    }
+    // as-safety: messages are limited to u8 number of ixs
+    res.push(instructions.len() as u8);
+    for ix in instructions {
+        res.push(ix.program_id_index);
+        // as-safety: messages are limited to 256 accounts
+        res.push(ix.accounts.len() as u8);
+        res.extend(ix.accounts);
+        // as-safety: tx size limit is < u16::MAX
+        res.extend((ix.data.len() as u16).to_le_bytes());
+        res.extend(ix.data);
+    }
+    // no address lookup tables
+    res.push(0);
+    res
+}
+
+/// Creates the vault transaction and its proposal for execution of `ixs` by the multisig vault.
+/// `member` must be a multisig member with initiate permissions and pays for the accounts' rent.
+pub fn squads_proposal_ixs(
+    multisig: &Pubkey,
+    vault_index: u8,
+    transaction_index: u64,
+    member: &Pubkey,
+    ixs: &[Instruction],
+) -> std::io::Result<[Instruction; 2]> {
+    let (vault, _bump) = find_squads_vault(multisig, vault_index);
+    let (transaction, _bump) = find_squads_transaction(multisig, transaction_index);
+    let (proposal, _bump) = find_squads_proposal(multisig, transaction_index);
+
+    let mut vault_transaction_create_data = Vec::from(VAULT_TRANSACTION_CREATE_IX_DISCM);
+    VaultTransactionCreateArgs {
+        vault_index,
+        ephemeral_signers: 0,
+        transaction_message: squads_transaction_message(&vault, ixs),
+        memo: None,
+    }
+    .serialize(&mut vault_transaction_create_data)?;
+
+    let mut proposal_create_data = Vec::from(PROPOSAL_CREATE_IX_DISCM);
+    ProposalCreateArgs {
+        transaction_index,
+        draft: false,
+    }
+    .serialize(&mut proposal_create_data)?;
+
+    Ok([
+        Instruction {
+            program_id: squads_program::ID,
+            accounts: vec![
+                AccountMeta::new(*multisig, false),
+                AccountMeta::new(transaction, false),
+                AccountMeta::new_readonly(*member, true),
+                AccountMeta::new(*member, true),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ],
+            data: vault_transaction_create_data,
+        },
+        Instruction {
+            program_id: squads_program::ID,
+            accounts: vec![
+                AccountMeta::new_readonly(*multisig, false),
+                AccountMeta::new(proposal, false),
+                AccountMeta::new_readonly(*member, true),
+                AccountMeta::new(*member, true),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ],
+            data: proposal_create_data,
+        },
+    ])
+}
+
+/// Wraps txs into squads proposals for a multisig vault that is the pool's manager or staker
+#[derive(Clone, Copy, Debug)]
+pub struct SquadsProposer {
+    pub multisig: Pubkey,
+    pub vault_index: u8,
+    pub vault: Pubkey,
+
+    /// Transaction index the next proposal will be created with
+    pub next_transaction_index: u64,
+}
+
+impl SquadsProposer {
+    pub async fn fetch(rpc: &RpcClient, multisig: Pubkey, vault_index: u8) -> Self {
+        let acc = rpc.get_account(&multisig).await.unwrap();
+        if acc.owner != squads_program::ID {
+            panic!("{multisig} is not a squads v4 multisig");
+        }
+        let SquadsMultisig {
+            transaction_index, ..
+        } = SquadsMultisig::deserialize_account_data(&acc.data).unwrap();
+        let (vault, _bump) = find_squads_vault(&multisig, vault_index);
+        Self {
+            multisig,
+            vault_index,
+            vault,
+            next_transaction_index: transaction_index + 1,
+        }
+    }
+
+    /// Returns the ixs to create a new proposal for `ixs`.
+    /// Each call creates a new proposal with the next transaction index.
+    pub fn proposal_ixs(&mut self, member: &Pubkey, ixs: &[Instruction]) -> Vec<Instruction> {
+        let res = squads_proposal_ixs(
+            &self.multisig,
+            self.vault_index,
+            self.next_transaction_index,
+            member,
+            ixs,
+        )
+        .unwrap();
+        eprintln!(
+            "Creating squads proposal for multisig {} transaction #{}",
+            self.multisig, self.next_transaction_index
+        );
+        self.next_transaction_index += 1;
+        res.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transaction_message_header() {
+        let vault = Pubkey::new_unique();
+        let writable = Pubkey::new_unique();
+        let readonly = Pubkey::new_unique();
+        let program = Pubkey::new_unique();
+        let ix = Instruction {
+            program_id: program,
+            accounts: vec![
+                AccountMeta::new(writable, false),
+                AccountMeta::new_readonly(vault, true),
+                AccountMeta::new_readonly(readonly, false),
+            ],
+            data: vec![1, 2, 3],
+        };
+        let msg = squads_transaction_message(&vault, &[ix]);
+        // vault is payer so its writable
+        assert_eq!(msg[..4], [1, 1, 1, 4]);
+        assert_eq!(msg[4..36], *vault.as_ref());
+        assert_eq!(msg[4 + 4 * 32], 1);
+        // program_id_index, 3 accounts, then 3 bytes data, then 0 luts
+        assert_eq!(msg.len(), 4 + 4 * 32 + 1 + 1 + 1 + 3 + 2 + 3 + 1);
+        assert_eq!(msg[msg.len() - 6..], [3, 0, 1, 2, 3, 0]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn transaction_message_rejects_other_signers() {
+        let vault = Pubkey::new_unique();
+        let ix = Instruction {
+            program_id: Pubkey::new_unique(),
+            accounts: vec![AccountMeta::new_readonly(Pubkey::new_unique(), true)],
+            data: vec![],
+        };
+        squads_transaction_message(&vault, &[ix]);
+    }
+}
@@ -0,0 +1,49 @@
+use std::path::PathBuf;
+
+use clap::Args;
+
+use crate::pool_config::{Plan, PlanSrc, SyncMode};
+
+use super::{
+    Subcmd, SyncArgs, SyncDelegationArgs, SyncPoolArgs, SyncTokenMetadataArgs,
+    SyncValidatorListArgs,
+};
+
+#[derive(Args, Debug)]
+#[command(
+    long_about = "Execute the changes recorded in a plan file output by a sync command's --plan. Aborts before sending anything if on-chain state has drifted from what the plan assumed.
+
+Pending stake changes of a `sync` plan, i.e. delegations to validators being added or of a pool not yet updated for the current epoch, are only computed after the update crank and validator list sync."
+)]
+pub struct ApplyArgs {
+    #[arg(
+        help = "Path to plan file. The sync command is rerun with the pool config file recorded in it and must compute exactly the recorded changes."
+    )]
+    pub plan_file: PathBuf,
+}
+
+impl ApplyArgs {
+    pub async fn run(args: crate::Args) {
+        let Self { plan_file } = match &args.subcmd {
+            Subcmd::Apply(a) => a,
+            _ => unreachable!(),
+        };
+
+        let Plan { changes, src, .. } = Plan::read_from_path(plan_file).unwrap();
+        let mode = SyncMode::Apply(&changes);
+
+        match src {
+            PlanSrc::Sync(config) => SyncArgs::run_with_config(&args, config, mode).await,
+            PlanSrc::SyncDelegation(config) => {
+                SyncDelegationArgs::run_with_config(&args, config, mode).await
+            }
+            PlanSrc::SyncPool(config) => SyncPoolArgs::run_with_config(&args, config, mode).await,
+            PlanSrc::SyncTokenMetadata(config) => {
+                SyncTokenMetadataArgs::run_with_config(&args, config, mode).await
+            }
+            PlanSrc::SyncValidatorList(config) => {
+                SyncValidatorListArgs::run_with_config(&args, config, mode).await
+            }
+        }
+    }
+}
@@ -0,0 +1,277 @@
+use std::{
+    any::Any,
+    panic::AssertUnwindSafe,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use borsh::BorshDeserialize;
+use clap::Args;
+use futures::FutureExt;
+use sanctum_solana_cli_utils::PubkeySrc;
+use serde::Serialize;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_readonly_account::keyed::Keyed;
+use solana_sdk::{
+    address_lookup_table::AddressLookupTableAccount, clock::Clock, pubkey::Pubkey, signer::Signer,
+    sysvar,
+};
+use spl_stake_pool_interface::StakePool;
+
+use crate::{
+    update::{update_pool, UpdatePoolArgs},
+    CrankConfigToml, TxNonce, UpdateCtrl,
+};
+
+use super::{FetchedPool, Subcmd};
+
+#[derive(Args, Debug)]
+#[command(long_about = "Run the update crank for a list of pools every epoch.
+
+Polls the clock sysvar and runs `update` for every pool in the crank config file on start and on each epoch change,
+retrying failed pools with exponential backoff.
+Logs a summary to stderr for each epoch cranked.
+
+Example crank config file:
+
+[crank]
+pools = [\"<pool pubkey>\", \"<pool pubkey>\"]")]
+pub struct CrankArgs {
+    #[arg(
+        long,
+        default_value_t = 30,
+        help = "Number of seconds to wait between polls of the clock sysvar"
+    )]
+    pub poll_interval: u64,
+
+    #[arg(
+        long,
+        default_value_t = 5,
+        help = "Number of times to retry a pool's update crank on failure before giving up for the epoch"
+    )]
+    pub max_retries: u32,
+
+    #[arg(
+        long,
+        default_value_t = 5,
+        help = "Number of seconds to wait before the first retry. Doubled on every subsequent retry."
+    )]
+    pub backoff: u64,
+
+    #[arg(
+        long,
+        help = "Exit after cranking this many epochs. Runs forever if not provided."
+    )]
+    pub max_epochs: Option<u64>,
+
+    #[arg(help = "Path to crank config file")]
+    pub crank_config: PathBuf,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PoolCrankStatus {
+    Updated,
+    AlreadyUpdated,
+    Failed,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct PoolCrankSummary {
+    pub pool: String,
+    pub status: PoolCrankStatus,
+    pub attempts: u32,
+
+    /// Error of the last failed attempt, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct EpochCrankSummary {
+    pub epoch: u64,
+    pub duration_secs: u64,
+    pub pools: Vec<PoolCrankSummary>, // put this last so it gets outputted last in toml Serialize
+}
+
+impl CrankArgs {
+    pub async fn run(args: crate::Args) {
+        let Self {
+            poll_interval,
+            max_retries,
+            backoff,
+            max_epochs,
+            crank_config,
+        } = match &args.subcmd {
+            Subcmd::Crank(a) => a,
+            _ => unreachable!(),
+        };
+
+        let CrankConfigToml { pools } = CrankConfigToml::read_from_path(crank_config).unwrap();
+        let pools: Vec<Pubkey> = pools
+            .iter()
+            .map(|p| PubkeySrc::parse(p).unwrap().pubkey())
+            .collect();
+
+        let rpc = args.config.nonblocking_rpc_client();
+        let payer = args.config.signer();
+        let nonce = args.tx_nonce();
+        let luts = args.luts(&rpc).await;
+
+        let crank = PoolCranker {
+            args: &args,
+            rpc: &rpc,
+            nonce: nonce.as_ref(),
+            luts: &luts,
+            payer: payer.as_ref(),
+            max_retries: *max_retries,
+            backoff: Duration::from_secs(*backoff),
+        };
+
+        let mut last_cranked_epoch = None;
+        let mut epochs_cranked = 0;
+        loop {
+            match fetch_clock(&rpc).await {
+                Ok(Clock { epoch, .. }) if last_cranked_epoch != Some(epoch) => {
+                    eprintln!("Cranking epoch {epoch}");
+                    let start = Instant::now();
+                    let mut summaries = Vec::with_capacity(pools.len());
+                    for pool in pools.iter() {
+                        summaries.push(crank.crank_pool(pool).await);
+                    }
+                    let summary = EpochCrankSummary {
+                        epoch,
+                        duration_secs: start.elapsed().as_secs(),
+                        pools: summaries,
+                    };
+                    eprintln!("{}", args.output.format("crank", &summary));
+
+                    last_cranked_epoch = Some(epoch);
+                    epochs_cranked += 1;
+                    if max_epochs.is_some_and(|max| epochs_cranked >= max) {
+                        return;
+                    }
+                }
+                Ok(_) => (),
+                Err(e) => eprintln!("Failed to fetch clock: {e}"),
+            }
+            tokio::time::sleep(Duration::from_secs(*poll_interval)).await;
+        }
+    }
+}
+
+async fn fetch_clock(rpc: &RpcClient) -> Result<Clock, String> {
+    let acc = rpc
+        .get_account(&sysvar::clock::ID)
+        .await
+        .map_err(|e| e.to_string())?;
+    bincode::deserialize(&acc.data).map_err(|e| e.to_string())
+}
+
+/// Runs the update crank for individual pools, shared by `crank` and `scan --crank`
+pub(crate) struct PoolCranker<'a> {
+    pub args: &'a crate::Args,
+    pub rpc: &'a RpcClient,
+    pub nonce: Option<&'a TxNonce>,
+    pub luts: &'a [AddressLookupTableAccount],
+    pub payer: &'a (dyn Signer + 'static),
+    pub max_retries: u32,
+    pub backoff: Duration,
+}
+
+impl PoolCranker<'_> {
+    /// Runs the update crank for `pool`, retrying with exponential backoff on failure.
+    ///
+    /// Failures panic everywhere else in this crate, so panics are caught here
+    /// to keep the crank running for the other pools and epochs.
+    pub async fn crank_pool(&self, pool: &Pubkey) -> PoolCrankSummary {
+        let mut backoff = self.backoff;
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let res = AssertUnwindSafe(self.try_crank_pool(pool))
+                .catch_unwind()
+                .await;
+            let error = match res {
+                Ok(status) => {
+                    return PoolCrankSummary {
+                        pool: pool.to_string(),
+                        status,
+                        attempts,
+                        error: None,
+                    }
+                }
+                Err(e) => panic_msg(e),
+            };
+            eprintln!("Attempt {attempts} to crank pool {pool} failed: {error}");
+            if attempts > self.max_retries {
+                return PoolCrankSummary {
+                    pool: pool.to_string(),
+                    status: PoolCrankStatus::Failed,
+                    attempts,
+                    error: Some(error),
+                };
+            }
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+        }
+    }
+
+    async fn try_crank_pool(&self, pool: &Pubkey) -> PoolCrankStatus {
+        let FetchedPool {
+            program_id,
+            epoch: current_epoch,
+            stake_pool_acc,
+            validators,
+        } = FetchedPool::fetch(self.rpc, pool).await;
+
+        let StakePool {
+            last_update_epoch, ..
+        } = StakePool::deserialize(&mut stake_pool_acc.data.as_slice()).unwrap();
+        let has_stale_validators = validators
+            .iter()
+            .any(|vsi| vsi.last_update_epoch < current_epoch);
+        let ctrl = match (last_update_epoch >= current_epoch, has_stale_validators) {
+            (true, false) => return PoolCrankStatus::AlreadyUpdated,
+            // IfNeeded skips the validator list of pools already updated for the epoch,
+            // e.g. if entries were left behind by a partially landed update
+            (true, true) => UpdateCtrl::ForceAll,
+            (false, _) => UpdateCtrl::IfNeeded,
+        };
+
+        update_pool(UpdatePoolArgs {
+            rpc: self.rpc,
+            send_mode: self.args.send_mode,
+            nonce: self.nonce,
+            luts: self.luts,
+            payer: self.payer,
+            program_id,
+            current_epoch,
+            stake_pool: Keyed {
+                pubkey: *pool,
+                account: &stake_pool_acc,
+            },
+            validator_list_entries: &validators,
+            fee_limit_cb: self.args.fee_limit_cb,
+            ctrl,
+            no_merge: false,
+            // retries refetch the validator list, so IfNeeded already skips chunks that landed.
+            // ForceAll redoes updated chunks unless the journal says they landed
+            resume: ctrl == UpdateCtrl::ForceAll,
+        })
+        .await;
+        PoolCrankStatus::Updated
+    }
+}
+
+fn panic_msg(e: Box<dyn Any + Send>) -> String {
+    match e.downcast::<String>() {
+        Ok(s) => *s,
+        Err(e) => match e.downcast::<&'static str>() {
+            Ok(s) => (*s).to_owned(),
+            Err(_) => "unknown panic".to_owned(),
+        },
+    }
+}
@@ -0,0 +1,112 @@
+use std::str::FromStr;
+
+use borsh::BorshDeserialize;
+use clap::{
+    builder::{StringValueParser, TypedValueParser},
+    Args,
+};
+use sanctum_solana_cli_utils::TxSendMode;
+use solana_sdk::{
+    address_lookup_table::{
+        instruction::{create_lookup_table, extend_lookup_table},
+        state::LOOKUP_TABLE_MAX_ADDRESSES,
+    },
+    pubkey::Pubkey,
+};
+use spl_stake_pool_interface::{StakePool, ValidatorList};
+
+use crate::{
+    handle_tx_full, max_extend_lut_addresses_per_tx, pool_lut_addresses, ps, with_auto_cb_ixs,
+};
+
+use super::Subcmd;
+
+#[derive(Args, Debug)]
+#[command(
+    long_about = "Create an address lookup table containing all of a stake pool's static accounts and validator stake accounts, for use with --lut"
+)]
+pub struct CreateLutArgs {
+    #[arg(
+        long,
+        short,
+        help = "Authority of the lookup table that can extend it. Defaults to payer if not provided"
+    )]
+    pub authority: Option<String>,
+
+    #[arg(
+        help = "Pubkey of the pool to create the lookup table for",
+        value_parser = StringValueParser::new().try_map(|s| Pubkey::from_str(&s)),
+    )]
+    pub pool: Pubkey,
+}
+
+impl CreateLutArgs {
+    pub async fn run(args: crate::Args) {
+        // args methods borrow all of args so they must be called before subcmd is moved out
+        let rpc = args.config.nonblocking_rpc_client();
+        let payer = args.config.signer();
+        let nonce = args.tx_nonce();
+        let luts = args.luts(&rpc).await;
+
+        let Self { authority, pool } = match args.subcmd {
+            Subcmd::CreateLut(a) => a,
+            _ => unreachable!(),
+        };
+
+        ps!(authority, @fb payer.as_ref(), @sm args.send_mode);
+
+        let stake_pool_acc = rpc.get_account(&pool).await.unwrap();
+        let program_id = stake_pool_acc.owner;
+        let stake_pool = StakePool::deserialize(&mut stake_pool_acc.data.as_slice()).unwrap();
+        let validator_list_acc = rpc.get_account(&stake_pool.validator_list).await.unwrap();
+        let ValidatorList { validators, .. } =
+            ValidatorList::deserialize(&mut validator_list_acc.data.as_slice()).unwrap();
+
+        let addresses = pool_lut_addresses(&program_id, &pool, &stake_pool, &validators);
+        if addresses.len() > LOOKUP_TABLE_MAX_ADDRESSES {
+            panic!(
+                "Pool requires {} addresses, more than a lookup table can hold",
+                addresses.len()
+            );
+        }
+
+        let recent_slot = rpc.get_slot().await.unwrap();
+        let (create_ix, lut) = create_lookup_table(authority.pubkey(), payer.pubkey(), recent_slot);
+        eprintln!(
+            "Creating lookup table {lut} with {} addresses",
+            addresses.len()
+        );
+
+        for (i, chunk) in addresses
+            .chunks(max_extend_lut_addresses_per_tx(nonce.as_ref()))
+            .enumerate()
+        {
+            let extend_ix = extend_lookup_table(
+                lut,
+                authority.pubkey(),
+                Some(payer.pubkey()),
+                chunk.to_vec(),
+            );
+            let ixs = if i == 0 {
+                vec![create_ix.clone(), extend_ix]
+            } else {
+                vec![extend_ix]
+            };
+            let ixs = match args.send_mode {
+                TxSendMode::DumpMsg => ixs,
+                _ => with_auto_cb_ixs(&rpc, &payer.pubkey(), ixs, &luts, args.fee_limit_cb).await,
+            };
+            handle_tx_full(
+                &rpc,
+                args.send_mode,
+                nonce.as_ref(),
+                &ixs,
+                &luts,
+                &mut [payer.as_ref(), authority],
+            )
+            .await;
+        }
+
+        println!("{lut}");
+    }
+}
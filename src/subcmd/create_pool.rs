@@ -16,7 +16,10 @@ use solana_sdk::{
 };
 use spl_associated_token_account_interface::CreateIdempotentKeys;
 use spl_stake_pool_interface::{AccountType, Fee, FutureEpochFee, Lockup, StakePool};
-use spl_token_2022::{extension::StateWithExtensions, state::Mint};
+use spl_token_2022::{
+    extension::{BaseStateWithExtensions, ExtensionType, StateWithExtensions},
+    state::Mint,
+};
 
 use crate::{
     parse::filter_default_stake_deposit_auth,
@@ -26,7 +29,7 @@ use crate::{
     },
     ps,
     subcmd::Subcmd,
-    tx_utils::{handle_tx_full, with_auto_cb_ixs, MAX_ADD_VALIDATORS_IX_PER_TX},
+    tx_utils::{handle_tx_full, max_add_validators_ix_per_tx, with_auto_cb_ixs},
 };
 
 #[derive(Args, Debug)]
@@ -38,6 +41,12 @@ pub struct CreatePoolArgs {
 
 impl CreatePoolArgs {
     pub async fn run(args: crate::Args) {
+        // args methods borrow all of args so they must be called before subcmd is moved out
+        let rpc = args.config.nonblocking_rpc_client();
+        let payer = args.config.signer();
+        let nonce = args.tx_nonce();
+        let luts = args.luts(&rpc).await;
+
         let Self { pool_config } = match args.subcmd {
             Subcmd::CreatePool(a) => a,
             _ => unreachable!(),
@@ -69,8 +78,6 @@ impl CreatePoolArgs {
             ..
         } = ConfigRaw::read_from_path(pool_config).unwrap();
 
-        let rpc = args.config.nonblocking_rpc_client();
-        let payer = args.config.signer();
         let program_id = program
             .expect("stake pool program was not provided")
             .program_id();
@@ -246,14 +253,16 @@ impl CreatePoolArgs {
             let first_ixs = match args.send_mode {
                 TxSendMode::DumpMsg => first_ixs,
                 _ => {
-                    with_auto_cb_ixs(&rpc, &payer.pubkey(), first_ixs, &[], args.fee_limit_cb).await
+                    with_auto_cb_ixs(&rpc, &payer.pubkey(), first_ixs, &luts, args.fee_limit_cb)
+                        .await
                 }
             };
             handle_tx_full(
                 &rpc,
                 args.send_mode,
+                nonce.as_ref(),
                 &first_ixs,
-                &[],
+                &luts,
                 &mut cc.create_reserve_tx_signers_maybe_dup(),
             )
             .await;
@@ -262,13 +271,14 @@ impl CreatePoolArgs {
         let ixs = Vec::from(cc.initialize_tx_ixs().unwrap());
         let ixs = match args.send_mode {
             TxSendMode::DumpMsg => ixs,
-            _ => with_auto_cb_ixs(&rpc, &payer.pubkey(), ixs, &[], args.fee_limit_cb).await,
+            _ => with_auto_cb_ixs(&rpc, &payer.pubkey(), ixs, &luts, args.fee_limit_cb).await,
         };
         handle_tx_full(
             &rpc,
             args.send_mode,
+            nonce.as_ref(),
             &ixs,
-            &[],
+            &luts,
             &mut cc.initialize_tx_signers_maybe_dup(),
         )
         .await;
@@ -339,13 +349,13 @@ impl CreatePoolArgs {
 
         // starting validator list is empty
         let (add, _remove) = svlc.add_remove_changeset(&[]);
-        print_adding_validators_msg(add.clone());
+        print_adding_validators_msg(args.output, add.clone());
 
         for add_validator_ix_chunk in svlc
             .add_validators_ixs(add)
             .unwrap()
             .as_slice()
-            .chunks(MAX_ADD_VALIDATORS_IX_PER_TX)
+            .chunks(max_add_validators_ix_per_tx(&luts, nonce.as_ref(), None))
         {
             let add_validator_ix_chunk = match args.send_mode {
                 TxSendMode::DumpMsg => Vec::from(add_validator_ix_chunk),
@@ -354,7 +364,7 @@ impl CreatePoolArgs {
                         &rpc,
                         &payer.pubkey(),
                         Vec::from(add_validator_ix_chunk),
-                        &[],
+                        &luts,
                         args.fee_limit_cb,
                     )
                     .await
@@ -363,8 +373,9 @@ impl CreatePoolArgs {
             handle_tx_full(
                 &rpc,
                 args.send_mode,
+                nonce.as_ref(),
                 &add_validator_ix_chunk,
-                &[],
+                &luts,
                 &mut svlc.signers_maybe_dup(),
             )
             .await;
@@ -373,9 +384,10 @@ impl CreatePoolArgs {
         // set preferred validators after since
         // we can only set preferred validators after adding the validators to the list
         let preferred_validator_changes = svlc.preferred_validator_changeset(&dummy_created_pool);
-        for change in preferred_validator_changes.clone() {
-            eprintln!("{change}");
-        }
+        args.output.eprint_changes(
+            "preferred-validator-changes",
+            &preferred_validator_changes.clone().collect::<Vec<_>>(),
+        );
         let preferred_validator_ixs = svlc
             .preferred_validator_ixs(preferred_validator_changes)
             .unwrap();
@@ -387,7 +399,7 @@ impl CreatePoolArgs {
                         &rpc,
                         &payer.pubkey(),
                         preferred_validator_ixs,
-                        &[],
+                        &luts,
                         args.fee_limit_cb,
                     )
                     .await
@@ -396,8 +408,9 @@ impl CreatePoolArgs {
             handle_tx_full(
                 &rpc,
                 args.send_mode,
+                nonce.as_ref(),
                 &preferred_validator_ixs,
-                &[],
+                &luts,
                 &mut svlc.signers_maybe_dup(),
             )
             .await;
@@ -427,23 +440,28 @@ impl CreatePoolArgs {
         };
 
         let changeset = spc.changeset(&dummy_created_pool);
-        for change in changeset.iter() {
-            eprintln!("{change}");
-        }
+        args.output.eprint_changes("pool-changes", &changeset);
         let sync_pool_ixs = spc.changeset_ixs(&changeset).unwrap();
         if !sync_pool_ixs.is_empty() {
             let sync_pool_ixs = match args.send_mode {
                 TxSendMode::DumpMsg => sync_pool_ixs,
                 _ => {
-                    with_auto_cb_ixs(&rpc, &payer.pubkey(), sync_pool_ixs, &[], args.fee_limit_cb)
-                        .await
+                    with_auto_cb_ixs(
+                        &rpc,
+                        &payer.pubkey(),
+                        sync_pool_ixs,
+                        &luts,
+                        args.fee_limit_cb,
+                    )
+                    .await
                 }
             };
             handle_tx_full(
                 &rpc,
                 args.send_mode,
+                nonce.as_ref(),
                 &sync_pool_ixs,
-                &[],
+                &luts,
                 &mut spc.signers_maybe_dup(),
             )
             .await;
@@ -451,8 +469,34 @@ impl CreatePoolArgs {
     }
 }
 
+/// Mirrors the spl stake pool program's `is_extension_supported_for_mint()`
+const SUPPORTED_MINT_EXTENSIONS: [ExtensionType; 8] = [
+    ExtensionType::Uninitialized,
+    ExtensionType::TransferFeeConfig,
+    ExtensionType::ConfidentialTransferMint,
+    ExtensionType::ConfidentialTransferFeeConfig,
+    ExtensionType::DefaultAccountState,
+    ExtensionType::InterestBearingConfig,
+    ExtensionType::MetadataPointer,
+    ExtensionType::TokenMetadata,
+];
+
 fn verify_mint(mint: &Account, manager_pk: &Pubkey) -> Result<(), Box<dyn Error>> {
-    let StateWithExtensions { base: mint, .. } = StateWithExtensions::<Mint>::unpack(&mint.data)?;
+    let mint_state = StateWithExtensions::<Mint>::unpack(&mint.data)?;
+    let unsupported: Vec<String> = mint_state
+        .get_extension_types()?
+        .into_iter()
+        .filter(|ext| !SUPPORTED_MINT_EXTENSIONS.contains(ext))
+        .map(|ext| format!("{ext:?}"))
+        .collect();
+    if !unsupported.is_empty() {
+        return Err(format!(
+            "Mint has extensions not supported by the stake pool program: {}",
+            unsupported.join(", ")
+        )
+        .into());
+    }
+    let mint = mint_state.base;
     if mint.decimals != 9 {
         return Err("Mint not of 9 decimals".into());
     }
@@ -470,7 +514,6 @@ fn verify_mint(mint: &Account, manager_pk: &Pubkey) -> Result<(), Box<dyn Error>
             }
         }
     }
-    // TODO: verify acceptable extensions
     Ok(())
 }
 
@@ -480,3 +523,59 @@ fn select_higher_fee(f1: &Fee, f2: &Fee) -> Fee {
         Ordering::Greater => f1.clone(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use spl_token_2022::extension::{
+        metadata_pointer::MetadataPointer, mint_close_authority::MintCloseAuthority,
+        BaseStateWithExtensionsMut, StateWithExtensionsMut,
+    };
+
+    use super::*;
+
+    /// A mint account otherwise valid for pool creation with the single extension `ext`,
+    /// initialized by `init_ext`
+    fn mint_with_extension(
+        manager: &Pubkey,
+        ext: ExtensionType,
+        init_ext: impl FnOnce(&mut StateWithExtensionsMut<Mint>),
+    ) -> Account {
+        let len = ExtensionType::try_calculate_account_len::<Mint>(&[ext]).unwrap();
+        let mut data = vec![0; len];
+        let mut state = StateWithExtensionsMut::<Mint>::unpack_uninitialized(&mut data).unwrap();
+        init_ext(&mut state);
+        state.base = Mint {
+            mint_authority: COption::Some(*manager),
+            supply: 0,
+            decimals: 9,
+            is_initialized: true,
+            freeze_authority: COption::None,
+        };
+        state.pack_base();
+        state.init_account_type().unwrap();
+        Account {
+            data,
+            owner: spl_token_2022::ID,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn verify_mint_allows_supported_extension() {
+        let manager = Pubkey::new_unique();
+        let mint = mint_with_extension(&manager, ExtensionType::MetadataPointer, |state| {
+            state.init_extension::<MetadataPointer>(true).unwrap();
+        });
+        verify_mint(&mint, &manager).unwrap();
+    }
+
+    #[test]
+    fn verify_mint_rejects_unsupported_extension() {
+        let manager = Pubkey::new_unique();
+        let mint = mint_with_extension(&manager, ExtensionType::MintCloseAuthority, |state| {
+            state.init_extension::<MintCloseAuthority>(true).unwrap();
+        });
+        let err = verify_mint(&mint, &manager).unwrap_err().to_string();
+        assert!(err.contains("MintCloseAuthority"), "{err}");
+    }
+}
@@ -0,0 +1,116 @@
+use std::{path::PathBuf, str::FromStr};
+
+use borsh::BorshDeserialize;
+use clap::{
+    builder::{StringValueParser, TypedValueParser},
+    Args,
+};
+use solana_sdk::pubkey::Pubkey;
+use spl_stake_pool_interface::{AccountType, StakePool, ValidatorList};
+
+use crate::{pool_config::ConfigRaw, AccountFile, SplStakePoolProgram};
+
+use super::Subcmd;
+
+#[derive(Args, Debug)]
+#[command(
+    long_about = "Decode stake pool and/or validator list account data from files into pool info without RPC access.
+
+Each file can either be:
+- `solana account --output json` output, the same format as the accounts in test-fixtures/
+- a bare UiAccount json, as returned by getAccountInfo
+- base64-encoded account data"
+)]
+pub struct DecodeArgs {
+    #[arg(
+        long,
+        help = "Pubkey of the stake pool. Required if no stake pool account file with a pubkey is provided.",
+        value_parser = StringValueParser::new().try_map(|s| Pubkey::from_str(&s)),
+    )]
+    pub pool: Option<Pubkey>,
+
+    #[arg(
+        long,
+        help = "The SPL stake pool program that owns the accounts. Required if no account file with an owner is provided.",
+        value_parser = StringValueParser::new().try_map(|s| SplStakePoolProgram::parse(&s)),
+    )]
+    pub program: Option<SplStakePoolProgram>,
+
+    #[arg(
+        required = true,
+        help = "Paths to the stake pool and/or validator list account files"
+    )]
+    pub account_files: Vec<PathBuf>,
+}
+
+impl DecodeArgs {
+    pub async fn run(args: crate::Args) {
+        let Self {
+            pool,
+            program,
+            account_files,
+        } = match args.subcmd {
+            Subcmd::Decode(a) => a,
+            _ => unreachable!(),
+        };
+
+        let mut decoded_pool: Option<(Option<Pubkey>, StakePool)> = None;
+        let mut decoded_validator_list: Option<ValidatorList> = None;
+        let mut owner: Option<Pubkey> = None;
+        for path in account_files.iter() {
+            let AccountFile {
+                pubkey,
+                owner: file_owner,
+                data,
+            } = AccountFile::read_from_path(path)
+                .unwrap_or_else(|e| panic!("Failed to read {}: {e}", path.display()));
+            owner = owner.or(file_owner);
+            match data.first() {
+                Some(b) if *b == AccountType::StakePool as u8 => {
+                    if decoded_pool.is_some() {
+                        panic!("More than one stake pool account provided");
+                    }
+                    let sp = StakePool::deserialize(&mut data.as_slice()).unwrap();
+                    decoded_pool = Some((pubkey, sp));
+                }
+                Some(b) if *b == AccountType::ValidatorList as u8 => {
+                    if decoded_validator_list.is_some() {
+                        panic!("More than one validator list account provided");
+                    }
+                    let vl = ValidatorList::deserialize(&mut data.as_slice()).unwrap();
+                    decoded_validator_list = Some(vl);
+                }
+                _ => panic!(
+                    "{} is not a stake pool or validator list account",
+                    path.display()
+                ),
+            }
+        }
+
+        let program_id = program
+            .map(|p| p.program_id())
+            .or(owner)
+            .unwrap_or_else(|| {
+                panic!(
+                    "Could not determine stake pool program from account files, provide --program"
+                )
+            });
+        let pool = pool
+            .or(decoded_pool.as_ref().and_then(|(pk, _)| *pk))
+            .unwrap_or_else(|| {
+                panic!("Could not determine stake pool pubkey from account files, provide --pool")
+            });
+
+        let mut display = ConfigRaw::default();
+        display.set_pool_pk(pool);
+        display.set_program(program_id);
+        if let Some((_, sp)) = decoded_pool.as_ref() {
+            display.set_pool(&program_id, pool, sp);
+        }
+        if let Some(vl) = decoded_validator_list.as_ref() {
+            display.set_validator_list(&program_id, &pool, vl);
+        }
+
+        args.output.print("pool", &display);
+    }
+}
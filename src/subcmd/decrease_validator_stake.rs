@@ -41,6 +41,12 @@ pub struct DecreaseValidatorStakeArgs {
 
 impl DecreaseValidatorStakeArgs {
     pub async fn run(args: crate::Args) {
+        // args methods borrow all of args so they must be called before subcmd is moved out
+        let rpc = args.config.nonblocking_rpc_client();
+        let payer = args.config.signer();
+        let nonce = args.tx_nonce();
+        let luts = args.luts(&rpc).await;
+
         let Self {
             pool_config,
             validator,
@@ -53,9 +59,6 @@ impl DecreaseValidatorStakeArgs {
 
         let ConfigRaw { pool, staker, .. } = ConfigRaw::read_from_path(pool_config).unwrap();
 
-        let rpc = args.config.nonblocking_rpc_client();
-        let payer = args.config.signer();
-
         ps!(staker, @fb payer.as_ref(), @sm args.send_mode);
 
         let pool = PubkeySrc::parse(pool.as_ref().unwrap()).unwrap().pubkey();
@@ -147,20 +150,21 @@ impl DecreaseValidatorStakeArgs {
             }
         };
         let changes = sdc.changeset(std::iter::once((vsi, &vsa, &tsa, desired_stake)));
-        changes.print_all_changes();
+        changes.print_all_changes(args.output);
 
         // should only have 1 ix
         let ixs: Vec<Instruction> = sdc.sync_delegation_ixs(changes).collect();
         if !ixs.is_empty() {
             let ixs = match args.send_mode {
                 TxSendMode::DumpMsg => ixs,
-                _ => with_auto_cb_ixs(&rpc, &payer.pubkey(), ixs, &[], args.fee_limit_cb).await,
+                _ => with_auto_cb_ixs(&rpc, &payer.pubkey(), ixs, &luts, args.fee_limit_cb).await,
             };
             handle_tx_full(
                 &rpc,
                 args.send_mode,
+                nonce.as_ref(),
                 &ixs,
-                &[],
+                &luts,
                 &mut sdc.signers_maybe_dup(),
             )
             .await;
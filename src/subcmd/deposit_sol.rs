@@ -15,7 +15,8 @@ use spl_associated_token_account_interface::CreateIdempotentKeys;
 use spl_stake_pool_interface::{StakePool, ValidatorList};
 
 use crate::{
-    handle_tx_full, ps, update_pool, with_auto_cb_ixs, Subcmd, UpdateCtrl, UpdatePoolArgs,
+    handle_tx_full, ps, update_pool, with_auto_cb_ixs, PoolQuoter, SlippageArgs, Subcmd,
+    UpdateCtrl, UpdatePoolArgs,
 };
 
 #[derive(Args, Debug)]
@@ -45,22 +46,30 @@ pub struct DepositSolArgs {
         value_parser = StringValueParser::new().map(|s| TokenAmtParser::new(9).parse(&s).unwrap()),
     )]
     pub sol: TokenAmt,
+
+    #[command(flatten)]
+    pub slippage: SlippageArgs,
 }
 
 impl DepositSolArgs {
     pub async fn run(args: crate::Args) {
+        // args methods borrow all of args so they must be called before subcmd is moved out
+        let rpc = args.config.nonblocking_rpc_client();
+        let payer = args.config.signer();
+        let nonce = args.tx_nonce();
+        let luts = args.luts(&rpc).await;
+
         let Self {
             from,
             mint_to,
             pool,
             sol,
+            slippage,
         } = match args.subcmd {
             Subcmd::DepositSol(a) => a,
             _ => unreachable!(),
         };
 
-        let rpc = args.config.nonblocking_rpc_client();
-        let payer = args.config.signer();
         ps!(from, @fb payer.as_ref(), @sm args.send_mode);
 
         let pool = PubkeySrc::parse(&pool).unwrap().pubkey();
@@ -106,6 +115,8 @@ impl DepositSolArgs {
         update_pool(UpdatePoolArgs {
             rpc: &rpc,
             send_mode: args.send_mode,
+            nonce: nonce.as_ref(),
+            luts: &luts,
             payer: payer.as_ref(),
             program_id,
             current_epoch,
@@ -117,9 +128,18 @@ impl DepositSolArgs {
             fee_limit_cb: args.fee_limit_cb,
             ctrl: UpdateCtrl::IfNeeded,
             no_merge: false,
+            resume: false,
         })
         .await;
 
+        // refetch to compute expected amount against the freshly updated pool
+        let fetched_pool = rpc.get_account(&pool).await.unwrap();
+        let decoded_pool =
+            <StakePool as borsh::BorshDeserialize>::deserialize(&mut fetched_pool.data.as_ref())
+                .unwrap();
+        let quote = PoolQuoter::new(&decoded_pool, current_epoch).deposit_sol(sol.amt);
+        let min_out = slippage.min_out(quote.pool_tokens_out);
+
         let mut ixs = vec![];
         if maybe_fetched_mint_to.is_none() {
             if !is_mint_to_authority_ata {
@@ -144,8 +164,11 @@ impl DepositSolArgs {
 
         // manually craft deposit sol instruction here because i fukt up
         // and wrote sanctum_spl_stake_pool_lib into unresolvable dependency hell
-        let mut data = vec![14];
+        //
+        // DepositSolWithSlippage
+        let mut data = vec![25];
         data.extend_from_slice(&sol.amt.to_le_bytes());
+        data.extend_from_slice(&min_out.to_le_bytes());
         let mut accounts = vec![
             AccountMeta {
                 pubkey: pool,
@@ -212,13 +235,26 @@ impl DepositSolArgs {
             accounts,
         });
 
-        // TODO: calc expected amount after fees
-        eprintln!("Depositing {sol} SOL");
+        eprintln!(
+            "Depositing {sol} SOL for {quote}. Min pool tokens out: {}",
+            TokenAmt {
+                amt: min_out,
+                decimals: 9
+            }
+        );
         let ixs = match args.send_mode {
             TxSendMode::DumpMsg => ixs,
-            _ => with_auto_cb_ixs(&rpc, &payer.pubkey(), ixs, &[], args.fee_limit_cb).await,
+            _ => with_auto_cb_ixs(&rpc, &payer.pubkey(), ixs, &luts, args.fee_limit_cb).await,
         };
         let mut signers = [payer.as_ref(), from];
-        handle_tx_full(&rpc, args.send_mode, &ixs, &[], &mut signers).await;
+        handle_tx_full(
+            &rpc,
+            args.send_mode,
+            nonce.as_ref(),
+            &ixs,
+            &luts,
+            &mut signers,
+        )
+        .await;
     }
 }
@@ -1,7 +1,7 @@
 use borsh::BorshDeserialize;
 use clap::Args;
 use sanctum_associated_token_lib::FindAtaAddressArgs;
-use sanctum_solana_cli_utils::{PubkeySrc, TxSendMode};
+use sanctum_solana_cli_utils::{PubkeySrc, TokenAmt, TxSendMode};
 use sanctum_spl_stake_pool_lib::account_resolvers::DepositStakeWithSlippage;
 use solana_readonly_account::keyed::Keyed;
 use solana_sdk::{
@@ -13,7 +13,8 @@ use spl_associated_token_account_interface::CreateIdempotentKeys;
 use spl_stake_pool_interface::{StakePool, ValidatorList, ValidatorStakeInfo};
 
 use crate::{
-    handle_tx_full, ps, update_pool, with_auto_cb_ixs, Subcmd, UpdateCtrl, UpdatePoolArgs,
+    handle_tx_full, ps, update_pool, with_auto_cb_ixs, PoolQuoter, SlippageArgs, Subcmd,
+    UpdateCtrl, UpdatePoolArgs,
 };
 
 #[derive(Args, Debug)]
@@ -40,23 +41,30 @@ pub struct DepositStakeArgs {
 
     #[arg(help = "Stake account to deposit. Either its pubkey or the stake account's keypair.")]
     pub stake_account: String,
+
+    #[command(flatten)]
+    pub slippage: SlippageArgs,
 }
 
 impl DepositStakeArgs {
     pub async fn run(args: crate::Args) {
+        // args methods borrow all of args so they must be called before subcmd is moved out
+        let rpc = args.config.nonblocking_rpc_client();
+        let payer = args.config.signer();
+        let nonce = args.tx_nonce();
+        let luts = args.luts(&rpc).await;
+
         let Self {
             mint_to,
             pool,
             stake_account,
             authority,
+            slippage,
         } = match args.subcmd {
             Subcmd::DepositStake(a) => a,
             _ => unreachable!(),
         };
 
-        let rpc = args.config.nonblocking_rpc_client();
-        let payer = args.config.signer();
-
         ps!(authority, @fb payer.as_ref(), @sm args.send_mode);
 
         let [pool, stake_account] =
@@ -157,6 +165,35 @@ impl DepositStakeArgs {
             .find(|v| v.vote_account_address == voter)
             .expect("Validator not part of stake pool");
 
+        update_pool(UpdatePoolArgs {
+            rpc: &rpc,
+            send_mode: args.send_mode,
+            nonce: nonce.as_ref(),
+            luts: &luts,
+            payer: payer.as_ref(),
+            program_id,
+            current_epoch,
+            stake_pool: Keyed {
+                pubkey: pool,
+                account: &fetched_pool,
+            },
+            validator_list_entries: &validators,
+            fee_limit_cb: args.fee_limit_cb,
+            ctrl: UpdateCtrl::IfNeeded,
+            no_merge: false,
+            resume: false,
+        })
+        .await;
+
+        // refetch to compute expected amount against the freshly updated pool
+        let fetched_pool = rpc.get_account(&pool).await.unwrap();
+        let decoded_pool =
+            <StakePool as borsh::BorshDeserialize>::deserialize(&mut fetched_pool.data.as_ref())
+                .unwrap();
+        let quote = PoolQuoter::new(&decoded_pool, current_epoch)
+            .deposit_stake(delegation.stake, fetched_stake_account.lamports);
+        let min_out = slippage.min_out(quote.pool_tokens_out);
+
         let deposit_stake_accounts = DepositStakeWithSlippage {
             pool: Keyed {
                 pubkey: pool,
@@ -176,35 +213,31 @@ impl DepositStakeArgs {
                     &program_id,
                     *vote_account_address,
                     *validator_seed_suffix,
-                    0,
-                ) // TODO: min_token_outs = 0 right now, need to handle slippage
+                    min_out,
+                )
                 .unwrap(),
         );
 
-        update_pool(UpdatePoolArgs {
-            rpc: &rpc,
-            send_mode: args.send_mode,
-            payer: payer.as_ref(),
-            program_id,
-            current_epoch,
-            stake_pool: Keyed {
-                pubkey: pool,
-                account: &fetched_pool,
-            },
-            validator_list_entries: &validators,
-            fee_limit_cb: args.fee_limit_cb,
-            ctrl: UpdateCtrl::IfNeeded,
-            no_merge: false,
-        })
-        .await;
-
-        // TODO: calc expected amount after fees
-        eprintln!("Depositing stake account {stake_account}");
+        eprintln!(
+            "Depositing stake account {stake_account} for {quote}. Min pool tokens out: {}",
+            TokenAmt {
+                amt: min_out,
+                decimals: 9
+            }
+        );
         let ixs = match args.send_mode {
             TxSendMode::DumpMsg => ixs,
-            _ => with_auto_cb_ixs(&rpc, &payer.pubkey(), ixs, &[], args.fee_limit_cb).await,
+            _ => with_auto_cb_ixs(&rpc, &payer.pubkey(), ixs, &luts, args.fee_limit_cb).await,
         };
         let mut signers = [payer.as_ref(), authority];
-        handle_tx_full(&rpc, args.send_mode, &ixs, &[], &mut signers).await;
+        handle_tx_full(
+            &rpc,
+            args.send_mode,
+            nonce.as_ref(),
+            &ixs,
+            &luts,
+            &mut signers,
+        )
+        .await;
     }
 }
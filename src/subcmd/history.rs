@@ -0,0 +1,160 @@
+use std::path::PathBuf;
+
+use clap::Args;
+use sanctum_solana_cli_utils::PubkeySrc;
+use serde::Serialize;
+
+use crate::{pool_config::ValidatorConfigRaw, sol_per_pool_token, PoolSnapshot, SnapshotStore};
+
+use super::Subcmd;
+
+#[derive(Args, Debug)]
+#[command(
+    long_about = "Output a per-epoch exchange rate, TVL and per-validator stake time series of a pool
+from the snapshots recorded by `snapshot`. Epochs without snapshots are omitted."
+)]
+pub struct HistoryArgs {
+    #[arg(
+        long,
+        help = "Directory of the snapshot store. Defaults to ~/.config/splsp/snapshots"
+    )]
+    pub store_dir: Option<PathBuf>,
+
+    #[arg(long, help = "Only output epochs >= this epoch")]
+    pub from_epoch: Option<u64>,
+
+    #[arg(long, help = "Only output epochs <= this epoch")]
+    pub to_epoch: Option<u64>,
+
+    #[arg(
+        long,
+        help = "Only output the stake history of these validators' vote accounts. Can be repeated."
+    )]
+    pub vote: Vec<String>,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Do not output per-validator stake history"
+    )]
+    pub no_validators: bool,
+
+    #[arg(
+        help = "Address of the stake pool. Can either be a base58-encoded pubkey or keypair file"
+    )]
+    pub pool: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct EpochHistory {
+    pub epoch: u64,
+    pub slot: u64,
+    pub last_update_epoch: Option<u64>,
+
+    /// SOL per pool token
+    pub exchange_rate: Option<f64>,
+    pub total_lamports: Option<u64>,
+    pub pool_token_supply: Option<u64>,
+    pub validators: Option<usize>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ValidatorStakeHistory {
+    pub epoch: u64,
+    pub vote: String,
+    pub active_stake_lamports: Option<u64>,
+    pub transient_stake_lamports: Option<u64>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct PoolHistory {
+    pub pool: String,
+    pub epochs: Vec<EpochHistory>,
+    pub validator_stakes: Vec<ValidatorStakeHistory>, // put this last so it gets outputted last in toml Serialize
+}
+
+impl HistoryArgs {
+    pub async fn run(args: crate::Args) {
+        let Self {
+            store_dir,
+            from_epoch,
+            to_epoch,
+            vote: votes,
+            no_validators,
+            pool,
+        } = match args.subcmd {
+            Subcmd::History(a) => a,
+            _ => unreachable!(),
+        };
+
+        let (from_epoch, to_epoch) = (from_epoch.unwrap_or(0), to_epoch.unwrap_or(u64::MAX));
+        if from_epoch > to_epoch {
+            panic!("--from-epoch {from_epoch} is after --to-epoch {to_epoch}");
+        }
+
+        let pool = PubkeySrc::parse(&pool).unwrap().pubkey();
+        let store = SnapshotStore::for_pool(store_dir.as_deref(), &pool);
+        let snapshots = store.read_by_epoch().unwrap_or_else(|e| {
+            panic!(
+                "Failed to read snapshot store {}: {e}",
+                store.path.display()
+            )
+        });
+
+        let mut history = PoolHistory {
+            pool: pool.to_string(),
+            epochs: vec![],
+            validator_stakes: vec![],
+        };
+        for (
+            epoch,
+            PoolSnapshot {
+                slot,
+                pool: snapshot,
+                ..
+            },
+        ) in snapshots.range(from_epoch..=to_epoch)
+        {
+            history.epochs.push(EpochHistory {
+                epoch: *epoch,
+                slot: *slot,
+                last_update_epoch: snapshot.last_update_epoch,
+                exchange_rate: snapshot
+                    .total_lamports
+                    .zip(snapshot.pool_token_supply)
+                    .and_then(|(lamports, supply)| sol_per_pool_token(lamports, supply)),
+                total_lamports: snapshot.total_lamports,
+                pool_token_supply: snapshot.pool_token_supply,
+                validators: snapshot.validators.as_ref().map(Vec::len),
+            });
+            if no_validators {
+                continue;
+            }
+            history.validator_stakes.extend(
+                snapshot
+                    .validators
+                    .iter()
+                    .flatten()
+                    .filter(|v| votes.is_empty() || votes.contains(&v.vote))
+                    .map(
+                        |ValidatorConfigRaw {
+                             vote,
+                             active_stake_lamports,
+                             transient_stake_lamports,
+                             ..
+                         }| ValidatorStakeHistory {
+                            epoch: *epoch,
+                            vote: vote.clone(),
+                            active_stake_lamports: *active_stake_lamports,
+                            transient_stake_lamports: *transient_stake_lamports,
+                        },
+                    ),
+            );
+        }
+
+        args.output.print("history", &history);
+    }
+}
@@ -1,9 +1,11 @@
 use clap::Args;
 use sanctum_solana_cli_utils::PubkeySrc;
+use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_readonly_account::keyed::Keyed;
+use solana_sdk::pubkey::Pubkey;
 use spl_stake_pool_interface::{StakePool, ValidatorList};
 
-use crate::pool_config::{ConfigRaw, ConfigTomlFile};
+use crate::pool_config::ConfigRaw;
 
 use super::Subcmd;
 
@@ -34,38 +36,46 @@ impl ListArgs {
         let pool = PubkeySrc::parse(&pool).unwrap().pubkey();
         let rpc = args.config.nonblocking_rpc_client();
 
-        let mut display = ConfigRaw::default();
-        display.set_pool_pk(pool);
+        let display = fetch_pool_info(&rpc, pool, verbose).await;
 
-        let fetched_pool = rpc.get_account(&pool).await.unwrap();
-        let program_id = fetched_pool.owner;
-        display.set_program(program_id);
+        args.output.print("pool", &display);
+    }
+}
 
-        let decoded_pool =
-            <StakePool as borsh::BorshDeserialize>::deserialize(&mut fetched_pool.data.as_ref())
-                .unwrap();
-        let validator_list_pk = decoded_pool.validator_list;
-        display.set_pool(&program_id, pool, &decoded_pool);
+/// Fetches the pool's info for display.
+/// `verbose` also fetches validator list and reserve stake info.
+pub(crate) async fn fetch_pool_info(rpc: &RpcClient, pool: Pubkey, verbose: bool) -> ConfigRaw {
+    let mut display = ConfigRaw::default();
+    display.set_pool_pk(pool);
 
-        if verbose {
-            let mut fetched = rpc
-                .get_multiple_accounts(&[validator_list_pk, decoded_pool.reserve_stake])
-                .await
-                .unwrap();
-            let fetched_reserve = fetched.pop().unwrap().unwrap();
-            let fetched_validator_list = fetched.pop().unwrap().unwrap();
+    let fetched_pool = rpc.get_account(&pool).await.unwrap();
+    let program_id = fetched_pool.owner;
+    display.set_program(program_id);
 
-            let decoded_validator_list = <ValidatorList as borsh::BorshDeserialize>::deserialize(
-                &mut fetched_validator_list.data.as_slice(),
-            )
+    let decoded_pool =
+        <StakePool as borsh::BorshDeserialize>::deserialize(&mut fetched_pool.data.as_ref())
             .unwrap();
-            display.set_reserve(&Keyed {
-                pubkey: decoded_pool.reserve_stake,
-                account: &fetched_reserve,
-            });
-            display.set_validator_list(&program_id, &pool, &decoded_validator_list);
-        }
+    let validator_list_pk = decoded_pool.validator_list;
+    display.set_pool(&program_id, pool, &decoded_pool);
 
-        println!("{}", ConfigTomlFile { pool: &display })
+    if verbose {
+        let mut fetched = rpc
+            .get_multiple_accounts(&[validator_list_pk, decoded_pool.reserve_stake])
+            .await
+            .unwrap();
+        let fetched_reserve = fetched.pop().unwrap().unwrap();
+        let fetched_validator_list = fetched.pop().unwrap().unwrap();
+
+        let decoded_validator_list = <ValidatorList as borsh::BorshDeserialize>::deserialize(
+            &mut fetched_validator_list.data.as_slice(),
+        )
+        .unwrap();
+        display.set_reserve(&Keyed {
+            pubkey: decoded_pool.reserve_stake,
+            account: &fetched_reserve,
+        });
+        display.set_validator_list(&program_id, &pool, &decoded_validator_list);
     }
+
+    display
 }
@@ -0,0 +1,390 @@
+use std::num::NonZeroU32;
+
+use borsh::BorshDeserialize;
+use clap::{Args, ValueEnum};
+use sanctum_solana_cli_utils::{PubkeySrc, TokenAmt};
+use sanctum_spl_stake_pool_lib::{
+    FindTransientStakeAccount, FindTransientStakeAccountArgs, FindValidatorStakeAccount,
+    FindValidatorStakeAccountArgs,
+};
+use serde::Serialize;
+use solana_sdk::{pubkey::Pubkey, stake::state::StakeStateV2};
+use spl_stake_pool_interface::{StakePool, StakeStatus, ValidatorList, ValidatorStakeInfo};
+
+use crate::{next_epoch_stake_and_transient_status, TransientStakeAccStatus, ValidatorStakeAccs};
+
+use super::{fetch_validator_stake_accs, Subcmd};
+
+#[derive(Args, Debug)]
+#[command(
+    long_about = "List the validators of a stake pool with derived metrics.
+
+Use with `--output table` for a human-readable view."
+)]
+pub struct ListValidatorsArgs {
+    #[arg(
+        long,
+        default_value_t = ValidatorSortBy::Index,
+        help = "Column to sort validators by. Stake amounts are sorted in descending order."
+    )]
+    pub sort_by: ValidatorSortBy,
+
+    #[arg(long, help = "Only list validators with this status")]
+    pub status: Option<ValidatorStatusFilter>,
+
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Only list validators that have not been updated for the current epoch"
+    )]
+    pub stale_only: bool,
+
+    #[arg(long, help = "Maximum number of validators to list, after sorting")]
+    pub limit: Option<usize>,
+
+    #[arg(
+        help = "Address of the stake pool. Can either be a base58-encoded pubkey or keypair file"
+    )]
+    pub pool: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ValidatorSortBy {
+    /// Position in the validator list
+    Index,
+    Vote,
+
+    /// Active + transient stake
+    Stake,
+    Active,
+    Transient,
+}
+
+impl std::fmt::Display for ValidatorSortBy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Index => "index",
+            Self::Vote => "vote",
+            Self::Stake => "stake",
+            Self::Active => "active",
+            Self::Transient => "transient",
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ValidatorStatusFilter {
+    Active,
+
+    /// Any of the deactivating statuses
+    Deactivating,
+    ReadyForRemoval,
+}
+
+impl ValidatorStatusFilter {
+    pub fn matches(&self, status: &StakeStatus) -> bool {
+        match self {
+            Self::Active => *status == StakeStatus::Active,
+            Self::Deactivating => matches!(
+                status,
+                StakeStatus::DeactivatingTransient
+                    | StakeStatus::DeactivatingValidator
+                    | StakeStatus::DeactivatingAll
+            ),
+            Self::ReadyForRemoval => *status == StakeStatus::ReadyForRemoval,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ValidatorRow {
+    pub index: usize,
+    pub vote: String,
+    pub status: &'static str,
+    pub active_sol: String,
+    pub transient_sol: String,
+
+    /// Share of the pool's total lamports in %
+    pub share: String,
+
+    /// Whether the transient stake account is activating or deactivating,
+    /// `None` if there is no transient stake account
+    pub transient: Option<&'static str>,
+    pub stale: bool,
+    pub validator_stake_account: String,
+    pub transient_stake_account: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ValidatorListing {
+    pub pool: String,
+    pub current_epoch: u64,
+    pub total_sol: String,
+    pub validators_total: usize,
+    pub validators: Vec<ValidatorRow>, // put this last so it gets outputted last in toml Serialize
+}
+
+impl ListValidatorsArgs {
+    pub async fn run(args: crate::Args) {
+        let Self {
+            sort_by,
+            status,
+            stale_only,
+            limit,
+            pool,
+        } = match &args.subcmd {
+            Subcmd::ListValidators(a) => a,
+            _ => unreachable!(),
+        };
+
+        let pool = PubkeySrc::parse(pool).unwrap().pubkey();
+        let rpc = args.config.nonblocking_rpc_client();
+        let current_epoch = rpc.get_epoch_info().await.unwrap().epoch;
+
+        let stake_pool_acc = rpc.get_account(&pool).await.unwrap();
+        let program_id = stake_pool_acc.owner;
+        let StakePool {
+            validator_list,
+            total_lamports,
+            ..
+        } = StakePool::deserialize(&mut stake_pool_acc.data.as_slice()).unwrap();
+        let validator_list_acc = rpc.get_account(&validator_list).await.unwrap();
+        let ValidatorList { validators, .. } =
+            ValidatorList::deserialize(&mut validator_list_acc.data.as_slice()).unwrap();
+
+        let selected = select_validators(
+            &validators,
+            *sort_by,
+            *status,
+            stale_only.then_some(current_epoch),
+            *limit,
+        );
+        // fetch only the selected validators' stake accounts instead of the whole list
+        let selected_vsis: Vec<ValidatorStakeInfo> =
+            selected.iter().map(|(_, vsi)| (*vsi).clone()).collect();
+        let stake_accs = fetch_validator_stake_accs(&rpc, program_id, pool, &selected_vsis).await;
+
+        let validator_rows = selected
+            .iter()
+            .zip(stake_accs.iter())
+            .map(
+                |((index, vsi), ValidatorStakeAccs { vsa, tsa, .. })| ValidatorRow {
+                    index: *index,
+                    vote: vsi.vote_account_address.to_string(),
+                    status: stake_status_str(&vsi.status),
+                    active_sol: sol_display(vsi.active_stake_lamports).to_string(),
+                    transient_sol: sol_display(vsi.transient_stake_lamports).to_string(),
+                    share: share_pct(
+                        vsi.active_stake_lamports
+                            .saturating_add(vsi.transient_stake_lamports),
+                        total_lamports,
+                    ),
+                    transient: transient_status(
+                        vsa.as_ref().map(|(_, s)| s),
+                        tsa.as_ref().map(|(_, s)| s),
+                        current_epoch,
+                    ),
+                    stale: vsi.last_update_epoch < current_epoch,
+                    validator_stake_account: FindValidatorStakeAccount::new(
+                        FindValidatorStakeAccountArgs {
+                            pool,
+                            vote: vsi.vote_account_address,
+                            seed: NonZeroU32::new(vsi.validator_seed_suffix),
+                        },
+                    )
+                    .run_for_prog(&program_id)
+                    .0
+                    .to_string(),
+                    transient_stake_account: FindTransientStakeAccount::new(
+                        FindTransientStakeAccountArgs {
+                            pool,
+                            vote: vsi.vote_account_address,
+                            seed: vsi.transient_seed_suffix,
+                        },
+                    )
+                    .run_for_prog(&program_id)
+                    .0
+                    .to_string(),
+                },
+            )
+            .collect();
+
+        let listing = ValidatorListing {
+            pool: pool.to_string(),
+            current_epoch,
+            total_sol: sol_display(total_lamports).to_string(),
+            validators_total: validators.len(),
+            validators: validator_rows,
+        };
+        args.output.print("validators", &listing);
+    }
+}
+
+/// Returns `(index in validator list, validator_stake_info)` of the validators to list, in display order.
+///
+/// `stale_as_of` filters for validators whose last update epoch is before it.
+pub fn select_validators(
+    validators: &[ValidatorStakeInfo],
+    sort_by: ValidatorSortBy,
+    status: Option<ValidatorStatusFilter>,
+    stale_as_of: Option<u64>,
+    limit: Option<usize>,
+) -> Vec<(usize, &ValidatorStakeInfo)> {
+    let mut selected: Vec<(usize, &ValidatorStakeInfo)> = validators
+        .iter()
+        .enumerate()
+        .filter(|(_, vsi)| match status {
+            Some(s) => s.matches(&vsi.status),
+            None => true,
+        })
+        .filter(|(_, vsi)| match stale_as_of {
+            Some(epoch) => vsi.last_update_epoch < epoch,
+            None => true,
+        })
+        .collect();
+    match sort_by {
+        ValidatorSortBy::Index => (),
+        ValidatorSortBy::Vote => selected.sort_by_key(|(_, vsi)| vsi.vote_account_address),
+        ValidatorSortBy::Stake => selected.sort_by_key(|(_, vsi)| {
+            std::cmp::Reverse(
+                vsi.active_stake_lamports
+                    .saturating_add(vsi.transient_stake_lamports),
+            )
+        }),
+        ValidatorSortBy::Active => {
+            selected.sort_by_key(|(_, vsi)| std::cmp::Reverse(vsi.active_stake_lamports))
+        }
+        ValidatorSortBy::Transient => {
+            selected.sort_by_key(|(_, vsi)| std::cmp::Reverse(vsi.transient_stake_lamports))
+        }
+    }
+    if let Some(limit) = limit {
+        selected.truncate(limit);
+    }
+    selected
+}
+
+/// `None` if there is no delegated transient stake account
+fn transient_status(
+    vsa: Option<&StakeStateV2>,
+    tsa: Option<&StakeStateV2>,
+    current_epoch: u64,
+) -> Option<&'static str> {
+    // next_epoch_stake_and_transient_status() panics on missing or undelegated stake accounts
+    let vsa = vsa?;
+    vsa.delegation()?;
+    let tsa = tsa.filter(|tsa| tsa.delegation().is_some()).cloned();
+    match next_epoch_stake_and_transient_status(vsa, &tsa, current_epoch).1 {
+        TransientStakeAccStatus::Activating => Some("activating"),
+        TransientStakeAccStatus::Deactivating => Some("deactivating"),
+        TransientStakeAccStatus::None => None,
+    }
+}
+
+fn stake_status_str(status: &StakeStatus) -> &'static str {
+    match status {
+        StakeStatus::Active => "active",
+        StakeStatus::DeactivatingTransient => "deactivating-transient",
+        StakeStatus::ReadyForRemoval => "ready-for-removal",
+        StakeStatus::DeactivatingValidator => "deactivating-validator",
+        StakeStatus::DeactivatingAll => "deactivating-all",
+    }
+}
+
+fn sol_display(amt: u64) -> TokenAmt {
+    TokenAmt { amt, decimals: 9 }
+}
+
+fn share_pct(lamports: u64, total_lamports: u64) -> String {
+    if total_lamports == 0 {
+        return "-".to_owned();
+    }
+    // precision loss is fine for display
+    format!("{:.2}", lamports as f64 * 100.0 / total_lamports as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vsi(
+        active: u64,
+        transient: u64,
+        last_update_epoch: u64,
+        status: StakeStatus,
+    ) -> ValidatorStakeInfo {
+        ValidatorStakeInfo {
+            active_stake_lamports: active,
+            transient_stake_lamports: transient,
+            last_update_epoch,
+            status,
+            vote_account_address: Pubkey::new_unique(),
+            // dont care
+            transient_seed_suffix: 0,
+            unused: 0,
+            validator_seed_suffix: 0,
+        }
+    }
+
+    #[test]
+    fn select_validators_filter_sort_limit() {
+        let validators = [
+            vsi(1, 0, 5, StakeStatus::Active),
+            vsi(3, 0, 4, StakeStatus::Active),
+            vsi(2, 5, 4, StakeStatus::Active),
+            vsi(10, 0, 4, StakeStatus::DeactivatingValidator),
+        ];
+        let indices = |selected: Vec<(usize, &ValidatorStakeInfo)>| -> Vec<usize> {
+            selected.into_iter().map(|(i, _)| i).collect()
+        };
+
+        assert_eq!(
+            indices(select_validators(
+                &validators,
+                ValidatorSortBy::Index,
+                None,
+                None,
+                None
+            )),
+            [0, 1, 2, 3]
+        );
+        assert_eq!(
+            indices(select_validators(
+                &validators,
+                ValidatorSortBy::Stake,
+                Some(ValidatorStatusFilter::Active),
+                None,
+                None
+            )),
+            [2, 1, 0]
+        );
+        assert_eq!(
+            indices(select_validators(
+                &validators,
+                ValidatorSortBy::Active,
+                None,
+                Some(5),
+                Some(2)
+            )),
+            [3, 1]
+        );
+        assert_eq!(
+            indices(select_validators(
+                &validators,
+                ValidatorSortBy::Index,
+                Some(ValidatorStatusFilter::Deactivating),
+                None,
+                None
+            )),
+            [3]
+        );
+    }
+
+    #[test]
+    fn share_pct_display() {
+        assert_eq!(share_pct(1, 3), "33.33");
+        assert_eq!(share_pct(0, 0), "-");
+    }
+}
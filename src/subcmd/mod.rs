@@ -1,61 +1,126 @@
 use clap::Subcommand;
 
+mod apply;
+mod crank;
+mod create_lut;
 mod create_pool;
+mod decode;
 mod decrease_validator_stake;
 mod deposit_sol;
 mod deposit_stake;
+mod history;
 mod increase_validator_stake;
 mod list;
+mod list_validators;
+mod quote;
+mod scan;
 mod set_staker;
+mod sign;
+mod snapshot;
+mod stats;
+mod submit;
+mod sync;
 mod sync_delegation;
+mod sync_lut;
 mod sync_pool;
+mod sync_token_metadata;
+mod sync_utils;
 mod sync_validator_list;
 mod update;
+mod withdraw_sol;
 mod withdraw_stake;
 
+pub use apply::*;
+pub use crank::*;
+pub use create_lut::*;
 pub use create_pool::*;
+pub use decode::*;
 pub use decrease_validator_stake::*;
 pub use deposit_sol::*;
 pub use deposit_stake::*;
+pub use history::*;
 pub use increase_validator_stake::*;
 pub use list::*;
+pub use list_validators::*;
+pub use quote::*;
+pub use scan::*;
 pub use set_staker::*;
+pub use sign::*;
+pub use snapshot::*;
+pub use stats::*;
+pub use submit::*;
+pub use sync::*;
 pub use sync_delegation::*;
+pub use sync_lut::*;
 pub use sync_pool::*;
+pub use sync_token_metadata::*;
 pub use sync_validator_list::*;
 pub use update::*;
+pub use withdraw_sol::*;
 pub use withdraw_stake::*;
 
 #[derive(Debug, Subcommand)]
 pub enum Subcmd {
+    Apply(ApplyArgs),
+    Crank(CrankArgs),
+    CreateLut(CreateLutArgs),
     CreatePool(CreatePoolArgs),
+    Decode(DecodeArgs),
     DecreaseValidatorStake(DecreaseValidatorStakeArgs),
     DepositSol(DepositSolArgs),
     DepositStake(DepositStakeArgs),
+    History(HistoryArgs),
     IncreaseValidatorStake(IncreaseValidatorStakeArgs),
     List(ListArgs),
+    ListValidators(ListValidatorsArgs),
+    Quote(QuoteArgs),
+    Scan(ScanArgs),
     SetStaker(SetStakerArgs),
+    Sign(SignArgs),
+    Snapshot(SnapshotArgs),
+    Stats(StatsArgs),
+    Submit(SubmitArgs),
+    Sync(SyncArgs),
     SyncDelegation(SyncDelegationArgs),
+    SyncLut(SyncLutArgs),
     SyncPool(SyncPoolArgs),
+    SyncTokenMetadata(SyncTokenMetadataArgs),
     SyncValidatorList(SyncValidatorListArgs),
     Update(UpdateArgs),
+    WithdrawSol(WithdrawSolArgs),
     WithdrawStake(WithdrawStakeArgs),
 }
 
 impl Subcmd {
     pub async fn run(args: crate::Args) {
         match args.subcmd {
+            Self::Apply(_) => ApplyArgs::run(args).await,
+            Self::Crank(_) => CrankArgs::run(args).await,
+            Self::CreateLut(_) => CreateLutArgs::run(args).await,
             Self::CreatePool(_) => CreatePoolArgs::run(args).await,
+            Self::Decode(_) => DecodeArgs::run(args).await,
             Self::DecreaseValidatorStake(_) => DecreaseValidatorStakeArgs::run(args).await,
             Self::DepositSol(_) => DepositSolArgs::run(args).await,
             Self::DepositStake(_) => DepositStakeArgs::run(args).await,
+            Self::History(_) => HistoryArgs::run(args).await,
             Self::IncreaseValidatorStake(_) => IncreaseValidatorStakeArgs::run(args).await,
             Self::List(_) => ListArgs::run(args).await,
+            Self::ListValidators(_) => ListValidatorsArgs::run(args).await,
+            Self::Quote(_) => QuoteArgs::run(args).await,
+            Self::Scan(_) => ScanArgs::run(args).await,
             Self::SetStaker(_) => SetStakerArgs::run(args).await,
+            Self::Sign(_) => SignArgs::run(args).await,
+            Self::Snapshot(_) => SnapshotArgs::run(args).await,
+            Self::Stats(_) => StatsArgs::run(args).await,
+            Self::Submit(_) => SubmitArgs::run(args).await,
+            Self::Sync(_) => SyncArgs::run(args).await,
             Self::SyncDelegation(_) => SyncDelegationArgs::run(args).await,
+            Self::SyncLut(_) => SyncLutArgs::run(args).await,
             Self::SyncPool(_) => SyncPoolArgs::run(args).await,
+            Self::SyncTokenMetadata(_) => SyncTokenMetadataArgs::run(args).await,
             Self::SyncValidatorList(_) => SyncValidatorListArgs::run(args).await,
             Self::Update(_) => UpdateArgs::run(args).await,
+            Self::WithdrawSol(_) => WithdrawSolArgs::run(args).await,
             Self::WithdrawStake(_) => WithdrawStakeArgs::run(args).await,
         }
     }
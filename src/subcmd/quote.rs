@@ -0,0 +1,113 @@
+use borsh::BorshDeserialize;
+use clap::{
+    builder::{StringValueParser, TypedValueParser},
+    Args, ValueEnum,
+};
+use sanctum_solana_cli_utils::{PubkeySrc, TokenAmt, TokenAmtParser};
+use solana_sdk::{clock::Clock, rent::Rent, stake::state::StakeStateV2, sysvar};
+use spl_stake_pool_interface::StakePool;
+
+use crate::PoolQuoter;
+
+use super::Subcmd;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum QuoteOp {
+    /// Amount is SOL to deposit
+    DepositSol,
+
+    /// Amount is the balance of the active stake account to deposit, including its rent-exempt reserve
+    DepositStake,
+
+    /// Amount is pool tokens to redeem
+    WithdrawSol,
+
+    /// Amount is pool tokens to redeem
+    WithdrawStake,
+}
+
+#[derive(Args, Debug)]
+#[command(
+    long_about = "Quote the expected output and fees of a deposit or withdrawal without sending anything"
+)]
+pub struct QuoteArgs {
+    #[arg(
+        long,
+        short,
+        help = "Token account to burn pool tokens from for withdrawals. Withdrawal fees are waived for the pool's manager fee account."
+    )]
+    pub tokens_burn_from: Option<String>,
+
+    #[arg(help = "Operation to quote", value_enum)]
+    pub op: QuoteOp,
+
+    #[arg(
+        help = "Address of the stake pool. Can either be a base58-encoded pubkey or keypair file"
+    )]
+    pub pool: String,
+
+    #[arg(
+        help = "Amount in",
+        value_parser = StringValueParser::new().map(|s| TokenAmtParser::new(9).parse(&s).unwrap()),
+    )]
+    pub amt: TokenAmt,
+}
+
+impl QuoteArgs {
+    pub async fn run(args: crate::Args) {
+        let Self {
+            tokens_burn_from,
+            op,
+            pool,
+            amt,
+        } = match args.subcmd {
+            Subcmd::Quote(a) => a,
+            _ => unreachable!(),
+        };
+
+        let pool = PubkeySrc::parse(&pool).unwrap().pubkey();
+        let burn_from = tokens_burn_from.map(|s| PubkeySrc::parse(&s).unwrap().pubkey());
+        let rpc = args.config.nonblocking_rpc_client();
+
+        let mut fetched = rpc
+            .get_multiple_accounts(&[pool, sysvar::clock::ID, sysvar::rent::ID])
+            .await
+            .unwrap();
+        let rent = fetched.pop().unwrap().unwrap();
+        let clock = fetched.pop().unwrap().unwrap();
+        let stake_pool_acc = fetched.pop().unwrap().unwrap();
+
+        let rent: Rent = bincode::deserialize(&rent.data).unwrap();
+        let Clock {
+            epoch: current_epoch,
+            ..
+        } = bincode::deserialize(&clock.data).unwrap();
+        let stake_pool = StakePool::deserialize(&mut stake_pool_acc.data.as_slice()).unwrap();
+
+        if stake_pool.last_update_epoch < current_epoch {
+            eprintln!(
+                "Pool not yet updated for epoch {current_epoch}, quote does not include this epoch's staking rewards"
+            );
+        }
+
+        let quoter = PoolQuoter::new(&stake_pool, current_epoch);
+        // no burn_from provided: dont assume fee exemption
+        let burn_from = burn_from.unwrap_or_default();
+        match op {
+            QuoteOp::DepositSol => args.output.print("quote", &quoter.deposit_sol(amt.amt)),
+            QuoteOp::DepositStake => {
+                let rent_exempt_reserve = rent.minimum_balance(StakeStateV2::size_of());
+                args.output.print(
+                    "quote",
+                    &quoter.deposit_stake(amt.amt.saturating_sub(rent_exempt_reserve), amt.amt),
+                )
+            }
+            QuoteOp::WithdrawSol => args
+                .output
+                .print("quote", &quoter.withdraw_sol(amt.amt, &burn_from)),
+            QuoteOp::WithdrawStake => args
+                .output
+                .print("quote", &quoter.withdraw_stake(amt.amt, &burn_from)),
+        }
+    }
+}
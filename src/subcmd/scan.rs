@@ -0,0 +1,208 @@
+use std::{collections::HashMap, time::Duration};
+
+use borsh::BorshDeserialize;
+use clap::{
+    builder::{StringValueParser, TypedValueParser},
+    Args,
+};
+use serde::Serialize;
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{
+    nonblocking::rpc_client::RpcClient,
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_filter::{Memcmp, RpcFilterType},
+};
+use solana_sdk::{account::Account, pubkey::Pubkey};
+use spl_stake_pool_interface::{AccountType, StakePool, ValidatorList};
+
+use crate::SplStakePoolProgram;
+
+use super::{PoolCranker, Subcmd};
+
+#[derive(Args, Debug)]
+#[command(
+    long_about = "Scan all pools of a stake pool program for pools that have not been updated for the current epoch.
+
+Outputs a report of stale pools to stdout.
+A pool is stale if its last update epoch or that of any of its validator list entries is behind the current epoch."
+)]
+pub struct ScanArgs {
+    #[arg(
+        long,
+        default_value_t = false,
+        help = "Also run the update crank for every stale pool found"
+    )]
+    pub crank: bool,
+
+    #[arg(
+        long,
+        default_value_t = 5,
+        help = "Number of times to retry a pool's update crank on failure before giving up. Only used with --crank."
+    )]
+    pub max_retries: u32,
+
+    #[arg(
+        long,
+        default_value_t = 5,
+        help = "Number of seconds to wait before the first retry. Doubled on every subsequent retry. Only used with --crank."
+    )]
+    pub backoff: u64,
+
+    #[arg(
+        help = SplStakePoolProgram::HELP_STR,
+        value_parser = StringValueParser::new().try_map(|s| SplStakePoolProgram::parse(&s)),
+    )]
+    pub program: SplStakePoolProgram,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct StalePool {
+    pub pool: String,
+    pub last_update_epoch: u64,
+    pub validators: usize,
+    pub validators_not_updated: usize,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ScanReport {
+    pub program: SplStakePoolProgram,
+    pub current_epoch: u64,
+    pub pools_scanned: usize,
+    pub stale_pools: Vec<StalePool>, // put this last so it gets outputted last in toml Serialize
+}
+
+impl ScanArgs {
+    pub async fn run(args: crate::Args) {
+        let Self {
+            crank,
+            max_retries,
+            backoff,
+            program,
+        } = match &args.subcmd {
+            Subcmd::Scan(a) => a,
+            _ => unreachable!(),
+        };
+        let program_id = program.program_id();
+
+        let rpc = args.config.nonblocking_rpc_client();
+        let current_epoch = rpc.get_epoch_info().await.unwrap().epoch;
+
+        let pools = fetch_accounts_of_type(&rpc, &program_id, AccountType::StakePool).await;
+        let mut validator_lists: HashMap<Pubkey, Account> =
+            fetch_accounts_of_type(&rpc, &program_id, AccountType::ValidatorList)
+                .await
+                .into_iter()
+                .collect();
+        eprintln!("Found {} pools", pools.len());
+
+        let mut stale_pools: Vec<StalePool> = pools
+            .iter()
+            .filter_map(|(pool, acc)| {
+                let StakePool {
+                    last_update_epoch,
+                    validator_list,
+                    ..
+                } = match StakePool::deserialize(&mut acc.data.as_slice()) {
+                    Ok(sp) => sp,
+                    Err(e) => {
+                        eprintln!("Skipping pool {pool}: failed to deserialize: {e}");
+                        return None;
+                    }
+                };
+                let validators = match validator_lists
+                    .remove(&validator_list)
+                    .map(|acc| ValidatorList::deserialize(&mut acc.data.as_slice()))
+                {
+                    Some(Ok(ValidatorList { validators, .. })) => validators,
+                    Some(Err(e)) => {
+                        eprintln!("Skipping pool {pool}: failed to deserialize validator list {validator_list}: {e}");
+                        return None;
+                    }
+                    None => {
+                        eprintln!("Skipping pool {pool}: validator list {validator_list} not found");
+                        return None;
+                    }
+                };
+                let validators_not_updated = validators
+                    .iter()
+                    .filter(|vsi| vsi.last_update_epoch < current_epoch)
+                    .count();
+                if last_update_epoch >= current_epoch && validators_not_updated == 0 {
+                    return None;
+                }
+                Some(StalePool {
+                    pool: pool.to_string(),
+                    last_update_epoch,
+                    validators: validators.len(),
+                    validators_not_updated,
+                })
+            })
+            .collect();
+        stale_pools.sort_by(|a, b| a.pool.cmp(&b.pool));
+
+        let report = ScanReport {
+            program: *program,
+            current_epoch,
+            pools_scanned: pools.len(),
+            stale_pools,
+        };
+        args.output.print("scan", &report);
+
+        if !crank {
+            return;
+        }
+
+        let payer = args.config.signer();
+        let nonce = args.tx_nonce();
+        let luts = args.luts(&rpc).await;
+        let cranker = PoolCranker {
+            args: &args,
+            rpc: &rpc,
+            nonce: nonce.as_ref(),
+            luts: &luts,
+            payer: payer.as_ref(),
+            max_retries: *max_retries,
+            backoff: Duration::from_secs(*backoff),
+        };
+        let mut failed = Vec::new();
+        for StalePool { pool, .. } in report.stale_pools.iter() {
+            let summary = cranker.crank_pool(&pool.parse().unwrap()).await;
+            eprintln!(
+                "Pool {pool}: {:?} after {} attempt(s)",
+                summary.status, summary.attempts
+            );
+            if summary.error.is_some() {
+                failed.push(summary.pool);
+            }
+        }
+        if !failed.is_empty() {
+            panic!("Failed to crank pools: {}", failed.join(", "));
+        }
+    }
+}
+
+async fn fetch_accounts_of_type(
+    rpc: &RpcClient,
+    program_id: &Pubkey,
+    account_type: AccountType,
+) -> Vec<(Pubkey, Account)> {
+    rpc.get_program_accounts_with_config(
+        program_id,
+        RpcProgramAccountsConfig {
+            // account_type is the first byte of both StakePool and ValidatorList
+            filters: Some(vec![RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+                0,
+                vec![account_type as u8],
+            ))]),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap()
+}
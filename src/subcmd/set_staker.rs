@@ -2,10 +2,11 @@ use std::path::PathBuf;
 
 use borsh::BorshDeserialize;
 use clap::Args;
-use sanctum_solana_cli_utils::{PubkeySrc, TxSendMode};
+use sanctum_solana_cli_utils::PubkeySrc;
+use solana_sdk::signer::{null_signer::NullSigner, Signer};
 use spl_stake_pool_interface::{set_staker_ix_with_program_id, SetStakerKeys, StakePool};
 
-use crate::{handle_tx_full, ps, with_auto_cb_ixs, ConfigRaw, Subcmd};
+use crate::{handle_authority_tx, ps, ConfigRaw, Subcmd};
 
 #[derive(Args, Debug)]
 #[command(long_about = "(Staker only) set a new staker from a pool config file")]
@@ -16,7 +17,7 @@ pub struct SetStakerArgs {
 
 impl SetStakerArgs {
     pub async fn run(args: crate::Args) {
-        let Self { pool_config } = match args.subcmd {
+        let Self { pool_config } = match &args.subcmd {
             Subcmd::SetStaker(a) => a,
             _ => unreachable!(),
         };
@@ -30,10 +31,18 @@ impl SetStakerArgs {
 
         let rpc = args.config.nonblocking_rpc_client();
         let payer = args.config.signer();
+        let nonce = args.tx_nonce();
+        let luts = args.luts(&rpc).await;
 
         let pool = PubkeySrc::parse(pool.as_ref().unwrap()).unwrap().pubkey();
 
-        ps!(old_staker, @fb payer.as_ref(), @sm args.send_mode);
+        let mut squads = args.squads_proposer(&rpc).await;
+        let squads_vault = squads.as_ref().map(|s| NullSigner::new(&s.vault));
+        let authority_fb = squads_vault
+            .as_ref()
+            .map_or(payer.as_ref(), |v| v as &dyn Signer);
+
+        ps!(old_staker, @fb authority_fb, @sm args.authority_send_mode());
 
         let new_staker = staker.map_or_else(
             || payer.pubkey(),
@@ -68,15 +77,14 @@ impl SetStakerArgs {
             },
         )
         .unwrap()];
-        let ixs = match args.send_mode {
-            TxSendMode::DumpMsg => ixs,
-            _ => with_auto_cb_ixs(&rpc, &payer.pubkey(), ixs, &[], args.fee_limit_cb).await,
-        };
-        handle_tx_full(
+        handle_authority_tx(
             &rpc,
             args.send_mode,
-            &ixs,
-            &[],
+            nonce.as_ref(),
+            &luts,
+            args.fee_limit_cb,
+            squads.as_mut(),
+            ixs,
             &mut [payer.as_ref(), old_staker],
         )
         .await;
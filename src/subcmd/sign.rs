@@ -0,0 +1,64 @@
+use clap::Args;
+use sanctum_solana_cli_utils::parse_signer;
+use solana_sdk::signer::Signer;
+
+use crate::tx_utils::{decode_b64_tx, encode_b64_tx, missing_signers};
+
+use super::Subcmd;
+
+#[derive(Args, Debug)]
+#[command(
+    long_about = "Add signatures to a base64 encoded transaction output by dump-msg or a previous sign, and output the partially-signed transaction to stdout"
+)]
+pub struct SignArgs {
+    #[arg(help = "Base64 encoded transaction to sign")]
+    pub tx: String,
+
+    #[arg(
+        long,
+        short,
+        help = "Signer to sign the transaction with e.g. keypair file path or usb://ledger.
+Can be specified multiple times. Defaults to the config wallet if not provided."
+    )]
+    pub signer: Vec<String>,
+}
+
+impl SignArgs {
+    pub async fn run(args: crate::Args) {
+        let Self { tx, signer } = match args.subcmd {
+            Subcmd::Sign(a) => a,
+            _ => unreachable!(),
+        };
+
+        let signers: Vec<Box<dyn Signer>> = if signer.is_empty() {
+            vec![args.config.signer()]
+        } else {
+            signer.iter().map(|s| parse_signer(s).unwrap()).collect()
+        };
+
+        let mut tx = decode_b64_tx(&tx);
+        let msg_bytes = tx.message.serialize();
+        let num_required_signatures: usize = tx.message.header().num_required_signatures.into();
+        let required_signers = &tx.message.static_account_keys()[..num_required_signatures];
+
+        for signer in signers.iter() {
+            let pk = signer.pubkey();
+            let i = required_signers
+                .iter()
+                .position(|s| *s == pk)
+                .unwrap_or_else(|| panic!("{pk} is not a required signer of the transaction"));
+            tx.signatures[i] = signer.try_sign_message(&msg_bytes).unwrap();
+            eprintln!("Signed by {pk}");
+        }
+
+        let missing = missing_signers(&tx);
+        if !missing.is_empty() {
+            eprintln!("Still missing signatures from:");
+            for pk in missing {
+                eprintln!("{pk}");
+            }
+        }
+
+        println!("{}", encode_b64_tx(&tx));
+    }
+}
@@ -0,0 +1,64 @@
+use std::path::PathBuf;
+
+use clap::Args;
+use sanctum_solana_cli_utils::PubkeySrc;
+use solana_sdk::{clock::Clock, sysvar};
+
+use crate::{PoolSnapshot, SnapshotStore};
+
+use super::{fetch_pool_info, Subcmd};
+
+#[derive(Args, Debug)]
+#[command(
+    long_about = "Record the current pool and validator list state into a local snapshot store for `history`.
+
+Run this at least once every epoch, e.g. after the update crank, to build up a per-epoch history.
+Snapshotting the same epoch more than once is fine, `history` uses the latest snapshot of each epoch."
+)]
+pub struct SnapshotArgs {
+    #[arg(
+        long,
+        help = "Directory of the snapshot store. Defaults to ~/.config/splsp/snapshots"
+    )]
+    pub store_dir: Option<PathBuf>,
+
+    #[arg(
+        help = "Address of the stake pool. Can either be a base58-encoded pubkey or keypair file"
+    )]
+    pub pool: String,
+}
+
+impl SnapshotArgs {
+    pub async fn run(args: crate::Args) {
+        let Self { store_dir, pool } = match args.subcmd {
+            Subcmd::Snapshot(a) => a,
+            _ => unreachable!(),
+        };
+
+        let pool = PubkeySrc::parse(&pool).unwrap().pubkey();
+        let rpc = args.config.nonblocking_rpc_client();
+
+        let clock = rpc.get_account(&sysvar::clock::ID).await.unwrap();
+        let Clock {
+            slot,
+            epoch,
+            unix_timestamp,
+            ..
+        } = bincode::deserialize(&clock.data).unwrap();
+        let snapshot = PoolSnapshot {
+            epoch,
+            slot,
+            unix_timestamp,
+            pool: fetch_pool_info(&rpc, pool, true).await,
+        };
+
+        let store = SnapshotStore::for_pool(store_dir.as_deref(), &pool);
+        store.append(&snapshot).unwrap_or_else(|e| {
+            panic!("Failed to append snapshot to {}: {e}", store.path.display())
+        });
+        eprintln!(
+            "Snapshotted pool {pool} for epoch {epoch} to {}",
+            store.path.display()
+        );
+    }
+}
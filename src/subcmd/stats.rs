@@ -0,0 +1,59 @@
+use borsh::BorshDeserialize;
+use clap::Args;
+use sanctum_solana_cli_utils::PubkeySrc;
+use solana_sdk::{clock::Clock, epoch_schedule::EpochSchedule, sysvar};
+use spl_stake_pool_interface::StakePool;
+
+use crate::PoolStats;
+
+use super::Subcmd;
+
+#[derive(Args, Debug)]
+#[command(
+    long_about = "Compute a stake pool's SOL-per-LST exchange rate, last epoch rate change, APY and epoch fee take.
+
+The last epoch rate change is between the pool's state before and after its last update,
+so the stats are for an older epoch if the pool has not been updated for the current one."
+)]
+pub struct StatsArgs {
+    #[arg(
+        help = "Address of the stake pool. Can either be a base58-encoded pubkey or keypair file"
+    )]
+    pub pool: String,
+}
+
+impl StatsArgs {
+    pub async fn run(args: crate::Args) {
+        let Self { pool } = match &args.subcmd {
+            Subcmd::Stats(a) => a,
+            _ => unreachable!(),
+        };
+
+        let pool = PubkeySrc::parse(pool).unwrap().pubkey();
+        let rpc = args.config.nonblocking_rpc_client();
+
+        let mut fetched = rpc
+            .get_multiple_accounts(&[pool, sysvar::clock::ID, sysvar::epoch_schedule::ID])
+            .await
+            .unwrap();
+        let epoch_schedule = fetched.pop().unwrap().unwrap();
+        let clock = fetched.pop().unwrap().unwrap();
+        let stake_pool_acc = fetched.pop().unwrap().unwrap();
+
+        let epoch_schedule: EpochSchedule = bincode::deserialize(&epoch_schedule.data).unwrap();
+        let clock: Clock = bincode::deserialize(&clock.data).unwrap();
+        let stake_pool = StakePool::deserialize(&mut stake_pool_acc.data.as_slice()).unwrap();
+
+        if stake_pool.last_update_epoch < clock.epoch {
+            eprintln!(
+                "Pool has not been updated for epoch {}, stats are as of its last update in epoch {}",
+                clock.epoch, stake_pool.last_update_epoch
+            );
+        }
+
+        args.output.print(
+            "stats",
+            &PoolStats::new(&stake_pool, &clock, &epoch_schedule),
+        );
+    }
+}
@@ -0,0 +1,52 @@
+use std::{fs::read_to_string, path::PathBuf};
+
+use clap::Args;
+use sanctum_solana_cli_utils::{HandleTxArgs, TxSendingNonblockingRpcClient};
+
+use crate::tx_utils::{combine_tx_signatures, decode_b64_tx, missing_signers};
+
+use super::Subcmd;
+
+#[derive(Args, Debug)]
+#[command(
+    long_about = "Combine the signatures of partially-signed transactions output by sign and send the fully-signed transaction"
+)]
+pub struct SubmitArgs {
+    #[arg(
+        help = "Paths to files each containing a base64 encoded partially-signed transaction. All transactions must be of the same message.",
+        required = true
+    )]
+    pub tx_files: Vec<PathBuf>,
+}
+
+impl SubmitArgs {
+    pub async fn run(args: crate::Args) {
+        let Self { tx_files } = match args.subcmd {
+            Subcmd::Submit(a) => a,
+            _ => unreachable!(),
+        };
+
+        let tx = combine_tx_signatures(
+            tx_files
+                .iter()
+                .map(|path| decode_b64_tx(&read_to_string(path).unwrap())),
+        );
+
+        let missing = missing_signers(&tx);
+        if !missing.is_empty() {
+            panic!(
+                "Missing signatures from: {}",
+                missing
+                    .iter()
+                    .map(|pk| pk.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+
+        let rpc = args.config.nonblocking_rpc_client();
+        rpc.handle_tx(&tx, args.send_mode, HandleTxArgs::cli_default())
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,373 @@
+use std::path::PathBuf;
+
+use clap::Args;
+use sanctum_solana_cli_utils::PubkeySrc;
+use solana_sdk::signer::{null_signer::NullSigner, Signer};
+
+use crate::{
+    pool_config::{
+        delegatable_lamports, is_delegation_scheme_valid, print_adding_validators_msg,
+        print_removing_validators_msg, resolve_delegation_scheme, sort_delegation_scheme,
+        ConfigRaw, Plan, PlanChange, PlanChangeKind, PlanSrc, SyncMode, SyncPoolConfig,
+        SyncValidatorListConfig, ValidatorDelegation, ValidatorDelegationTarget,
+    },
+    ps,
+};
+
+use super::{
+    sync_delegation::fetch_delegation_change_srcs,
+    sync_utils::{SyncState, SyncTxs},
+    Subcmd,
+};
+
+#[derive(Args, Debug)]
+#[command(
+    long_about = "(Manager and staker) sync stake pool settings, validator list and delegation targets with a single pool config file"
+)]
+pub struct SyncArgs {
+    #[arg(help = r#"Path to pool config file to update the stake pool to.
+Each validator entry may have a `target` field to sync its delegation to, validators without one are left as-is.
+Make sure to set `old-manager` field in toml for a non-wallet current pool manager signer."#)]
+    pub pool_config: PathBuf,
+
+    #[arg(
+        long,
+        help = "Output a toml plan of all changes to stdout instead of building and sending any transactions"
+    )]
+    pub plan: bool,
+}
+
+impl SyncArgs {
+    pub async fn run(args: crate::Args) {
+        let Self { pool_config, plan } = match &args.subcmd {
+            Subcmd::Sync(a) => a,
+            _ => unreachable!(),
+        };
+        let config = ConfigRaw::read_from_path(pool_config).unwrap();
+        Self::run_with_config(&args, config, SyncMode::from_plan_flag(*plan)).await;
+    }
+
+    pub async fn run_with_config(args: &crate::Args, config: ConfigRaw, mode: SyncMode<'_>) {
+        let src = PlanSrc::Sync(config.clone());
+
+        let mut delegation_scheme: Vec<ValidatorDelegation> = config
+            .validators
+            .iter()
+            .flatten()
+            .filter_map(|v| {
+                v.target.map(|target| ValidatorDelegation {
+                    vote: PubkeySrc::parse(&v.vote).unwrap().pubkey(),
+                    target,
+                })
+            })
+            .collect();
+        sort_delegation_scheme(&mut delegation_scheme);
+        is_delegation_scheme_valid(delegation_scheme.iter().map(|v| &v.target)).unwrap();
+
+        let rpc = args.config.nonblocking_rpc_client();
+        let payer = args.config.signer();
+        let nonce = args.tx_nonce();
+        let luts = args.luts(&rpc).await;
+
+        let mut txs = SyncTxs::new(
+            args,
+            &rpc,
+            nonce.as_ref(),
+            &luts,
+            args.squads_proposer(&rpc).await,
+        );
+        let squads_vault = txs.squads.as_ref().map(|s| NullSigner::new(&s.vault));
+        let authority_fb = squads_vault
+            .as_ref()
+            .map_or(payer.as_ref(), |v| v as &dyn Signer);
+
+        let ConfigRaw {
+            pool,
+            manager,
+            staker,
+            old_manager,
+            ..
+        } = &config;
+        ps!(old_manager, @fb authority_fb, @sm mode.signer_send_mode(args.authority_send_mode()));
+        let new_manager = manager;
+        ps!(new_manager, @fb old_manager, @sm mode.signer_send_mode(args.authority_send_mode()));
+        // staker pubkey to set in the pool is separate from the staker signer
+        // for validator list and delegation changes, which defaults to config wallet
+        ps!(staker, @fb authority_fb, @sm mode.signer_send_mode(args.authority_send_mode()));
+
+        let pool = PubkeySrc::parse(pool.as_ref().unwrap()).unwrap().pubkey();
+        let state = SyncState::fetch(&rpc, pool).await;
+        let SyncState {
+            program_id,
+            stake_pool,
+            validators: old_validators,
+            curr_epoch,
+            rent,
+            ..
+        } = &state;
+
+        let spc = SyncPoolConfig::from_config_raw(
+            &config,
+            *program_id,
+            pool,
+            stake_pool,
+            payer.as_ref(),
+            old_manager,
+            new_manager,
+        );
+        let svlc = SyncValidatorListConfig::from_config_raw(
+            &config,
+            *program_id,
+            pool,
+            stake_pool,
+            payer.as_ref(),
+            staker,
+            rent,
+        );
+
+        // Plan
+
+        let pool_changeset = spc.changeset(stake_pool);
+        let (add, remove) = svlc.add_remove_changeset(old_validators);
+        let preferred_validator_changes = svlc.preferred_validator_changeset(stake_pool);
+        let is_validator_list_changing =
+            add.clone().next().is_some() || remove.clone().next().is_some();
+        let is_updated_for_curr_epoch = state.is_updated_for_curr_epoch();
+
+        if !pool_changeset.is_empty() && old_manager.pubkey() != stake_pool.manager {
+            panic!(
+                "Wrong manager. Expecting {}, got {}",
+                stake_pool.manager,
+                old_manager.pubkey()
+            );
+        }
+        if (is_validator_list_changing
+            || preferred_validator_changes.clone().next().is_some()
+            || !delegation_scheme.is_empty())
+            && staker.pubkey() != spc.staker
+        {
+            panic!(
+                "Wrong staker. Expecting {}, got {}",
+                spc.staker,
+                staker.pubkey()
+            );
+        }
+
+        if is_updated_for_curr_epoch {
+            eprintln!("Update not required");
+        } else {
+            eprintln!("Pool not yet updated for epoch {curr_epoch}, will update");
+        }
+
+        args.output.eprint_changes("pool-changes", &pool_changeset);
+        print_removing_validators_msg(args.output, remove.clone());
+        print_adding_validators_msg(args.output, add.clone());
+        args.output.eprint_changes(
+            "preferred-validator-changes",
+            &preferred_validator_changes.clone().collect::<Vec<_>>(),
+        );
+
+        // validators that are not yet part of the pool can only be delegated to after they're added,
+        // and no delegation change is known before the update crank if the pool is not yet updated.
+        // Percent and weight targets are resolved once, against the total lamports
+        // the delegation step sees, which only the update crank changes.
+        // Resolve before partitioning since weight targets depend on the full delegation scheme
+        let delegation_scheme = if is_updated_for_curr_epoch {
+            resolve_delegation_scheme(
+                &delegation_scheme,
+                delegatable_lamports(stake_pool.total_lamports, rent),
+            )
+        } else {
+            delegation_scheme
+        };
+        let (mut known_delegation_scheme, mut pending_delegation_scheme): (Vec<_>, Vec<_>) =
+            delegation_scheme
+                .iter()
+                .copied()
+                .partition(|ValidatorDelegation { vote, .. }| {
+                    is_updated_for_curr_epoch
+                        && old_validators
+                            .iter()
+                            .any(|vsi| vsi.vote_account_address == *vote)
+                });
+        // remainder must be synced last, after all other pending validators
+        if !pending_delegation_scheme.is_empty()
+            && known_delegation_scheme
+                .last()
+                .is_some_and(|d| matches!(d.target, ValidatorDelegationTarget::Remainder))
+        {
+            pending_delegation_scheme.extend(known_delegation_scheme.pop());
+        }
+
+        let change_srcs = if known_delegation_scheme.is_empty() {
+            vec![]
+        } else {
+            fetch_delegation_change_srcs(
+                &rpc,
+                program_id,
+                &pool,
+                old_validators,
+                &known_delegation_scheme,
+            )
+            .await
+        };
+        let mut sdc = state.sync_delegation_config(payer.as_ref(), staker);
+        // new validators' stake accounts are funded from the reserve before delegation
+        sdc.reserve_lamports = sdc
+            .reserve_lamports
+            .saturating_sub(svlc.add_validators_reserve_lamports(add.clone()));
+        let changes = sdc.changeset(
+            change_srcs
+                .iter()
+                .map(|(vsi, vsa, tsa, target)| (*vsi, vsa, tsa, *target)),
+        );
+        if delegation_scheme.is_empty() {
+            eprintln!("No delegation targets specified");
+        }
+        changes.print_all_changes(args.output);
+        for ValidatorDelegation { vote, .. } in pending_delegation_scheme.iter() {
+            eprintln!("{vote}: delegation will be synced after update and validator list sync");
+        }
+        let mut delegation_plan_changes = changes.plan_changes(&staker.pubkey());
+        delegation_plan_changes.extend(pending_delegation_scheme.iter().map(
+            |ValidatorDelegation { vote, .. }| PlanChange {
+                kind: PlanChangeKind::PendingStake,
+                target: vote.to_string(),
+                old: None,
+                new: None,
+                signers: vec![staker.pubkey().to_string()],
+                est_lamport_cost: 0,
+            },
+        ));
+
+        if mode.handle_plan(|| {
+            let mut plan = Plan::new(&pool, &payer.pubkey(), src);
+            plan.add_update_pool_if_needed(stake_pool.last_update_epoch, *curr_epoch);
+            plan.add_txs(pool_changeset.len(), usize::MAX, &spc.signers_maybe_dup());
+            // worst case: every validator to remove needs its stake decreased first
+            plan.add_txs(
+                2 * remove.clone().count(),
+                txs.max_remove_validator_ixs_enum_per_tx(),
+                &svlc.signers_maybe_dup(),
+            );
+            plan.add_txs(
+                add.clone().count(),
+                txs.max_add_validators_ix_per_tx(),
+                &svlc.signers_maybe_dup(),
+            );
+            plan.add_txs(
+                preferred_validator_changes.clone().count(),
+                usize::MAX,
+                &svlc.signers_maybe_dup(),
+            );
+            plan.add_txs(
+                delegation_plan_changes.len(),
+                txs.max_increase_validator_stake_ix_per_tx(&state),
+                &svlc.signers_maybe_dup(),
+            );
+            plan.changes.extend(spc.plan_changes(&pool_changeset));
+            plan.changes.extend(svlc.plan_changes(
+                add.clone(),
+                remove.clone(),
+                preferred_validator_changes.clone(),
+            ));
+            plan.changes.extend(delegation_plan_changes);
+            plan
+        }) {
+            return;
+        }
+
+        // Execute: update crank -> pool params -> validator list -> delegation
+
+        // need to update first to be able to add/remove validators and change delegation
+        state.update_if_needed(&txs, payer.as_ref()).await;
+
+        if !pool_changeset.is_empty() {
+            txs.send_chunked(
+                &spc.changeset_ixs(&pool_changeset).unwrap(),
+                usize::MAX,
+                &mut spc.signers_maybe_dup(),
+            )
+            .await;
+        }
+
+        let remove_vsas = state.fetch_vsa_states(&rpc, remove.clone()).await;
+        txs.send_chunked(
+            &svlc.remove_validators_ixs(remove.zip(remove_vsas)).unwrap(),
+            txs.max_remove_validator_ixs_enum_per_tx(),
+            &mut svlc.signers_maybe_dup(),
+        )
+        .await;
+
+        txs.send_chunked(
+            &svlc.add_validators_ixs(add).unwrap(),
+            txs.max_add_validators_ix_per_tx(),
+            &mut svlc.signers_maybe_dup(),
+        )
+        .await;
+
+        txs.send_chunked(
+            &svlc
+                .preferred_validator_ixs(preferred_validator_changes)
+                .unwrap(),
+            usize::MAX,
+            &mut svlc.signers_maybe_dup(),
+        )
+        .await;
+
+        // changes of validators already in the pool were computed and, in apply mode,
+        // checked against the plan before anything was sent, so send them as-is
+        // IncreaseAdditionalValidatorStake is worst case, takes 14 account inputs vs Decrease's 11
+        txs.send_chunked(
+            &sdc.sync_delegation_ixs(changes).collect::<Vec<_>>(),
+            txs.max_increase_validator_stake_ix_per_tx(&state),
+            &mut sdc.signers_maybe_dup(),
+        )
+        .await;
+
+        if pending_delegation_scheme.is_empty() {
+            return;
+        }
+
+        // refetch since update and validator list changes modify reserve and validator stake amounts
+        let state = SyncState::fetch(&rpc, pool).await;
+        if !is_updated_for_curr_epoch {
+            // pending_delegation_scheme is the full unresolved delegation scheme
+            pending_delegation_scheme = resolve_delegation_scheme(
+                &pending_delegation_scheme,
+                delegatable_lamports(state.stake_pool.total_lamports, &state.rent),
+            );
+        }
+        // validators to be added wont be in the list if txs were not actually sent
+        pending_delegation_scheme.retain(|ValidatorDelegation { vote, .. }| {
+            state
+                .validators
+                .iter()
+                .any(|vsi| vsi.vote_account_address == *vote)
+        });
+        let change_srcs = fetch_delegation_change_srcs(
+            &rpc,
+            &state.program_id,
+            &pool,
+            &state.validators,
+            &pending_delegation_scheme,
+        )
+        .await;
+
+        let sdc = state.sync_delegation_config(payer.as_ref(), staker);
+        let changes = sdc.changeset(
+            change_srcs
+                .iter()
+                .map(|(vsi, vsa, tsa, target)| (*vsi, vsa, tsa, *target)),
+        );
+        eprintln!("Delegation after update and validator list sync:");
+        changes.print_all_changes(args.output);
+
+        txs.send_chunked(
+            &sdc.sync_delegation_ixs(changes).collect::<Vec<_>>(),
+            txs.max_increase_validator_stake_ix_per_tx(&state),
+            &mut sdc.signers_maybe_dup(),
+        )
+        .await;
+    }
+}
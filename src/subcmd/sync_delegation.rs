@@ -1,90 +1,102 @@
-use std::{cmp::Ordering, path::PathBuf};
+use std::path::PathBuf;
 
 use borsh::BorshDeserialize;
 use clap::Args;
-use itertools::Itertools;
-use sanctum_solana_cli_utils::{PubkeySrc, TxSendMode};
+use sanctum_solana_cli_utils::PubkeySrc;
 use sanctum_spl_stake_pool_lib::{
     FindTransientStakeAccount, FindTransientStakeAccountArgs, FindValidatorStakeAccount,
 };
+use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_sdk::{
-    clock::Clock,
     pubkey::Pubkey,
-    rent::Rent,
+    signer::{null_signer::NullSigner, Signer},
     stake::{self, state::StakeStateV2},
-    sysvar,
 };
-use spl_stake_pool_interface::{StakePool, ValidatorList, ValidatorStakeInfo};
+use spl_stake_pool_interface::ValidatorStakeInfo;
 
 use crate::{
-    handle_tx_full, is_delegation_scheme_valid, ps, with_auto_cb_ixs, SyncDelegationConfig,
-    SyncDelegationConfigToml, ValidatorDelegation, ValidatorDelegationTarget,
-    MAX_INCREASE_VALIDATOR_STAKE_IX_PER_TX,
+    delegatable_lamports, is_delegation_scheme_valid, ps, resolve_delegation_scheme,
+    sort_delegation_scheme, Plan, PlanSrc, SyncDelegationConfigToml, SyncMode, ValidatorDelegation,
+    ValidatorDelegationTarget,
 };
 
-use super::Subcmd;
+use super::{
+    sync_utils::{SyncState, SyncTxs},
+    Subcmd,
+};
 
 #[derive(Args, Debug)]
 #[command(long_about = "(Staker only) sync target stake delegation amounts")]
 pub struct SyncDelegationArgs {
     #[arg(help = "Path to sync delegation config file")]
     pub sync_delegation_config: PathBuf,
+
+    #[arg(
+        long,
+        help = "Output a toml plan of all changes to stdout instead of building and sending any transactions"
+    )]
+    pub plan: bool,
 }
 
 impl SyncDelegationArgs {
     pub async fn run(args: crate::Args) {
         let Self {
             sync_delegation_config,
-        } = match args.subcmd {
+            plan,
+        } = match &args.subcmd {
             Subcmd::SyncDelegation(a) => a,
             _ => unreachable!(),
         };
+        let config = SyncDelegationConfigToml::read_from_path(sync_delegation_config).unwrap();
+        Self::run_with_config(&args, config, SyncMode::from_plan_flag(*plan)).await;
+    }
 
+    pub async fn run_with_config(
+        args: &crate::Args,
+        config: SyncDelegationConfigToml,
+        mode: SyncMode<'_>,
+    ) {
+        let src = PlanSrc::SyncDelegation(config.clone());
         let SyncDelegationConfigToml {
             pool,
             staker,
             validators: delegation_scheme,
-        } = SyncDelegationConfigToml::read_from_path(sync_delegation_config).unwrap();
+        } = config;
         let mut delegation_scheme: Vec<ValidatorDelegation> = delegation_scheme
             .into_iter()
             .map(|s| s.try_into().unwrap())
             .collect();
-        // move Remainder to the end
-        // TODO: kinda jank to rely on this sorting behaviour to ensure
-        // correct handling of Remainder
-        delegation_scheme.sort_by(|a, b| {
-            if matches!(a.target, ValidatorDelegationTarget::Remainder) {
-                Ordering::Greater
-            } else if matches!(b.target, ValidatorDelegationTarget::Remainder) {
-                Ordering::Less
-            } else {
-                // preserve og order
-                Ordering::Equal
-            }
-        });
+        sort_delegation_scheme(&mut delegation_scheme);
         is_delegation_scheme_valid(delegation_scheme.iter().map(|v| &v.target)).unwrap();
 
         let rpc = args.config.nonblocking_rpc_client();
         let payer = args.config.signer();
+        let nonce = args.tx_nonce();
+        let luts = args.luts(&rpc).await;
 
         let pool = PubkeySrc::parse(&pool).unwrap().pubkey();
 
-        ps!(staker, @fb payer.as_ref(), @sm args.send_mode);
-
-        let mut fetched = rpc
-            .get_multiple_accounts(&[pool, sysvar::clock::ID, sysvar::rent::ID])
-            .await
-            .unwrap();
-        let rent = fetched.pop().unwrap().unwrap();
-        let clock = fetched.pop().unwrap().unwrap();
-        let stake_pool_acc = fetched.pop().unwrap().unwrap();
-        let program_id = stake_pool_acc.owner;
-
-        let rent: Rent = bincode::deserialize(&rent.data).unwrap();
-        let Clock {
-            epoch: curr_epoch, ..
-        } = bincode::deserialize(&clock.data).unwrap();
-        let stake_pool = StakePool::deserialize(&mut stake_pool_acc.data.as_slice()).unwrap();
+        let mut txs = SyncTxs::new(
+            args,
+            &rpc,
+            nonce.as_ref(),
+            &luts,
+            args.squads_proposer(&rpc).await,
+        );
+        let squads_vault = txs.squads.as_ref().map(|s| NullSigner::new(&s.vault));
+        let authority_fb = squads_vault
+            .as_ref()
+            .map_or(payer.as_ref(), |v| v as &dyn Signer);
+
+        ps!(staker, @fb authority_fb, @sm mode.signer_send_mode(args.authority_send_mode()));
+
+        let state = SyncState::fetch(&rpc, pool).await;
+        let SyncState {
+            program_id,
+            stake_pool,
+            validators,
+            ..
+        } = &state;
 
         if staker.pubkey() != stake_pool.staker {
             panic!(
@@ -94,120 +106,131 @@ impl SyncDelegationArgs {
             );
         }
 
-        let mut fetched = rpc
-            .get_multiple_accounts(&[stake_pool.validator_list, stake_pool.reserve_stake])
-            .await
-            .unwrap();
-        let reserve_acc = fetched.pop().unwrap().unwrap();
-        let validator_list_acc = fetched.pop().unwrap().unwrap();
-
-        let ValidatorList { validators, .. } =
-            ValidatorList::deserialize(&mut validator_list_acc.data.as_slice()).unwrap();
-
-        let vsis: Vec<&ValidatorStakeInfo> = delegation_scheme
-            .iter()
-            .map(|ValidatorDelegation { vote, .. }| {
-                validators
-                    .iter()
-                    .find(|vsi| vsi.vote_account_address == *vote)
-                    .unwrap_or_else(|| panic!("Validator {vote} not part of pool"))
-            })
-            .collect();
-        let stake_accs: Vec<Pubkey> = vsis
-            .iter()
-            .flat_map(
-                |ValidatorStakeInfo {
-                     vote_account_address,
-                     transient_seed_suffix,
-                     ..
-                 }| {
-                    let (vsa_pubkey, _bump) = FindValidatorStakeAccount {
+        let delegation_scheme = resolve_delegation_scheme(
+            &delegation_scheme,
+            delegatable_lamports(stake_pool.total_lamports, &state.rent),
+        );
+        let change_srcs =
+            fetch_delegation_change_srcs(&rpc, program_id, &pool, validators, &delegation_scheme)
+                .await;
+
+        let sdc = state.sync_delegation_config(payer.as_ref(), staker);
+
+        let changes = sdc.changeset(
+            change_srcs
+                .iter()
+                .map(|(vsi, vsa, tsa, target)| (*vsi, vsa, tsa, *target)),
+        );
+        changes.print_all_changes(args.output);
+
+        if mode.handle_plan(|| {
+            let mut plan = Plan::new(&pool, &payer.pubkey(), src);
+            plan.changes = changes.plan_changes(&staker.pubkey());
+            plan.add_txs(
+                plan.changes.len(),
+                txs.max_increase_validator_stake_ix_per_tx(&state),
+                &sdc.signers_maybe_dup(),
+            );
+            plan
+        }) {
+            return;
+        }
+
+        // IncreaseAdditionalValidatorStake is worst case, takes 14 account inputs vs Decrease's 11
+        txs.send_chunked(
+            &sdc.sync_delegation_ixs(changes).collect::<Vec<_>>(),
+            txs.max_increase_validator_stake_ix_per_tx(&state),
+            &mut sdc.signers_maybe_dup(),
+        )
+        .await;
+    }
+}
+
+/// Returns `(validator_stake_info, validator_stake_account_state, transient_stake_account_state, desired_stake_lamports)`
+/// for each validator in `delegation_scheme`.
+///
+/// Panics if any validator in `delegation_scheme` is not part of the pool
+/// or if `delegation_scheme` has unresolved percent or weight targets
+pub(crate) async fn fetch_delegation_change_srcs<'a>(
+    rpc: &RpcClient,
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    validators: &'a [ValidatorStakeInfo],
+    delegation_scheme: &[ValidatorDelegation],
+) -> Vec<(
+    &'a ValidatorStakeInfo,
+    StakeStateV2,
+    Option<StakeStateV2>,
+    u64,
+)> {
+    let pool = *pool;
+    let program_id = *program_id;
+    let vsis: Vec<&ValidatorStakeInfo> = delegation_scheme
+        .iter()
+        .map(|ValidatorDelegation { vote, .. }| {
+            validators
+                .iter()
+                .find(|vsi| vsi.vote_account_address == *vote)
+                .unwrap_or_else(|| panic!("Validator {vote} not part of pool"))
+        })
+        .collect();
+    let stake_accs: Vec<Pubkey> = vsis
+        .iter()
+        .flat_map(
+            |ValidatorStakeInfo {
+                 vote_account_address,
+                 transient_seed_suffix,
+                 ..
+             }| {
+                let (vsa_pubkey, _bump) = FindValidatorStakeAccount {
+                    pool,
+                    vote: *vote_account_address,
+                    seed: None,
+                }
+                .run_for_prog(&program_id);
+                let (tsa_pubkey, _bump) =
+                    FindTransientStakeAccount::new(FindTransientStakeAccountArgs {
                         pool,
                         vote: *vote_account_address,
-                        seed: None,
-                    }
+                        seed: *transient_seed_suffix,
+                    })
                     .run_for_prog(&program_id);
-                    let (tsa_pubkey, _bump) =
-                        FindTransientStakeAccount::new(FindTransientStakeAccountArgs {
-                            pool,
-                            vote: *vote_account_address,
-                            seed: *transient_seed_suffix,
-                        })
-                        .run_for_prog(&program_id);
-                    [vsa_pubkey, tsa_pubkey]
-                },
+                [vsa_pubkey, tsa_pubkey]
+            },
+        )
+        .collect();
+    let fetched = rpc.get_multiple_accounts(&stake_accs).await.unwrap();
+    let fetched_stake_accs: Vec<(StakeStateV2, Option<StakeStateV2>)> = fetched
+        .chunks(2)
+        .map(|a| {
+            (
+                // vsa should always exist
+                StakeStateV2::deserialize(&mut a[0].as_ref().unwrap().data.as_slice()).unwrap(),
+                // tsa might be in all kinds of states
+                a[1].as_ref()
+                    .filter(|a| a.owner == stake::program::ID) // donation mightve happened after merging
+                    .map(|a| StakeStateV2::deserialize(&mut a.data.as_slice()).unwrap()),
             )
-            .collect();
-        let fetched = rpc.get_multiple_accounts(&stake_accs).await.unwrap();
-        let fetched_stake_accs: Vec<(StakeStateV2, Option<StakeStateV2>)> = fetched
-            .chunks(2)
-            .map(|a| {
-                (
-                    // vsa should always exist
-                    StakeStateV2::deserialize(&mut a[0].as_ref().unwrap().data.as_slice()).unwrap(),
-                    // tsa might be in all kinds of states
-                    a[1].as_ref()
-                        .filter(|a| a.owner == stake::program::ID) // donation mightve happened after merging
-                        .map(|a| StakeStateV2::deserialize(&mut a.data.as_slice()).unwrap()),
-                )
-            })
-            .collect();
-
-        let change_srcs = delegation_scheme
-            .iter()
-            .zip(vsis)
-            .zip(fetched_stake_accs.iter())
-            .map(|((scheme, vsi), (vsa, tsa))| {
-                let target_stake = match scheme.target {
-                    ValidatorDelegationTarget::Lamports(lamports) => lamports,
-                    // TODO: u64::MAX ensures + having the remainder entry at the end of the array
-                    // ensures correct behaviour but the terminal will always print a shortfall msg
-                    ValidatorDelegationTarget::Remainder => u64::MAX,
-                };
-                (vsi, vsa, tsa, target_stake)
-            });
-
-        let sdc = SyncDelegationConfig {
-            program_id,
-            payer: payer.as_ref(),
-            staker,
-            pool,
-            validator_list: stake_pool.validator_list,
-            reserve: stake_pool.reserve_stake,
-            reserve_lamports: reserve_acc.lamports,
-            curr_epoch,
-            rent,
-        };
-
-        let changes = sdc.changeset(change_srcs);
-        changes.print_all_changes();
-
-        // IncreaseAdditionalValidatorStake is worst case, takes 14 account inputs vs Decrease's 11
-        for ix_chunk in &sdc
-            .sync_delegation_ixs(changes)
-            .chunks(MAX_INCREASE_VALIDATOR_STAKE_IX_PER_TX)
-        {
-            let ix_chunk = match args.send_mode {
-                TxSendMode::DumpMsg => ix_chunk.collect(),
-                _ => {
-                    with_auto_cb_ixs(
-                        &rpc,
-                        &payer.pubkey(),
-                        ix_chunk.collect(),
-                        &[],
-                        args.fee_limit_cb,
+        })
+        .collect();
+
+    delegation_scheme
+        .iter()
+        .zip(vsis)
+        .zip(fetched_stake_accs)
+        .map(|((scheme, vsi), (vsa, tsa))| {
+            let target_stake = match scheme.target {
+                ValidatorDelegationTarget::Lamports(lamports) => lamports,
+                // TODO: u64::MAX ensures + having the remainder entry at the end of the array
+                // ensures correct behaviour but the terminal will always print a shortfall msg
+                ValidatorDelegationTarget::Remainder => u64::MAX,
+                ValidatorDelegationTarget::Percent(_) | ValidatorDelegationTarget::Weight(_) => {
+                    panic!(
+                        "delegation scheme must be resolved with resolve_delegation_scheme() first"
                     )
-                    .await
                 }
             };
-            handle_tx_full(
-                &rpc,
-                args.send_mode,
-                &ix_chunk,
-                &[],
-                &mut sdc.signers_maybe_dup(),
-            )
-            .await;
-        }
-    }
+            (vsi, vsa, tsa, target_stake)
+        })
+        .collect()
 }
@@ -0,0 +1,119 @@
+use std::str::FromStr;
+
+use borsh::BorshDeserialize;
+use clap::{
+    builder::{StringValueParser, TypedValueParser},
+    Args,
+};
+use sanctum_solana_cli_utils::TxSendMode;
+use solana_sdk::{
+    address_lookup_table::{instruction::extend_lookup_table, state::AddressLookupTable},
+    pubkey::Pubkey,
+};
+use spl_stake_pool_interface::{StakePool, ValidatorList};
+
+use crate::{
+    handle_tx_full, lut_addresses_to_extend, max_extend_lut_addresses_per_tx, pool_lut_addresses,
+    ps, with_auto_cb_ixs,
+};
+
+use super::Subcmd;
+
+#[derive(Args, Debug)]
+#[command(
+    long_about = "Extend a lookup table created by create-lut with the stake accounts of validators added to the pool since"
+)]
+pub struct SyncLutArgs {
+    #[arg(
+        long,
+        short,
+        help = "Authority of the lookup table. Defaults to payer if not provided"
+    )]
+    pub authority: Option<String>,
+
+    #[arg(
+        help = "Pubkey of the pool the lookup table is for",
+        value_parser = StringValueParser::new().try_map(|s| Pubkey::from_str(&s)),
+    )]
+    pub pool: Pubkey,
+
+    #[arg(
+        help = "Pubkey of the lookup table to extend",
+        value_parser = StringValueParser::new().try_map(|s| Pubkey::from_str(&s)),
+    )]
+    pub lut: Pubkey,
+}
+
+impl SyncLutArgs {
+    pub async fn run(args: crate::Args) {
+        // args methods borrow all of args so they must be called before subcmd is moved out
+        let rpc = args.config.nonblocking_rpc_client();
+        let payer = args.config.signer();
+        let nonce = args.tx_nonce();
+        let luts = args.luts(&rpc).await;
+
+        let Self {
+            authority,
+            pool,
+            lut,
+        } = match args.subcmd {
+            Subcmd::SyncLut(a) => a,
+            _ => unreachable!(),
+        };
+
+        ps!(authority, @fb payer.as_ref(), @sm args.send_mode);
+
+        let mut fetched = rpc.get_multiple_accounts(&[pool, lut]).await.unwrap();
+        let lut_acc = fetched.pop().unwrap().unwrap();
+        let stake_pool_acc = fetched.pop().unwrap().unwrap();
+        let program_id = stake_pool_acc.owner;
+        let stake_pool = StakePool::deserialize(&mut stake_pool_acc.data.as_slice()).unwrap();
+        let validator_list_acc = rpc.get_account(&stake_pool.validator_list).await.unwrap();
+        let ValidatorList { validators, .. } =
+            ValidatorList::deserialize(&mut validator_list_acc.data.as_slice()).unwrap();
+
+        let lut_state = AddressLookupTable::deserialize(&lut_acc.data).unwrap();
+        if lut_state.meta.authority != Some(authority.pubkey()) {
+            panic!(
+                "Wrong lookup table authority. Expecting {:?}, got {}",
+                lut_state.meta.authority,
+                authority.pubkey()
+            );
+        }
+
+        let to_extend = lut_addresses_to_extend(
+            &lut_state.addresses,
+            &pool_lut_addresses(&program_id, &pool, &stake_pool, &validators),
+        );
+        if to_extend.is_empty() {
+            eprintln!("No changes necessary");
+            return;
+        }
+        eprintln!(
+            "Extending lookup table {lut} with {} addresses",
+            to_extend.len()
+        );
+
+        for chunk in to_extend.chunks(max_extend_lut_addresses_per_tx(nonce.as_ref())) {
+            let ixs = vec![extend_lookup_table(
+                lut,
+                authority.pubkey(),
+                Some(payer.pubkey()),
+                chunk.to_vec(),
+            )];
+            let ixs = match args.send_mode {
+                TxSendMode::DumpMsg => ixs,
+                _ => with_auto_cb_ixs(&rpc, &payer.pubkey(), ixs, &luts, args.fee_limit_cb).await,
+            };
+            handle_tx_full(
+                &rpc,
+                args.send_mode,
+                nonce.as_ref(),
+                &ixs,
+                &luts,
+                &mut [payer.as_ref(), authority],
+            )
+            .await;
+        }
+    }
+}
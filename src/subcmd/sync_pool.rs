@@ -2,16 +2,16 @@ use std::path::PathBuf;
 
 use borsh::BorshDeserialize;
 use clap::Args;
-use sanctum_solana_cli_utils::{PubkeySrc, TxSendMode};
+use sanctum_solana_cli_utils::PubkeySrc;
+use solana_sdk::signer::{null_signer::NullSigner, Signer};
 use spl_stake_pool_interface::StakePool;
 
 use crate::{
-    pool_config::{ConfigRaw, SyncPoolConfig},
+    pool_config::{ConfigRaw, Plan, PlanSrc, SyncMode, SyncPoolConfig},
     ps,
-    tx_utils::{handle_tx_full, with_auto_cb_ixs},
 };
 
-use super::Subcmd;
+use super::{sync_utils::SyncTxs, Subcmd};
 
 #[derive(Args, Debug)]
 #[command(long_about = "(Manager only) sync stake pool settings with a pool config file")]
@@ -21,45 +21,59 @@ pub struct SyncPoolArgs {
 Make sure to set `old-manager` field in toml for a non-wallet current pool manager signer."#
     )]
     pub pool_config: PathBuf,
+
+    #[arg(
+        long,
+        help = "Output a toml plan of all changes to stdout instead of building and sending any transactions"
+    )]
+    pub plan: bool,
 }
 
 impl SyncPoolArgs {
     pub async fn run(args: crate::Args) {
-        let Self { pool_config } = match args.subcmd {
+        let Self { pool_config, plan } = match &args.subcmd {
             Subcmd::SyncPool(a) => a,
             _ => unreachable!(),
         };
+        let config = ConfigRaw::read_from_path(pool_config).unwrap();
+        Self::run_with_config(&args, config, SyncMode::from_plan_flag(*plan)).await;
+    }
 
-        let ConfigRaw {
-            pool,
-            manager,
-            manager_fee_account,
-            staker,
-            stake_deposit_auth,
-            stake_deposit_referral_fee,
-            sol_deposit_referral_fee,
-            epoch_fee,
-            stake_withdrawal_fee,
-            sol_withdrawal_fee,
-            stake_deposit_fee,
-            sol_deposit_fee,
-            sol_deposit_auth,
-            sol_withdraw_auth,
-            old_manager,
-            ..
-        } = ConfigRaw::read_from_path(pool_config).unwrap();
+    pub async fn run_with_config(args: &crate::Args, config: ConfigRaw, mode: SyncMode<'_>) {
+        let src = PlanSrc::SyncPool(config.clone());
 
         let rpc = args.config.nonblocking_rpc_client();
         let payer = args.config.signer();
+        let nonce = args.tx_nonce();
+        let luts = args.luts(&rpc).await;
 
-        let pool = PubkeySrc::parse(pool.as_ref().unwrap()).unwrap().pubkey();
+        let pool = PubkeySrc::parse(config.pool.as_ref().unwrap())
+            .unwrap()
+            .pubkey();
 
         let fetched_pool = rpc.get_account(&pool).await.unwrap();
         let program_id = fetched_pool.owner;
         let stake_pool: StakePool =
             StakePool::deserialize(&mut fetched_pool.data.as_slice()).unwrap();
 
-        ps!(old_manager, @fb payer.as_ref(), @sm args.send_mode);
+        let mut txs = SyncTxs::new(
+            args,
+            &rpc,
+            nonce.as_ref(),
+            &luts,
+            args.squads_proposer(&rpc).await,
+        );
+        let squads_vault = txs.squads.as_ref().map(|s| NullSigner::new(&s.vault));
+        let authority_fb = squads_vault
+            .as_ref()
+            .map_or(payer.as_ref(), |v| v as &dyn Signer);
+
+        let ConfigRaw {
+            manager,
+            old_manager,
+            ..
+        } = &config;
+        ps!(old_manager, @fb authority_fb, @sm mode.signer_send_mode(args.authority_send_mode()));
 
         if old_manager.pubkey() != stake_pool.manager {
             panic!(
@@ -70,79 +84,35 @@ impl SyncPoolArgs {
         }
 
         let new_manager = manager;
-        ps!(new_manager, @fb old_manager, @sm args.send_mode);
-
-        let [manager_fee_account, staker] = [
-            (manager_fee_account, stake_pool.manager_fee_account),
-            (staker, stake_pool.staker),
-        ]
-        .map(|(file_opt, stake_pool_val)| {
-            file_opt.map_or_else(
-                || stake_pool_val,
-                |s| PubkeySrc::parse(&s).unwrap().pubkey(),
-            )
-        });
-
-        let [sol_deposit_auth, sol_withdraw_auth, stake_deposit_auth] =
-            [sol_deposit_auth, sol_withdraw_auth, stake_deposit_auth]
-                .map(|string_opt| string_opt.map(|s| PubkeySrc::parse(&s).unwrap().pubkey()));
-
-        let [sol_deposit_referral_fee, stake_deposit_referral_fee] = [
-            (sol_deposit_referral_fee, stake_pool.sol_referral_fee),
-            (stake_deposit_referral_fee, stake_pool.stake_referral_fee),
-        ]
-        .map(|(opt, stake_pool_val)| opt.unwrap_or(stake_pool_val));
-
-        let [epoch_fee, stake_withdrawal_fee, sol_withdrawal_fee, stake_deposit_fee, sol_deposit_fee] =
-            [
-                (epoch_fee, &stake_pool.epoch_fee),
-                (stake_withdrawal_fee, &stake_pool.stake_withdrawal_fee),
-                (sol_withdrawal_fee, &stake_pool.sol_withdrawal_fee),
-                (stake_deposit_fee, &stake_pool.stake_deposit_fee),
-                (sol_deposit_fee, &stake_pool.sol_deposit_fee),
-            ]
-            .map(|(opt, stake_pool_val)| opt.unwrap_or(stake_pool_val.clone()));
-
-        let spc = SyncPoolConfig {
+        ps!(new_manager, @fb old_manager, @sm mode.signer_send_mode(args.authority_send_mode()));
+
+        let spc = SyncPoolConfig::from_config_raw(
+            &config,
             program_id,
             pool,
-            payer: payer.as_ref(),
-            manager: old_manager,
+            &stake_pool,
+            payer.as_ref(),
+            old_manager,
             new_manager,
-            staker,
-            manager_fee_account,
-            sol_deposit_auth,
-            stake_deposit_auth,
-            sol_withdraw_auth,
-            epoch_fee,
-            stake_deposit_referral_fee,
-            sol_deposit_referral_fee,
-            stake_withdrawal_fee,
-            sol_withdrawal_fee,
-            stake_deposit_fee,
-            sol_deposit_fee,
-        };
+        );
 
         let changeset = spc.changeset(&stake_pool);
-        for change in changeset.iter() {
-            eprintln!("{change}");
+        args.output.eprint_changes("pool-changes", &changeset);
+        if mode.handle_plan(|| {
+            let mut plan = Plan::new(&pool, &payer.pubkey(), src);
+            plan.changes = spc.plan_changes(&changeset);
+            plan.add_txs(changeset.len(), usize::MAX, &spc.signers_maybe_dup());
+            plan
+        }) {
+            return;
         }
         if changeset.is_empty() {
             eprintln!("No changes necessary");
             return;
         }
-        let sync_pool_ixs = spc.changeset_ixs(&changeset).unwrap();
-        let sync_pool_ixs = match args.send_mode {
-            TxSendMode::DumpMsg => sync_pool_ixs,
-            _ => {
-                with_auto_cb_ixs(&rpc, &payer.pubkey(), sync_pool_ixs, &[], args.fee_limit_cb).await
-            }
-        };
-        handle_tx_full(
-            &rpc,
-            args.send_mode,
-            &sync_pool_ixs,
-            &[],
+        txs.send_chunked(
+            &spc.changeset_ixs(&changeset).unwrap(),
+            usize::MAX,
             &mut spc.signers_maybe_dup(),
         )
         .await;
@@ -0,0 +1,153 @@
+use std::path::PathBuf;
+
+use borsh::BorshDeserialize;
+use clap::Args;
+use sanctum_solana_cli_utils::{PubkeySrc, TxSendMode};
+use solana_sdk::{rent::Rent, sysvar};
+use spl_stake_pool_interface::StakePool;
+
+use crate::{
+    pool_config::{
+        find_metaplex_metadata, ConfigRaw, OnchainTokenMetadata, Plan, PlanSrc, SyncMode,
+        SyncTokenMetadataConfig, TokenMetadataFields,
+    },
+    ps,
+    tx_utils::{handle_tx_full, with_auto_cb_ixs},
+};
+
+use super::Subcmd;
+
+#[derive(Args, Debug)]
+#[command(
+    long_about = "(Manager only) create or update the pool token's metadata with the name, symbol and uri of a pool config file"
+)]
+pub struct SyncTokenMetadataArgs {
+    #[arg(
+        help = r#"Path to pool config file with the `name`, `symbol` and `uri` fields to set the pool token's metadata to.
+Fields that are not set are left unchanged, all 3 are required if the metadata has not been created yet."#
+    )]
+    pub pool_config: PathBuf,
+
+    #[arg(
+        long,
+        help = "Output a toml plan of all changes to stdout instead of building and sending any transactions"
+    )]
+    pub plan: bool,
+}
+
+impl SyncTokenMetadataArgs {
+    pub async fn run(args: crate::Args) {
+        let Self { pool_config, plan } = match &args.subcmd {
+            Subcmd::SyncTokenMetadata(a) => a,
+            _ => unreachable!(),
+        };
+        let config = ConfigRaw::read_from_path(pool_config).unwrap();
+        Self::run_with_config(&args, config, SyncMode::from_plan_flag(*plan)).await;
+    }
+
+    pub async fn run_with_config(args: &crate::Args, config: ConfigRaw, mode: SyncMode<'_>) {
+        let src = PlanSrc::SyncTokenMetadata(config.clone());
+        let ConfigRaw {
+            pool,
+            manager,
+            name,
+            symbol,
+            uri,
+            ..
+        } = config;
+
+        let rpc = args.config.nonblocking_rpc_client();
+        let payer = args.config.signer();
+        let nonce = args.tx_nonce();
+        let luts = args.luts(&rpc).await;
+
+        let pool = PubkeySrc::parse(pool.as_ref().unwrap()).unwrap().pubkey();
+
+        let fetched_pool = rpc.get_account(&pool).await.unwrap();
+        let program_id = fetched_pool.owner;
+        let stake_pool: StakePool =
+            StakePool::deserialize(&mut fetched_pool.data.as_slice()).unwrap();
+
+        ps!(manager, @fb payer.as_ref(), @sm mode.signer_send_mode(args.send_mode));
+
+        if manager.pubkey() != stake_pool.manager {
+            panic!(
+                "Wrong manager. Expecting {}, got {}",
+                stake_pool.manager,
+                manager.pubkey()
+            );
+        }
+
+        let mint = stake_pool.pool_mint;
+        let (metaplex_metadata, _bump) = find_metaplex_metadata(&mint);
+        let mut fetched = rpc
+            .get_multiple_accounts(&[mint, metaplex_metadata, sysvar::rent::ID])
+            .await
+            .unwrap();
+        let rent = fetched.pop().unwrap().unwrap();
+        let rent: Rent = bincode::deserialize(&rent.data).unwrap();
+        let metaplex_metadata = fetched.pop().unwrap();
+        let mint_acc = fetched.pop().unwrap().unwrap();
+
+        let onchain =
+            OnchainTokenMetadata::from_fetched(&mint, &mint_acc, metaplex_metadata.as_ref())
+                .unwrap();
+
+        let fields = match onchain.fields() {
+            Some(TokenMetadataFields {
+                name: curr_name,
+                symbol: curr_symbol,
+                uri: curr_uri,
+            }) => TokenMetadataFields {
+                name: name.unwrap_or(curr_name),
+                symbol: symbol.unwrap_or(curr_symbol),
+                uri: uri.unwrap_or(curr_uri),
+            },
+            None => TokenMetadataFields {
+                name: name.expect("name required to create token metadata"),
+                symbol: symbol.expect("symbol required to create token metadata"),
+                uri: uri.expect("uri required to create token metadata"),
+            },
+        };
+
+        let stmc = SyncTokenMetadataConfig {
+            program_id,
+            pool,
+            mint,
+            payer: payer.as_ref(),
+            manager,
+            fields,
+            rent: &rent,
+        };
+
+        let changeset = stmc.changeset(&onchain);
+        args.output
+            .eprint_changes("token-metadata-changes", &changeset);
+        if mode.handle_plan(|| {
+            let mut plan = Plan::new(&pool, &payer.pubkey(), src);
+            plan.changes = stmc.plan_changes(&onchain, &changeset);
+            plan.add_txs(changeset.len(), usize::MAX, &stmc.signers_maybe_dup());
+            plan
+        }) {
+            return;
+        }
+        if changeset.is_empty() {
+            eprintln!("No changes necessary");
+            return;
+        }
+        let ixs = stmc.changeset_ixs(&onchain, &changeset).unwrap();
+        let ixs = match args.send_mode {
+            TxSendMode::DumpMsg => ixs,
+            _ => with_auto_cb_ixs(&rpc, &payer.pubkey(), ixs, &luts, args.fee_limit_cb).await,
+        };
+        handle_tx_full(
+            &rpc,
+            args.send_mode,
+            nonce.as_ref(),
+            &ixs,
+            &luts,
+            &mut stmc.signers_maybe_dup(),
+        )
+        .await;
+    }
+}
@@ -0,0 +1,227 @@
+//! Pool fetching, updating and tx sending shared by the sync subcommands
+
+use std::num::NonZeroU32;
+
+use borsh::BorshDeserialize;
+use sanctum_solana_cli_utils::TxSendMode;
+use sanctum_spl_stake_pool_lib::{FindValidatorStakeAccount, FindValidatorStakeAccountArgs};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_readonly_account::keyed::Keyed;
+use solana_sdk::{
+    account::Account, address_lookup_table::AddressLookupTableAccount, clock::Clock,
+    instruction::Instruction, pubkey::Pubkey, rent::Rent, signer::Signer,
+    stake::state::StakeStateV2, sysvar,
+};
+use spl_stake_pool_interface::{StakePool, ValidatorList, ValidatorStakeInfo};
+
+use crate::{
+    handle_authority_tx, max_add_validators_ix_per_tx, max_increase_validator_stake_ix_per_tx,
+    max_remove_validator_ixs_enum_per_tx, pool_lut_addresses, update_pool, SquadsProposer,
+    SyncDelegationConfig, TxNonce, UpdateCtrl, UpdatePoolArgs,
+};
+
+/// Pool state a sync starts from
+pub(crate) struct SyncState {
+    pub pool: Pubkey,
+    pub program_id: Pubkey,
+    pub stake_pool_acc: Account,
+    pub stake_pool: StakePool,
+    pub validators: Vec<ValidatorStakeInfo>,
+
+    /// Note: this is accountinfo.lamports and includes rent-exempt lamports
+    pub reserve_lamports: u64,
+
+    pub curr_epoch: u64,
+    pub rent: Rent,
+}
+
+impl SyncState {
+    pub async fn fetch(rpc: &RpcClient, pool: Pubkey) -> Self {
+        let mut fetched = rpc
+            .get_multiple_accounts(&[pool, sysvar::clock::ID, sysvar::rent::ID])
+            .await
+            .unwrap();
+        let rent = fetched.pop().unwrap().unwrap();
+        let clock = fetched.pop().unwrap().unwrap();
+        let stake_pool_acc = fetched.pop().unwrap().unwrap();
+        let program_id = stake_pool_acc.owner;
+
+        let rent: Rent = bincode::deserialize(&rent.data).unwrap();
+        let Clock {
+            epoch: curr_epoch, ..
+        } = bincode::deserialize(&clock.data).unwrap();
+        let stake_pool = StakePool::deserialize(&mut stake_pool_acc.data.as_slice()).unwrap();
+
+        let mut fetched = rpc
+            .get_multiple_accounts(&[stake_pool.validator_list, stake_pool.reserve_stake])
+            .await
+            .unwrap();
+        let reserve_acc = fetched.pop().unwrap().unwrap();
+        let validator_list_acc = fetched.pop().unwrap().unwrap();
+        let ValidatorList { validators, .. } =
+            ValidatorList::deserialize(&mut validator_list_acc.data.as_slice()).unwrap();
+
+        Self {
+            pool,
+            program_id,
+            stake_pool_acc,
+            stake_pool,
+            validators,
+            reserve_lamports: reserve_acc.lamports,
+            curr_epoch,
+            rent,
+        }
+    }
+
+    /// What a lookup table created by `sync-lut` for this pool would contain
+    pub fn pool_lut_addresses(&self) -> Vec<Pubkey> {
+        pool_lut_addresses(
+            &self.program_id,
+            &self.pool,
+            &self.stake_pool,
+            &self.validators,
+        )
+    }
+
+    pub fn is_updated_for_curr_epoch(&self) -> bool {
+        self.stake_pool.last_update_epoch >= self.curr_epoch
+    }
+
+    pub fn sync_delegation_config<'a>(
+        &self,
+        payer: &'a (dyn Signer + 'static),
+        staker: &'a (dyn Signer + 'static),
+    ) -> SyncDelegationConfig<'a> {
+        SyncDelegationConfig {
+            program_id: self.program_id,
+            payer,
+            staker,
+            pool: self.pool,
+            validator_list: self.stake_pool.validator_list,
+            reserve: self.stake_pool.reserve_stake,
+            reserve_lamports: self.reserve_lamports,
+            curr_epoch: self.curr_epoch,
+            rent: self.rent.clone(),
+        }
+    }
+
+    /// Used to check that validators to remove weren't already DeactivateDelinquent'd
+    pub async fn fetch_vsa_states<'a>(
+        &self,
+        rpc: &RpcClient,
+        validators: impl Iterator<Item = &'a ValidatorStakeInfo>,
+    ) -> Vec<StakeStateV2> {
+        let vsas: Vec<Pubkey> = validators
+            .map(|vsi| {
+                FindValidatorStakeAccount::new(FindValidatorStakeAccountArgs {
+                    pool: self.pool,
+                    vote: vsi.vote_account_address,
+                    seed: NonZeroU32::new(vsi.validator_seed_suffix),
+                })
+                .run_for_prog(&self.program_id)
+                .0
+            })
+            .collect();
+        rpc.get_multiple_accounts(&vsas)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|acc_opt| bincode::deserialize(&acc_opt.unwrap().data).unwrap())
+            .collect()
+    }
+
+    /// Validator list and delegation changes require the pool to be updated for the current epoch
+    pub async fn update_if_needed(&self, txs: &SyncTxs<'_>, payer: &(dyn Signer + 'static)) {
+        update_pool(UpdatePoolArgs {
+            rpc: txs.rpc,
+            send_mode: txs.send_mode,
+            nonce: txs.nonce,
+            luts: txs.luts,
+            payer,
+            program_id: self.program_id,
+            current_epoch: self.curr_epoch,
+            stake_pool: Keyed {
+                pubkey: self.pool,
+                account: &self.stake_pool_acc,
+            },
+            validator_list_entries: &self.validators,
+            fee_limit_cb: txs.fee_limit_cb,
+            ctrl: UpdateCtrl::IfNeeded,
+            no_merge: false,
+            resume: false,
+        })
+        .await;
+    }
+}
+
+/// Sends a sync's manager and staker txs, as squads proposals if `squads` is set
+pub(crate) struct SyncTxs<'a> {
+    pub rpc: &'a RpcClient,
+    pub send_mode: TxSendMode,
+    pub nonce: Option<&'a TxNonce>,
+    pub luts: &'a [AddressLookupTableAccount],
+    pub fee_limit_cb: u64,
+    pub squads: Option<SquadsProposer>,
+}
+
+impl<'a> SyncTxs<'a> {
+    pub fn new(
+        args: &crate::Args,
+        rpc: &'a RpcClient,
+        nonce: Option<&'a TxNonce>,
+        luts: &'a [AddressLookupTableAccount],
+        squads: Option<SquadsProposer>,
+    ) -> Self {
+        Self {
+            rpc,
+            send_mode: args.send_mode,
+            nonce,
+            luts,
+            fee_limit_cb: args.fee_limit_cb,
+            squads,
+        }
+    }
+
+    pub fn max_add_validators_ix_per_tx(&self) -> usize {
+        max_add_validators_ix_per_tx(self.luts, self.nonce, self.squads.as_ref())
+    }
+
+    pub fn max_remove_validator_ixs_enum_per_tx(&self) -> usize {
+        max_remove_validator_ixs_enum_per_tx(self.nonce, self.squads.as_ref())
+    }
+
+    /// Higher if the loaded luts cover all of `state`'s pool and validator accounts
+    pub fn max_increase_validator_stake_ix_per_tx(&self, state: &SyncState) -> usize {
+        max_increase_validator_stake_ix_per_tx(
+            self.luts,
+            &state.pool_lut_addresses(),
+            self.nonce,
+            self.squads.as_ref(),
+        )
+    }
+
+    /// Sends `ixs` in txs of up to `max_ixs_per_tx` ixs each.
+    /// Does nothing if `ixs` is empty.
+    ///
+    /// First signer in signers is transaction payer
+    pub async fn send_chunked(
+        &mut self,
+        ixs: &[Instruction],
+        max_ixs_per_tx: usize,
+        signers: &mut [&dyn Signer],
+    ) {
+        for chunk in ixs.chunks(max_ixs_per_tx) {
+            handle_authority_tx(
+                self.rpc,
+                self.send_mode,
+                self.nonce,
+                self.luts,
+                self.fee_limit_cb,
+                self.squads.as_mut(),
+                chunk.to_vec(),
+                signers,
+            )
+            .await;
+        }
+    }
+}
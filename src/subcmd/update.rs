@@ -5,9 +5,10 @@ use clap::{
     builder::{StringValueParser, TypedValueParser},
     Args,
 };
+use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_readonly_account::keyed::Keyed;
-use solana_sdk::{clock::Clock, pubkey::Pubkey, sysvar};
-use spl_stake_pool_interface::{StakePool, ValidatorList};
+use solana_sdk::{account::Account, clock::Clock, pubkey::Pubkey, sysvar};
+use spl_stake_pool_interface::{StakePool, ValidatorList, ValidatorStakeInfo};
 
 use crate::{
     update::{update_pool, UpdatePoolArgs},
@@ -36,6 +37,13 @@ pub struct UpdateArgs {
     )]
     pub no_merge: bool,
 
+    #[arg(
+        long,
+        help = "Skip validator list chunks that a previous interrupted run of this epoch's update recorded as landed.
+Chunks whose validators are not all updated for the epoch are always redone."
+    )]
+    pub resume: bool,
+
     #[arg(
         help = "Pubkey of the pool to update",
         value_parser = StringValueParser::new().try_map(|s| Pubkey::from_str(&s)),
@@ -45,37 +53,34 @@ pub struct UpdateArgs {
 
 impl UpdateArgs {
     pub async fn run(args: crate::Args) {
+        // args methods borrow all of args so they must be called before subcmd is moved out
+        let rpc = args.config.nonblocking_rpc_client();
+        let payer = args.config.signer();
+        let nonce = args.tx_nonce();
+        let luts = args.luts(&rpc).await;
+
         let Self {
             pool,
             ctrl,
             no_merge,
+            resume,
         } = match args.subcmd {
             Subcmd::Update(a) => a,
             _ => unreachable!(),
         };
 
-        let rpc = args.config.nonblocking_rpc_client();
-        let payer = args.config.signer();
-
-        let mut fetched = rpc
-            .get_multiple_accounts(&[pool, sysvar::clock::ID])
-            .await
-            .unwrap();
-        let clock = fetched.pop().unwrap().unwrap();
-        let stake_pool_acc = fetched.pop().unwrap().unwrap();
-
-        let program_id = stake_pool_acc.owner;
-        let Clock { epoch, .. } = bincode::deserialize(&clock.data).unwrap();
-        let stake_pool = StakePool::deserialize(&mut stake_pool_acc.data.as_slice()).unwrap();
-
-        let validator_list_acc = rpc.get_account(&stake_pool.validator_list).await.unwrap();
-
-        let ValidatorList { validators, .. } =
-            ValidatorList::deserialize(&mut validator_list_acc.data.as_slice()).unwrap();
+        let FetchedPool {
+            program_id,
+            epoch,
+            stake_pool_acc,
+            validators,
+        } = FetchedPool::fetch(&rpc, &pool).await;
 
         update_pool(UpdatePoolArgs {
             rpc: &rpc,
             send_mode: args.send_mode,
+            nonce: nonce.as_ref(),
+            luts: &luts,
             payer: payer.as_ref(),
             program_id,
             current_epoch: epoch,
@@ -87,7 +92,44 @@ impl UpdateArgs {
             fee_limit_cb: args.fee_limit_cb,
             ctrl,
             no_merge,
+            resume,
         })
         .await;
     }
 }
+
+/// Accounts required to run the update crank for a pool
+#[derive(Clone, Debug)]
+pub struct FetchedPool {
+    pub program_id: Pubkey,
+    pub epoch: u64,
+    pub stake_pool_acc: Account,
+    pub validators: Vec<ValidatorStakeInfo>,
+}
+
+impl FetchedPool {
+    pub async fn fetch(rpc: &RpcClient, pool: &Pubkey) -> Self {
+        let mut fetched = rpc
+            .get_multiple_accounts(&[*pool, sysvar::clock::ID])
+            .await
+            .unwrap();
+        let clock = fetched.pop().unwrap().unwrap();
+        let stake_pool_acc = fetched.pop().unwrap().unwrap();
+
+        let program_id = stake_pool_acc.owner;
+        let Clock { epoch, .. } = bincode::deserialize(&clock.data).unwrap();
+        let stake_pool = StakePool::deserialize(&mut stake_pool_acc.data.as_slice()).unwrap();
+
+        let validator_list_acc = rpc.get_account(&stake_pool.validator_list).await.unwrap();
+
+        let ValidatorList { validators, .. } =
+            ValidatorList::deserialize(&mut validator_list_acc.data.as_slice()).unwrap();
+
+        Self {
+            program_id,
+            epoch,
+            stake_pool_acc,
+            validators,
+        }
+    }
+}
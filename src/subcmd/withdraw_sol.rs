@@ -0,0 +1,253 @@
+use clap::{
+    builder::{StringValueParser, TypedValueParser},
+    Args,
+};
+use sanctum_associated_token_lib::FindAtaAddressArgs;
+use sanctum_solana_cli_utils::{
+    PubkeySrc, TokenAmt, TokenAmtOrAll, TokenAmtOrAllParser, TxSendMode,
+};
+use sanctum_spl_stake_pool_lib::FindWithdrawAuthority;
+use solana_readonly_account::keyed::Keyed;
+use solana_sdk::{clock::Clock, instruction::AccountMeta, stake, sysvar};
+use spl_stake_pool_interface::{
+    withdraw_sol_with_slippage_ix_with_program_id, StakePool, ValidatorList,
+    WithdrawSolWithSlippageIxArgs, WithdrawSolWithSlippageKeys,
+};
+
+use crate::{
+    handle_tx_full, ps, update_pool, with_auto_cb_ixs, PoolQuoter, SlippageArgs, Subcmd,
+    UpdateCtrl, UpdatePoolArgs,
+};
+
+#[derive(Args, Debug)]
+#[command(long_about = "Withdraws SOL from a stake pool's reserve, burning the LST in return")]
+pub struct WithdrawSolArgs {
+    #[arg(
+        long,
+        short,
+        help = "Token account authority of burn_from. Defaults to payer if not set."
+    )]
+    pub authority: Option<String>,
+
+    #[arg(
+        long,
+        short,
+        help = "Token account to burn and redeem pool tokens from. Defaults to authority's ATA if not set."
+    )]
+    pub tokens_burn_from: Option<String>,
+
+    #[arg(
+        long,
+        short,
+        help = "SOL account receiving the withdrawn SOL. Defaults to authority if not set."
+    )]
+    pub beneficiary: Option<String>,
+
+    #[arg(
+        long,
+        short,
+        help = "The stake pool's SOL withdraw authority. Only required if the pool has one set. Defaults to payer if not set."
+    )]
+    pub sol_withdraw_auth: Option<String>,
+
+    #[arg(
+        help = "The stake pool to withdraw SOL from. Either the stake pool's pubkey or keypair."
+    )]
+    pub pool: String,
+
+    #[arg(
+        help = "Amount of stake pool tokens to redeem. Also accepts 'all'.",
+        value_parser = StringValueParser::new().map(|s| TokenAmtOrAllParser::new(9).parse(&s).unwrap()),
+    )]
+    pub token_amt: TokenAmtOrAll,
+
+    #[command(flatten)]
+    pub slippage: SlippageArgs,
+}
+
+impl WithdrawSolArgs {
+    pub async fn run(args: crate::Args) {
+        // args methods borrow all of args so they must be called before subcmd is moved out
+        let rpc = args.config.nonblocking_rpc_client();
+        let payer = args.config.signer();
+        let nonce = args.tx_nonce();
+        let luts = args.luts(&rpc).await;
+
+        let Self {
+            authority,
+            tokens_burn_from,
+            beneficiary,
+            sol_withdraw_auth,
+            pool,
+            token_amt,
+            slippage,
+        } = match args.subcmd {
+            Subcmd::WithdrawSol(a) => a,
+            _ => unreachable!(),
+        };
+
+        ps!(authority, @fb payer.as_ref(), @sm args.send_mode);
+
+        let beneficiary = beneficiary.map_or_else(
+            || authority.pubkey(),
+            |b| PubkeySrc::parse(&b).unwrap().pubkey(),
+        );
+        let pool = PubkeySrc::parse(&pool).unwrap().pubkey();
+
+        let fetched_pool = rpc.get_account(&pool).await.unwrap();
+        let program_id = fetched_pool.owner;
+        let decoded_pool =
+            <StakePool as borsh::BorshDeserialize>::deserialize(&mut fetched_pool.data.as_ref())
+                .unwrap();
+
+        // only parsed and signed with if the pool has a SOL withdraw authority
+        let has_sol_withdraw_auth = decoded_pool.sol_withdraw_authority.is_some();
+        let sol_withdraw_auth = sol_withdraw_auth.filter(|_| has_sol_withdraw_auth);
+        ps!(sol_withdraw_auth, @fb payer.as_ref(), @sm args.send_mode);
+        if let Some(expected) = decoded_pool.sol_withdraw_authority {
+            if sol_withdraw_auth.pubkey() != expected {
+                panic!(
+                    "Wrong SOL withdraw authority. Expecting {expected}, got {}",
+                    sol_withdraw_auth.pubkey()
+                );
+            }
+        }
+
+        let burn_from = tokens_burn_from.map_or_else(
+            || {
+                FindAtaAddressArgs {
+                    wallet: authority.pubkey(),
+                    mint: decoded_pool.pool_mint,
+                    token_program: decoded_pool.token_program,
+                }
+                .find_ata_address()
+                .0
+            },
+            |b| PubkeySrc::parse(&b).unwrap().pubkey(),
+        );
+
+        let mut fetched = rpc
+            .get_multiple_accounts(&[burn_from, decoded_pool.validator_list, sysvar::clock::ID])
+            .await
+            .unwrap();
+
+        let clock = fetched.pop().unwrap().unwrap();
+        let Clock {
+            epoch: current_epoch,
+            ..
+        } = bincode::deserialize(&clock.data).unwrap();
+
+        let fetched_validator_list = fetched.pop().unwrap().unwrap();
+        let ValidatorList { validators, .. } =
+            <ValidatorList as borsh::BorshDeserialize>::deserialize(
+                &mut fetched_validator_list.data.as_slice(),
+            )
+            .unwrap();
+
+        let fetched_burn_from = fetched.pop().unwrap().unwrap();
+        let decoded_burn_from = spl_token_2022::extension::StateWithExtensions::<
+            spl_token_2022::state::Account,
+        >::unpack(&fetched_burn_from.data)
+        .unwrap()
+        .base;
+        let amt = match token_amt {
+            TokenAmtOrAll::All { .. } => decoded_burn_from.amount,
+            TokenAmtOrAll::Amt { amt, .. } => {
+                if amt > decoded_burn_from.amount {
+                    panic!(
+                        "Insufficient balance in burn_from. Requested {}, has {}",
+                        token_amt,
+                        TokenAmt {
+                            amt: decoded_burn_from.amount,
+                            decimals: 9
+                        }
+                    )
+                }
+                amt
+            }
+        };
+
+        update_pool(UpdatePoolArgs {
+            rpc: &rpc,
+            send_mode: args.send_mode,
+            nonce: nonce.as_ref(),
+            luts: &luts,
+            payer: payer.as_ref(),
+            program_id,
+            current_epoch,
+            stake_pool: Keyed {
+                pubkey: pool,
+                account: &fetched_pool,
+            },
+            validator_list_entries: &validators,
+            fee_limit_cb: args.fee_limit_cb,
+            ctrl: UpdateCtrl::IfNeeded,
+            no_merge: false,
+            resume: false,
+        })
+        .await;
+
+        // refetch to compute expected amount against the freshly updated pool
+        let fetched_pool = rpc.get_account(&pool).await.unwrap();
+        let decoded_pool =
+            <StakePool as borsh::BorshDeserialize>::deserialize(&mut fetched_pool.data.as_ref())
+                .unwrap();
+        let quote = PoolQuoter::new(&decoded_pool, current_epoch).withdraw_sol(amt, &burn_from);
+        let min_out = slippage.min_out(quote.lamports_out);
+
+        eprintln!(
+            "Redeeming {token_amt} tokens for SOL to {beneficiary}: {quote}. Min SOL out: {}",
+            TokenAmt {
+                amt: min_out,
+                decimals: 9
+            }
+        );
+        let mut ix = withdraw_sol_with_slippage_ix_with_program_id(
+            program_id,
+            WithdrawSolWithSlippageKeys {
+                stake_pool: pool,
+                withdraw_authority: FindWithdrawAuthority { pool }.run_for_prog(&program_id).0,
+                user_transfer_authority: authority.pubkey(),
+                burn_from,
+                reserve_stake: decoded_pool.reserve_stake,
+                lamports_to: beneficiary,
+                manager_fee_account: decoded_pool.manager_fee_account,
+                pool_mint: decoded_pool.pool_mint,
+                clock: sysvar::clock::ID,
+                stake_history: sysvar::stake_history::ID,
+                stake_program: stake::program::ID,
+                token_program: decoded_pool.token_program,
+            },
+            WithdrawSolWithSlippageIxArgs {
+                pool_tokens_in: amt,
+                min_lamports_out: min_out,
+            },
+        )
+        .unwrap();
+        if let Some(sol_withdraw_auth) = decoded_pool.sol_withdraw_authority {
+            ix.accounts.push(AccountMeta {
+                pubkey: sol_withdraw_auth,
+                is_signer: true,
+                is_writable: false,
+            });
+        }
+        let ixs = vec![ix];
+        let ixs = match args.send_mode {
+            TxSendMode::DumpMsg => ixs,
+            _ => with_auto_cb_ixs(&rpc, &payer.pubkey(), ixs, &luts, args.fee_limit_cb).await,
+        };
+        let mut signers = vec![payer.as_ref(), authority];
+        if has_sol_withdraw_auth {
+            signers.push(sol_withdraw_auth);
+        }
+        handle_tx_full(
+            &rpc,
+            args.send_mode,
+            nonce.as_ref(),
+            &ixs,
+            &luts,
+            &mut signers,
+        )
+        .await;
+    }
+}
@@ -1,3 +1,6 @@
+use std::num::NonZeroU32;
+
+use borsh::BorshDeserialize;
 use clap::{
     builder::{StringValueParser, TypedValueParser},
     Args,
@@ -7,7 +10,9 @@ use sanctum_associated_token_lib::FindAtaAddressArgs;
 use sanctum_solana_cli_utils::{
     PubkeySrc, TokenAmt, TokenAmtOrAll, TokenAmtOrAllParser, TxSendMode,
 };
-use sanctum_spl_stake_pool_lib::account_resolvers::WithdrawStakeWithSlippage;
+use sanctum_spl_stake_pool_lib::{
+    FindTransientStakeAccount, FindTransientStakeAccountArgs, FindValidatorStakeAccount,
+};
 use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_readonly_account::keyed::Keyed;
 use solana_sdk::{
@@ -16,15 +21,14 @@ use solana_sdk::{
     pubkey::Pubkey,
     rent::Rent,
     stake::{self, state::StakeStateV2},
-    system_instruction, sysvar,
-};
-use spl_stake_pool_interface::{
-    withdraw_stake_with_slippage_ix_with_program_id, StakePool, ValidatorList,
-    WithdrawStakeWithSlippageIxArgs,
+    sysvar,
 };
+use spl_stake_pool_interface::{StakePool, ValidatorList, ValidatorStakeInfo};
 
 use crate::{
-    handle_tx_full, ps, update_pool, with_auto_cb_ixs, Subcmd, UpdateCtrl, UpdatePoolArgs,
+    max_withdraw_stake_splits_per_tx, ps, try_handle_tx_full, update_pool, with_auto_cb_ixs,
+    PoolQuoter, SlippageArgs, Subcmd, UpdateCtrl, UpdatePoolArgs, ValidatorStakeAccs,
+    WithdrawStakeSplit, WithdrawStakeSplitConfig,
 };
 
 #[derive(Args, Debug)]
@@ -54,10 +58,17 @@ pub struct WithdrawStakeArgs {
     #[arg(
         long,
         short,
-        help = "Validator vote account of stake account to withdraw to. Defaults to the pool's largest validator if not set. Cannot be used if pool has preferred validator"
+        help = "Validator vote account of stake account to withdraw to. Defaults to the pool's preferred validator, or the validator with the most withdrawable stake, if not set. Cannot be used if pool has preferred validator"
     )]
     pub validator: Option<String>,
 
+    #[arg(
+        long,
+        help = "Split the withdrawal across as many of the pool's stake accounts as required, creating one stake account per split. Falls back to transient stake and the reserve once active stake is exhausted.",
+        conflicts_with = "validator"
+    )]
+    pub split: bool,
+
     #[arg(
         help = "The stake pool to withdraw stake from. Either the stake pool's pubkey or keypair."
     )]
@@ -68,25 +79,33 @@ pub struct WithdrawStakeArgs {
         value_parser = StringValueParser::new().map(|s| TokenAmtOrAllParser::new(9).parse(&s).unwrap()),
     )]
     pub token_amt: TokenAmtOrAll,
+
+    #[command(flatten)]
+    pub slippage: SlippageArgs,
 }
 
 impl WithdrawStakeArgs {
     pub async fn run(args: crate::Args) {
+        // args methods borrow all of args so they must be called before subcmd is moved out
+        let rpc = args.config.nonblocking_rpc_client();
+        let payer = args.config.signer();
+        let nonce = args.tx_nonce();
+        let luts = args.luts(&rpc).await;
+
         let Self {
             authority,
             tokens_burn_from,
             beneficiary,
             pool,
             validator,
+            split,
             token_amt,
+            slippage,
         } = match args.subcmd {
             Subcmd::WithdrawStake(a) => a,
             _ => unreachable!(),
         };
 
-        let rpc = args.config.nonblocking_rpc_client();
-        let payer = args.config.signer();
-
         ps!(authority, @fb payer.as_ref(), @sm args.send_mode);
 
         let beneficiary = beneficiary.map_or_else(
@@ -150,38 +169,21 @@ impl WithdrawStakeArgs {
             }
         };
 
-        // TODO: handle tsa and reserve edge cases
-        // TODO make sure validator has enough stake to service withdrawal
-        let vsi = match decoded_pool.preferred_withdraw_validator_vote_address {
-            Some(preferred) => {
-                if let Some(v) = validator {
-                    if v != preferred {
-                        panic!("Want to withdraw from validator {v} but stake pool's preferred is {preferred}");
-                    }
-                }
-                validators
-                    .iter()
-                    .find(|vsi| vsi.vote_account_address == preferred)
-                    .unwrap()
+        if let (Some(preferred), Some(v)) = (
+            decoded_pool.preferred_withdraw_validator_vote_address,
+            validator,
+        ) {
+            if v != preferred {
+                panic!(
+                    "Want to withdraw from validator {v} but stake pool's preferred is {preferred}"
+                );
             }
-            None => validator.map_or_else(
-                || {
-                    validators
-                        .iter()
-                        .max_by_key(|vsi| vsi.active_stake_lamports)
-                        .expect("No validators in pool")
-                },
-                |v| {
-                    validators
-                        .iter()
-                        .find(|vsi| vsi.vote_account_address == v)
-                        .unwrap_or_else(|| panic!("Validator {v} not part of pool"))
-                },
-            ),
-        };
-
-        let (split_to, seed) =
-            find_unused_stake_prog_create_with_seed(&rpc, &authority.pubkey()).await;
+        }
+        if let Some(v) = validator {
+            if !validators.iter().any(|vsi| vsi.vote_account_address == v) {
+                panic!("Validator {v} not part of pool");
+            }
+        }
 
         let mut fetched = rpc
             .get_multiple_accounts(&[sysvar::clock::ID, sysvar::rent::ID])
@@ -198,6 +200,8 @@ impl WithdrawStakeArgs {
         update_pool(UpdatePoolArgs {
             rpc: &rpc,
             send_mode: args.send_mode,
+            nonce: nonce.as_ref(),
+            luts: &luts,
             payer: payer.as_ref(),
             program_id,
             current_epoch,
@@ -209,58 +213,213 @@ impl WithdrawStakeArgs {
             fee_limit_cb: args.fee_limit_cb,
             ctrl: UpdateCtrl::IfNeeded,
             no_merge: false,
+            resume: false,
         })
         .await;
 
-        // TODO: calc expected amount after fees
-        eprintln!(
-            "Redeeming {} tokens for stake account staked to validator {}",
-            token_amt, vsi.vote_account_address
-        );
-        let resolve = WithdrawStakeWithSlippage {
-            pool: Keyed {
-                pubkey: pool,
-                account: &decoded_pool,
-            },
+        // refetch to compute expected amount against the freshly updated pool
+        let mut fetched = rpc
+            .get_multiple_accounts(&[
+                pool,
+                decoded_pool.validator_list,
+                decoded_pool.reserve_stake,
+            ])
+            .await
+            .unwrap();
+        let reserve_lamports = fetched.pop().unwrap().unwrap().lamports;
+        let fetched_validator_list = fetched.pop().unwrap().unwrap();
+        let ValidatorList { validators, .. } =
+            <ValidatorList as borsh::BorshDeserialize>::deserialize(
+                &mut fetched_validator_list.data.as_slice(),
+            )
+            .unwrap();
+        let fetched_pool = fetched.pop().unwrap().unwrap();
+        let decoded_pool =
+            <StakePool as borsh::BorshDeserialize>::deserialize(&mut fetched_pool.data.as_ref())
+                .unwrap();
+
+        let config = WithdrawStakeSplitConfig {
+            program_id,
+            pool,
+            stake_pool: &decoded_pool,
+            payer: payer.pubkey(),
+            authority: authority.pubkey(),
             burn_from,
-            transfer_authority: authority.pubkey(),
             beneficiary,
-            split_to,
+            reserve_lamports,
+            curr_epoch: current_epoch,
+            rent: &rent,
         };
-        let computed_keys = resolve.compute_keys_for_vsa(
-            &program_id,
-            vsi.vote_account_address,
-            vsi.validator_seed_suffix,
+        let splits = if split {
+            let (splits, shortfall) = config.splits(&validators, amt);
+            if shortfall > 0 {
+                panic!(
+                    "Pool does not have enough withdrawable stake. Short by {} pool tokens",
+                    TokenAmt {
+                        amt: shortfall,
+                        decimals: 9
+                    }
+                );
+            }
+            splits
+        } else {
+            let accs = fetch_validator_stake_accs(&rpc, program_id, pool, &validators).await;
+            let (src, available) = config.single_src(&accs, validator);
+            let quote =
+                PoolQuoter::new(&decoded_pool, current_epoch).withdraw_stake(amt, &burn_from);
+            if quote.lamports_out > available {
+                panic!(
+                    "Only {} SOL can be withdrawn from {src} but requested {quote}. Use --split to withdraw from multiple stake accounts",
+                    TokenAmt {
+                        amt: available,
+                        decimals: 9
+                    }
+                );
+            }
+            vec![WithdrawStakeSplit { src, quote }]
+        };
+
+        let expected_out: u64 = splits.iter().map(|s| s.quote.lamports_out).sum();
+        let min_out = slippage.min_out(expected_out);
+        eprintln!(
+            "Redeeming {token_amt} tokens for {} stake account(s). Min SOL out: {}",
+            splits.len(),
+            TokenAmt {
+                amt: min_out,
+                decimals: 9
+            },
         );
-        let mut signers = [payer.as_ref(), authority];
-        let ixs = vec![
-            system_instruction::create_account_with_seed(
-                &payer.pubkey(),
-                &split_to,
-                &authority.pubkey(),
-                &seed,
-                rent.minimum_balance(StakeStateV2::size_of()),
-                StakeStateV2::size_of() as u64,
-                &stake::program::ID,
-            ),
-            withdraw_stake_with_slippage_ix_with_program_id(
-                program_id,
-                resolve.resolve_with_computed_keys(computed_keys),
-                WithdrawStakeWithSlippageIxArgs {
-                    pool_tokens_in: amt,
-                    min_lamports_out: 0, // TODO: slippage
-                },
+
+        let mut split_tos: Vec<Pubkey> = Vec::with_capacity(splits.len());
+        let mut split_ixs = Vec::with_capacity(splits.len());
+        for split in splits.iter() {
+            let (split_to, seed) = loop {
+                let (split_to, seed) =
+                    find_unused_stake_prog_create_with_seed(&rpc, &authority.pubkey()).await;
+                if !split_tos.contains(&split_to) {
+                    break (split_to, seed);
+                }
+            };
+            // each split gets min_out proportional to its share of expected_out,
+            // nothing to protect if every split is quoted to return 0
+            //
+            // as-safety: result <= min_out
+            let split_min_out = (u128::from(min_out) * u128::from(split.quote.lamports_out))
+                .checked_div(u128::from(expected_out))
+                .map_or(0, |m| m as u64);
+            eprintln!("{}: {}", split.src, split.quote);
+            split_ixs.push(
+                config
+                    .split_ixs(split, split_to, &seed, split_min_out)
+                    .unwrap(),
+            );
+            split_tos.push(split_to);
+        }
+
+        let max_splits_per_tx = max_withdraw_stake_splits_per_tx(nonce.as_ref());
+        let n_txs = split_ixs.len().div_ceil(max_splits_per_tx);
+        if n_txs > 1 {
+            // each tx lands independently so a failed tx does not revert the ones before it
+            eprintln!(
+                "Splitting withdrawal into {n_txs} txs of up to {max_splits_per_tx} splits each"
+            );
+        }
+        for (i, chunk) in split_ixs.chunks(max_splits_per_tx).enumerate() {
+            let ixs: Vec<_> = chunk.iter().flatten().cloned().collect();
+            let ixs = match args.send_mode {
+                TxSendMode::DumpMsg => ixs,
+                _ => with_auto_cb_ixs(&rpc, &payer.pubkey(), ixs, &luts, args.fee_limit_cb).await,
+            };
+            let mut signers = [payer.as_ref(), authority];
+            if let Err(e) = try_handle_tx_full(
+                &rpc,
+                args.send_mode,
+                nonce.as_ref(),
+                &ixs,
+                &luts,
+                &mut signers,
             )
-            .unwrap(),
-        ];
-        let ixs = match args.send_mode {
-            TxSendMode::DumpMsg => ixs,
-            _ => with_auto_cb_ixs(&rpc, &payer.pubkey(), ixs, &[], args.fee_limit_cb).await,
-        };
-        handle_tx_full(&rpc, args.send_mode, &ixs, &[], &mut signers).await;
+            .await
+            {
+                let landed = i * max_splits_per_tx;
+                eprintln!(
+                    "Tx {} of {n_txs} failed. {landed} of {} splits landed:",
+                    i + 1,
+                    splits.len()
+                );
+                for (split, split_to) in splits.iter().zip(split_tos.iter()).take(landed) {
+                    eprintln!("{}: {} to {split_to}", split.src, split.quote);
+                }
+                panic!("{e}");
+            }
+        }
     }
 }
 
+/// getMultipleAccounts limit
+pub(crate) const MAX_ACCOUNTS_PER_FETCH: usize = 100;
+
+pub(crate) async fn fetch_validator_stake_accs<'a>(
+    rpc: &RpcClient,
+    program_id: Pubkey,
+    pool: Pubkey,
+    validators: &'a [ValidatorStakeInfo],
+) -> Vec<ValidatorStakeAccs<'a>> {
+    let stake_accs: Vec<Pubkey> = validators
+        .iter()
+        .flat_map(
+            |ValidatorStakeInfo {
+                 vote_account_address,
+                 transient_seed_suffix,
+                 validator_seed_suffix,
+                 ..
+             }| {
+                let (vsa_pubkey, _bump) = FindValidatorStakeAccount {
+                    pool,
+                    vote: *vote_account_address,
+                    seed: NonZeroU32::new(*validator_seed_suffix),
+                }
+                .run_for_prog(&program_id);
+                let (tsa_pubkey, _bump) =
+                    FindTransientStakeAccount::new(FindTransientStakeAccountArgs {
+                        pool,
+                        vote: *vote_account_address,
+                        seed: *transient_seed_suffix,
+                    })
+                    .run_for_prog(&program_id);
+                [vsa_pubkey, tsa_pubkey]
+            },
+        )
+        .collect();
+    let mut fetched = Vec::with_capacity(stake_accs.len());
+    for chunk in stake_accs.chunks(MAX_ACCOUNTS_PER_FETCH) {
+        fetched.extend(rpc.get_multiple_accounts(chunk).await.unwrap());
+    }
+    validators
+        .iter()
+        .zip(fetched.chunks(2))
+        .map(|(vsi, a)| {
+            let [vsa, tsa] = [&a[0], &a[1]].map(|acc| {
+                acc.as_ref()
+                    .filter(|a| a.owner == stake::program::ID) // donation mightve happened after merging
+                    .map(|a| {
+                        (
+                            a.lamports,
+                            StakeStateV2::deserialize(&mut a.data.as_slice()).unwrap(),
+                        )
+                    })
+            });
+            ValidatorStakeAccs {
+                vsi,
+                // vsa no longer exists for validators being removed
+                vsa,
+                // tsa might be in all kinds of states
+                tsa,
+            }
+        })
+        .collect()
+}
+
 async fn find_unused_stake_prog_create_with_seed(
     rpc: &RpcClient,
     authority: &Pubkey,
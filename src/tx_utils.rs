@@ -1,3 +1,4 @@
+use data_encoding::BASE64;
 use sanctum_solana_cli_utils::{
     HandleTxArgs, RecentBlockhash, TxSendMode, TxSendingNonblockingRpcClient,
 };
@@ -12,17 +13,136 @@ use solana_sdk::{
     hash::Hash,
     instruction::Instruction,
     message::{v0::Message, CompileError, VersionedMessage},
+    nonce,
     pubkey::Pubkey,
     signature::Signature,
     signer::Signer,
+    system_instruction,
     transaction::VersionedTransaction,
 };
 
-pub const MAX_ADD_VALIDATORS_IX_PER_TX: usize = 7;
+use crate::{has_pool_lut, has_srlut, SquadsProposer};
+
+/// For txs compiled against the SRLUT
+pub const MAX_ADD_VALIDATORS_IX_PER_TX: usize = 8;
+
+pub const MAX_ADD_VALIDATORS_IX_PER_TX_NO_SRLUT: usize = 7;
+
+/// For durable nonce txs compiled against the SRLUT.
+///
+/// Durable nonce txs are prefixed with an advance nonce ix
+/// and may need an additional signature from the nonce authority.
+pub const MAX_ADD_VALIDATORS_IX_PER_TX_NONCE: usize = 6;
+
+pub const MAX_ADD_VALIDATORS_IX_PER_TX_NO_SRLUT_NONCE: usize = 5;
+
+/// For squads proposals.
+///
+/// The proposed ixs are serialized into the vault transaction create ix's data
+/// without lookup tables, so the SRLUT makes no difference.
+pub const MAX_ADD_VALIDATORS_IX_PER_TX_SQUADS: usize = 5;
+
+pub const MAX_ADD_VALIDATORS_IX_PER_TX_SQUADS_NONCE: usize = 3;
 
 pub const MAX_REMOVE_VALIDATOR_IXS_ENUM_PER_TX: usize = 5;
 
-pub const MAX_INCREASE_VALIDATOR_STAKE_IX_PER_TX: usize = 3;
+pub const MAX_REMOVE_VALIDATOR_IXS_ENUM_PER_TX_NONCE: usize = 3;
+
+/// For squads proposals
+pub const MAX_REMOVE_VALIDATOR_IXS_ENUM_PER_TX_SQUADS: usize = 4;
+
+pub const MAX_REMOVE_VALIDATOR_IXS_ENUM_PER_TX_SQUADS_NONCE: usize = 2;
+
+/// For txs compiled against the SRLUT
+pub const MAX_INCREASE_VALIDATOR_STAKE_IX_PER_TX: usize = 4;
+
+pub const MAX_INCREASE_VALIDATOR_STAKE_IX_PER_TX_NO_SRLUT: usize = 3;
+
+/// For durable nonce txs compiled against the SRLUT
+pub const MAX_INCREASE_VALIDATOR_STAKE_IX_PER_TX_NONCE: usize = 3;
+
+pub const MAX_INCREASE_VALIDATOR_STAKE_IX_PER_TX_NO_SRLUT_NONCE: usize = 2;
+
+/// For txs compiled against a pool's lookup table, see [`has_pool_lut`].
+/// Only signers, programs and the ephemeral stake accounts are not looked up.
+pub const MAX_INCREASE_VALIDATOR_STAKE_IX_PER_TX_POOL_LUT: usize = 10;
+
+/// For durable nonce txs compiled against a pool's lookup table
+pub const MAX_INCREASE_VALIDATOR_STAKE_IX_PER_TX_POOL_LUT_NONCE: usize = 8;
+
+/// For squads proposals
+pub const MAX_INCREASE_VALIDATOR_STAKE_IX_PER_TX_SQUADS: usize = 2;
+
+pub const MAX_INCREASE_VALIDATOR_STAKE_IX_PER_TX_SQUADS_NONCE: usize = 1;
+
+/// Each split is a create_account_with_seed + withdraw_stake_with_slippage ix pair
+pub const MAX_WITHDRAW_STAKE_SPLITS_PER_TX: usize = 2;
+
+pub const MAX_WITHDRAW_STAKE_SPLITS_PER_TX_NONCE: usize = 1;
+
+pub fn max_add_validators_ix_per_tx(
+    luts: &[AddressLookupTableAccount],
+    nonce: Option<&TxNonce>,
+    squads: Option<&SquadsProposer>,
+) -> usize {
+    match (squads.is_some(), has_srlut(luts), nonce.is_some()) {
+        (true, _, false) => MAX_ADD_VALIDATORS_IX_PER_TX_SQUADS,
+        (true, _, true) => MAX_ADD_VALIDATORS_IX_PER_TX_SQUADS_NONCE,
+        (false, true, false) => MAX_ADD_VALIDATORS_IX_PER_TX,
+        (false, false, false) => MAX_ADD_VALIDATORS_IX_PER_TX_NO_SRLUT,
+        (false, true, true) => MAX_ADD_VALIDATORS_IX_PER_TX_NONCE,
+        (false, false, true) => MAX_ADD_VALIDATORS_IX_PER_TX_NO_SRLUT_NONCE,
+    }
+}
+
+pub fn max_remove_validator_ixs_enum_per_tx(
+    nonce: Option<&TxNonce>,
+    squads: Option<&SquadsProposer>,
+) -> usize {
+    match (squads.is_some(), nonce.is_some()) {
+        (true, false) => MAX_REMOVE_VALIDATOR_IXS_ENUM_PER_TX_SQUADS,
+        (true, true) => MAX_REMOVE_VALIDATOR_IXS_ENUM_PER_TX_SQUADS_NONCE,
+        (false, false) => MAX_REMOVE_VALIDATOR_IXS_ENUM_PER_TX,
+        (false, true) => MAX_REMOVE_VALIDATOR_IXS_ENUM_PER_TX_NONCE,
+    }
+}
+
+/// `pool_lut_addresses` are the [`crate::pool_lut_addresses`] of the validators
+/// whose stake is being changed
+pub fn max_increase_validator_stake_ix_per_tx(
+    luts: &[AddressLookupTableAccount],
+    pool_lut_addresses: &[Pubkey],
+    nonce: Option<&TxNonce>,
+    squads: Option<&SquadsProposer>,
+) -> usize {
+    if squads.is_some() {
+        return if nonce.is_some() {
+            MAX_INCREASE_VALIDATOR_STAKE_IX_PER_TX_SQUADS_NONCE
+        } else {
+            MAX_INCREASE_VALIDATOR_STAKE_IX_PER_TX_SQUADS
+        };
+    }
+    match (
+        has_pool_lut(luts, pool_lut_addresses),
+        has_srlut(luts),
+        nonce.is_some(),
+    ) {
+        (true, _, false) => MAX_INCREASE_VALIDATOR_STAKE_IX_PER_TX_POOL_LUT,
+        (true, _, true) => MAX_INCREASE_VALIDATOR_STAKE_IX_PER_TX_POOL_LUT_NONCE,
+        (false, true, false) => MAX_INCREASE_VALIDATOR_STAKE_IX_PER_TX,
+        (false, false, false) => MAX_INCREASE_VALIDATOR_STAKE_IX_PER_TX_NO_SRLUT,
+        (false, true, true) => MAX_INCREASE_VALIDATOR_STAKE_IX_PER_TX_NONCE,
+        (false, false, true) => MAX_INCREASE_VALIDATOR_STAKE_IX_PER_TX_NO_SRLUT_NONCE,
+    }
+}
+
+pub fn max_withdraw_stake_splits_per_tx(nonce: Option<&TxNonce>) -> usize {
+    if nonce.is_some() {
+        MAX_WITHDRAW_STAKE_SPLITS_PER_TX_NONCE
+    } else {
+        MAX_WITHDRAW_STAKE_SPLITS_PER_TX
+    }
+}
 
 const CU_BUFFER_RATIO: f64 = 1.1;
 
@@ -33,17 +153,30 @@ const BLOCK_CU_LIMIT: u32 = 60_000_000;
 pub async fn with_auto_cb_ixs(
     rpc: &RpcClient,
     payer_pk: &Pubkey,
-    mut ixs: Vec<Instruction>,
+    ixs: Vec<Instruction>,
     luts: &[AddressLookupTableAccount],
     fee_limit_cb_lamports: u64,
 ) -> Vec<Instruction> {
+    try_with_auto_cb_ixs(rpc, payer_pk, ixs, luts, fee_limit_cb_lamports)
+        .await
+        .unwrap()
+}
+
+/// [`with_auto_cb_ixs`] that returns simulation errors instead of panicking, for retrying
+pub async fn try_with_auto_cb_ixs(
+    rpc: &RpcClient,
+    payer_pk: &Pubkey,
+    mut ixs: Vec<Instruction>,
+    luts: &[AddressLookupTableAccount],
+    fee_limit_cb_lamports: u64,
+) -> Result<Vec<Instruction>, String> {
     if fee_limit_cb_lamports == 0 {
-        return ixs;
+        return Ok(ixs);
     }
     let tx_to_sim = to_est_cu_sim_tx(payer_pk, &ixs, luts).unwrap();
     let units_consumed = estimate_compute_unit_limit_nonblocking(rpc, &tx_to_sim)
         .await
-        .unwrap();
+        .map_err(|e| format!("{e:?}"))?;
     let units_consumed = buffer_compute_units(units_consumed, CU_BUFFER_RATIO)
         .saturating_add(CUS_REQUIRED_FOR_SET_CU_LIMIT_IXS);
     let microlamports_per_cu = calc_compute_unit_price(units_consumed, fee_limit_cb_lamports);
@@ -55,31 +188,181 @@ pub async fn with_auto_cb_ixs(
         0,
         ComputeBudgetInstruction::set_compute_unit_price(microlamports_per_cu),
     );
-    ixs
+    Ok(ixs)
 }
 
-/// First signer in signers is transaction payer
+/// Durable nonce account to use in place of a recent blockhash
+pub struct TxNonce {
+    pub nonce: Pubkey,
+    pub authority: Box<dyn Signer>,
+}
+
+impl std::fmt::Debug for TxNonce {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TxNonce")
+            .field("nonce", &self.nonce)
+            .field("authority", &self.authority.pubkey())
+            .finish()
+    }
+}
+
+impl TxNonce {
+    /// Fetches the nonce account and returns its stored durable nonce.
+    ///
+    /// Panics if the nonce account is not initialized or `self.authority` is not its authority
+    pub async fn fetch_hash(&self, rpc: &RpcClient) -> Hash {
+        let nonce_acc = rpc.get_account(&self.nonce).await.unwrap();
+        let versions: nonce::state::Versions = bincode::deserialize(&nonce_acc.data).unwrap();
+        let data = match versions.state() {
+            nonce::State::Initialized(data) => data,
+            nonce::State::Uninitialized => panic!("Nonce account {} not initialized", self.nonce),
+        };
+        if data.authority != self.authority.pubkey() {
+            panic!(
+                "Wrong nonce authority. Expecting {}, got {}",
+                data.authority,
+                self.authority.pubkey()
+            );
+        }
+        data.blockhash()
+    }
+}
+
+/// First signer in signers is transaction payer.
+///
+/// If `nonce` is set, the tx uses the nonce's stored hash instead of a recent blockhash,
+/// and is prefixed with an advance nonce instruction signed by the nonce authority.
 pub async fn handle_tx_full(
     rpc: &RpcClient,
     send_mode: TxSendMode,
+    nonce: Option<&TxNonce>,
     ixs: &[Instruction],
     luts: &[AddressLookupTableAccount],
     signers: &mut [&dyn Signer],
 ) {
+    try_handle_tx_full(rpc, send_mode, nonce, ixs, luts, signers)
+        .await
+        .unwrap()
+}
+
+/// [`handle_tx_full`] that returns rpc and tx errors instead of panicking, for retrying
+pub async fn try_handle_tx_full(
+    rpc: &RpcClient,
+    send_mode: TxSendMode,
+    nonce: Option<&TxNonce>,
+    ixs: &[Instruction],
+    luts: &[AddressLookupTableAccount],
+    signers: &mut [&dyn Signer],
+) -> Result<(), String> {
     let payer_pk = signers[0].pubkey();
+    let mut signers = signers.to_vec();
+    let (ixs, hash) = match nonce {
+        Some(nonce) => {
+            signers.push(nonce.authority.as_ref());
+            let ixs: Vec<Instruction> = core::iter::once(
+                system_instruction::advance_nonce_account(&nonce.nonce, &nonce.authority.pubkey()),
+            )
+            .chain(ixs.iter().cloned())
+            .collect();
+            (ixs, nonce.fetch_hash(rpc).await)
+        }
+        None => {
+            let RecentBlockhash { hash, .. } = rpc
+                .get_confirmed_blockhash()
+                .await
+                .map_err(|e| format!("{e:?}"))?;
+            (ixs.to_vec(), hash)
+        }
+    };
     signers.sort_by_key(|s| s.pubkey());
-    let RecentBlockhash { hash, .. } = rpc.get_confirmed_blockhash().await.unwrap();
     rpc.handle_tx(
         &VersionedTransaction::try_new(
-            VersionedMessage::V0(Message::try_compile(&payer_pk, ixs, luts, hash).unwrap()),
-            &SortedSigners(signers),
+            VersionedMessage::V0(Message::try_compile(&payer_pk, &ixs, luts, hash).unwrap()),
+            &SortedSigners(signers.as_slice()),
         )
         .unwrap(),
         send_mode,
         HandleTxArgs::cli_default(),
     )
     .await
-    .unwrap();
+    .map(|_| ())
+    .map_err(|e| format!("{e:?}"))
+}
+
+/// Sends `ixs` signed by `signers`, or if `squads` is set, creates a squads proposal for `ixs`
+/// signed and paid for by only the first signer, the multisig member.
+///
+/// First signer in signers is transaction payer
+pub async fn handle_authority_tx(
+    rpc: &RpcClient,
+    send_mode: TxSendMode,
+    nonce: Option<&TxNonce>,
+    luts: &[AddressLookupTableAccount],
+    fee_limit_cb: u64,
+    squads: Option<&mut SquadsProposer>,
+    ixs: Vec<Instruction>,
+    signers: &mut [&dyn Signer],
+) {
+    let payer = signers[0];
+    let mut member_only = [payer];
+    let (ixs, signers) = match squads {
+        Some(squads) => (
+            squads.proposal_ixs(&payer.pubkey(), &ixs),
+            member_only.as_mut_slice(),
+        ),
+        None => (ixs, signers),
+    };
+    let ixs = match send_mode {
+        TxSendMode::DumpMsg => ixs,
+        _ => with_auto_cb_ixs(rpc, &payer.pubkey(), ixs, luts, fee_limit_cb).await,
+    };
+    handle_tx_full(rpc, send_mode, nonce, &ixs, luts, signers).await;
+}
+
+/// Decodes a base64 encoded tx as output by dump-msg
+pub fn decode_b64_tx(s: &str) -> VersionedTransaction {
+    let bytes = BASE64.decode(s.trim().as_bytes()).unwrap();
+    bincode::deserialize(&bytes).unwrap()
+}
+
+pub fn encode_b64_tx(tx: &VersionedTransaction) -> String {
+    BASE64.encode(&bincode::serialize(tx).unwrap())
+}
+
+/// Combines the valid signatures of `txs` into a single tx.
+///
+/// Panics if `txs` is empty or not all of `txs` are of the same message
+pub fn combine_tx_signatures(
+    txs: impl IntoIterator<Item = VersionedTransaction>,
+) -> VersionedTransaction {
+    let mut txs = txs.into_iter();
+    let mut res = txs.next().expect("No transactions to combine");
+    let msg_bytes = res.message.serialize();
+    for tx in txs {
+        if tx.message.serialize() != msg_bytes {
+            panic!("Transactions are not all of the same message");
+        }
+        for ((sig, res_sig), signer) in tx
+            .signatures
+            .into_iter()
+            .zip(res.signatures.iter_mut())
+            .zip(res.message.static_account_keys())
+        {
+            if sig.verify(signer.as_ref(), &msg_bytes) {
+                *res_sig = sig;
+            }
+        }
+    }
+    res
+}
+
+/// Returns the required signers of `tx` whose signatures are missing or invalid
+pub fn missing_signers(tx: &VersionedTransaction) -> Vec<Pubkey> {
+    tx.verify_with_results()
+        .into_iter()
+        .zip(tx.message.static_account_keys())
+        .filter_map(|(is_valid, signer)| (!is_valid).then_some(*signer))
+        .collect()
 }
 
 /// Copied-pastad from sanctum-solana-utils but modified to simulate with CU limit = block limit
@@ -104,20 +387,31 @@ fn to_est_cu_sim_tx(
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashSet;
+    use std::collections::BTreeSet;
 
     use sanctum_solana_test_utils::assert_tx_with_cb_ixs_within_size_limits;
+    use sanctum_spl_stake_pool_lib::{
+        FindEphemeralStakeAccount, FindEphemeralStakeAccountArgs, ZERO_FEE,
+    };
     use solana_sdk::{
         rent::Rent,
         signature::Keypair,
+        signer::null_signer::NullSigner,
         stake::{
+            self,
             stake_flags::StakeFlags,
             state::{Delegation, Meta, Stake, StakeStateV2},
         },
     };
-    use spl_stake_pool_interface::{StakeStatus, ValidatorStakeInfo};
+    use spl_stake_pool_interface::{
+        AccountType, FutureEpochFee, Lockup, StakePool, StakeStatus, ValidatorStakeInfo,
+    };
 
-    use crate::{pool_config::SyncValidatorListConfig, SyncDelegationConfig};
+    use crate::{
+        find_squads_vault, mock_pool_lut, mock_srlut, pool_config::SyncValidatorListConfig,
+        squads_proposal_ixs, PoolQuoter, SyncDelegationConfig, WithdrawStakeSplit,
+        WithdrawStakeSplitConfig, WithdrawStakeSrc,
+    };
 
     use super::*;
 
@@ -135,17 +429,67 @@ mod tests {
         ))
     }
 
-    #[test]
-    fn check_max_add_validators_ix_per_tx_limit() {
-        let validators: HashSet<Pubkey> = (0..MAX_ADD_VALIDATORS_IX_PER_TX)
-            .map(|_| Pubkey::new_unique())
-            .collect();
+    /// Worst-case: nonce authority != payer, so 1 more signer
+    fn with_advance_nonce_ix(ixs: impl IntoIterator<Item = Instruction>) -> Vec<Instruction> {
+        core::iter::once(system_instruction::advance_nonce_account(
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+        ))
+        .chain(ixs)
+        .collect()
+    }
+
+    /// Returns the pool authority to sign the ixs with and,
+    /// if `squads`, the multisig whose vault is the authority
+    fn mock_authority(squads: bool) -> (Box<dyn Signer>, Option<Pubkey>) {
+        if squads {
+            let multisig = Pubkey::new_unique();
+            let (vault, _bump) = find_squads_vault(&multisig, 0);
+            (Box::new(NullSigner::new(&vault)), Some(multisig))
+        } else {
+            (Box::new(Keypair::new()), None)
+        }
+    }
+
+    /// If `squads_multisig` is set, `payer` is the multisig member proposing `ixs`
+    fn assert_within_size_limits(
+        payer: &Pubkey,
+        ixs: impl IntoIterator<Item = Instruction>,
+        luts: &[AddressLookupTableAccount],
+        nonce: bool,
+        squads_multisig: Option<Pubkey>,
+    ) {
+        let ixs: Vec<Instruction> = match squads_multisig {
+            Some(multisig) => {
+                let ixs: Vec<Instruction> = ixs.into_iter().collect();
+                // worst-case: longest u64 transaction index is still 8 bytes
+                squads_proposal_ixs(&multisig, 0, u64::MAX, payer, &ixs)
+                    .unwrap()
+                    .into()
+            }
+            None => ixs.into_iter().collect(),
+        };
+        let ixs: Vec<Instruction> = if nonce {
+            with_advance_nonce_ix(ixs)
+        } else {
+            ixs.into_iter().collect()
+        };
+        assert_tx_with_cb_ixs_within_size_limits(payer, ixs.into_iter(), luts);
+    }
+
+    fn check_add_validators_ix_per_tx_limit(
+        max: usize,
+        luts: &[AddressLookupTableAccount],
+        nonce: bool,
+        squads: bool,
+    ) {
+        let validators: BTreeSet<Pubkey> = (0..max).map(|_| Pubkey::new_unique()).collect();
         let payer = Keypair::new();
-        let staker = Keypair::new();
+        let (staker, squads_multisig) = mock_authority(squads);
         let svlc = SyncValidatorListConfig {
             program_id: Pubkey::new_unique(),
             payer: &payer,
-            staker: &staker,
+            staker: staker.as_ref(),
             pool: Pubkey::new_unique(),
             validator_list: Pubkey::new_unique(),
             reserve: Pubkey::new_unique(),
@@ -157,21 +501,82 @@ mod tests {
         };
         let (add, _remove) = svlc.add_remove_changeset(&[]);
         let ixs = svlc.add_validators_ixs(add).unwrap();
-        let mut iter = ixs.as_slice().chunks(MAX_ADD_VALIDATORS_IX_PER_TX);
+        let mut iter = ixs.as_slice().chunks(max);
         let add_validator_ix_chunk = iter.next().unwrap();
-        assert_eq!(add_validator_ix_chunk.len(), MAX_ADD_VALIDATORS_IX_PER_TX);
-        // size = 1231 WEW
-        assert_tx_with_cb_ixs_within_size_limits(
+        assert_eq!(add_validator_ix_chunk.len(), max);
+        assert_within_size_limits(
             &payer.pubkey(),
             add_validator_ix_chunk.iter().cloned(),
-            &[],
+            luts,
+            nonce,
+            squads_multisig,
         );
         assert!(iter.next().is_none());
     }
 
     #[test]
-    fn check_max_remove_validator_ixs_enum_per_tx_limit() {
-        let validators: Vec<ValidatorStakeInfo> = (0..MAX_REMOVE_VALIDATOR_IXS_ENUM_PER_TX)
+    fn check_max_add_validators_ix_per_tx_limit() {
+        // size = 1195
+        check_add_validators_ix_per_tx_limit(
+            MAX_ADD_VALIDATORS_IX_PER_TX,
+            &[mock_srlut()],
+            false,
+            false,
+        );
+    }
+
+    #[test]
+    fn check_max_add_validators_ix_per_tx_no_srlut_limit() {
+        // size = 1231 WEW
+        check_add_validators_ix_per_tx_limit(
+            MAX_ADD_VALIDATORS_IX_PER_TX_NO_SRLUT,
+            &[],
+            false,
+            false,
+        );
+    }
+
+    #[test]
+    fn check_max_add_validators_ix_per_tx_nonce_limit() {
+        // size = 1226
+        check_add_validators_ix_per_tx_limit(
+            MAX_ADD_VALIDATORS_IX_PER_TX_NONCE,
+            &[mock_srlut()],
+            true,
+            false,
+        );
+    }
+
+    #[test]
+    fn check_max_add_validators_ix_per_tx_no_srlut_nonce_limit() {
+        // size = 1231
+        check_add_validators_ix_per_tx_limit(
+            MAX_ADD_VALIDATORS_IX_PER_TX_NO_SRLUT_NONCE,
+            &[],
+            true,
+            false,
+        );
+    }
+
+    #[test]
+    fn check_max_add_validators_ix_per_tx_squads_limit() {
+        // size = 1217
+        check_add_validators_ix_per_tx_limit(MAX_ADD_VALIDATORS_IX_PER_TX_SQUADS, &[], false, true);
+    }
+
+    #[test]
+    fn check_max_add_validators_ix_per_tx_squads_nonce_limit() {
+        // size = 1215
+        check_add_validators_ix_per_tx_limit(
+            MAX_ADD_VALIDATORS_IX_PER_TX_SQUADS_NONCE,
+            &[],
+            true,
+            true,
+        );
+    }
+
+    fn check_remove_validator_ixs_enum_per_tx_limit(max: usize, nonce: bool, squads: bool) {
+        let validators: Vec<ValidatorStakeInfo> = (0..max)
             .map(|_| ValidatorStakeInfo {
                 // worst-case: all validators need to have stake removed
                 active_stake_lamports: 1_000_000_000,
@@ -186,15 +591,15 @@ mod tests {
             })
             .collect();
         let payer = Keypair::new();
-        let staker = Keypair::new();
+        let (staker, squads_multisig) = mock_authority(squads);
         let svlc = SyncValidatorListConfig {
             program_id: Pubkey::new_unique(),
             payer: &payer,
-            staker: &staker,
+            staker: staker.as_ref(),
             pool: Pubkey::new_unique(),
             validator_list: Pubkey::new_unique(),
             reserve: Pubkey::new_unique(),
-            validators: HashSet::new(),
+            validators: BTreeSet::new(),
             rent: &Rent::default(),
             // dont care
             preferred_deposit_validator: None,
@@ -204,14 +609,58 @@ mod tests {
         let ixs = svlc
             .remove_validators_ixs(remove.zip(mock_all_vsas_active_itr()))
             .unwrap();
-        assert_eq!(ixs.len(), MAX_REMOVE_VALIDATOR_IXS_ENUM_PER_TX * 2);
+        assert_eq!(ixs.len(), max * 2);
+        assert_within_size_limits(&payer.pubkey(), ixs, &[], nonce, squads_multisig);
+    }
+
+    #[test]
+    fn check_max_remove_validator_ixs_enum_per_tx_limit() {
         // size = 1184
-        assert_tx_with_cb_ixs_within_size_limits(&payer.pubkey(), ixs.into_iter(), &[]);
+        check_remove_validator_ixs_enum_per_tx_limit(
+            MAX_REMOVE_VALIDATOR_IXS_ENUM_PER_TX,
+            false,
+            false,
+        );
     }
 
     #[test]
-    fn check_max_increase_validator_stake_ixs_per_tx_limit() {
-        let validators: Vec<ValidatorStakeInfo> = (0..MAX_INCREASE_VALIDATOR_STAKE_IX_PER_TX)
+    fn check_max_remove_validator_ixs_enum_per_tx_nonce_limit() {
+        // size = 1122
+        check_remove_validator_ixs_enum_per_tx_limit(
+            MAX_REMOVE_VALIDATOR_IXS_ENUM_PER_TX_NONCE,
+            true,
+            false,
+        );
+    }
+
+    #[test]
+    fn check_max_remove_validator_ixs_enum_per_tx_squads_limit() {
+        // size = 1227
+        check_remove_validator_ixs_enum_per_tx_limit(
+            MAX_REMOVE_VALIDATOR_IXS_ENUM_PER_TX_SQUADS,
+            false,
+            true,
+        );
+    }
+
+    #[test]
+    fn check_max_remove_validator_ixs_enum_per_tx_squads_nonce_limit() {
+        // size = 1161
+        check_remove_validator_ixs_enum_per_tx_limit(
+            MAX_REMOVE_VALIDATOR_IXS_ENUM_PER_TX_SQUADS_NONCE,
+            true,
+            true,
+        );
+    }
+
+    fn check_increase_validator_stake_ixs_per_tx_limit(
+        max: usize,
+        luts: &[AddressLookupTableAccount],
+        nonce: bool,
+        squads: bool,
+        pool_lut: bool,
+    ) {
+        let validators: Vec<ValidatorStakeInfo> = (0..max)
             .map(|_| ValidatorStakeInfo {
                 // worst-case: all validators need to have stake increased
                 active_stake_lamports: 0,
@@ -226,11 +675,11 @@ mod tests {
             })
             .collect();
         let payer = Keypair::new();
-        let staker = Keypair::new();
+        let (staker, squads_multisig) = mock_authority(squads);
         let sdc = SyncDelegationConfig {
             program_id: Pubkey::new_unique(),
             payer: &payer,
-            staker: &staker,
+            staker: staker.as_ref(),
             pool: Pubkey::new_unique(),
             validator_list: Pubkey::new_unique(),
             reserve: Pubkey::new_unique(),
@@ -247,18 +696,271 @@ mod tests {
                 .map(|(vsi, (vsa, tsa, target))| (vsi, vsa, tsa, target)),
         );
         let ixs: Vec<_> = sdc.sync_delegation_ixs(cs).collect();
-        assert_eq!(ixs.len(), MAX_INCREASE_VALIDATOR_STAKE_IX_PER_TX);
+        assert_eq!(ixs.len(), max);
+        let mut luts = luts.to_vec();
+        if pool_lut {
+            // ephemeral stake accounts are derived per tx so a pool's lut never has them
+            let ephemerals: Vec<Pubkey> = (0..max as u64)
+                .map(|seed| {
+                    FindEphemeralStakeAccount::new(FindEphemeralStakeAccountArgs {
+                        pool: sdc.pool,
+                        seed,
+                    })
+                    .run_for_prog(&sdc.program_id)
+                    .0
+                })
+                .collect();
+            luts.push(mock_pool_lut(&ixs, &ephemerals));
+        }
         /*
         eprintln!(
             "{}",
             sanctum_solana_test_utils::tx_ser_size_with_cb_ixs(
                 &payer.pubkey(),
                 ixs.into_iter(),
-                &[]
+                luts
             )
         );
         */
+        assert_within_size_limits(&payer.pubkey(), ixs, &luts, nonce, squads_multisig);
+    }
+
+    #[test]
+    fn check_max_increase_validator_stake_ixs_per_tx_limit() {
+        // size = 1194
+        check_increase_validator_stake_ixs_per_tx_limit(
+            MAX_INCREASE_VALIDATOR_STAKE_IX_PER_TX,
+            &[mock_srlut()],
+            false,
+            false,
+            false,
+        );
+    }
+
+    #[test]
+    fn check_max_increase_validator_stake_ixs_per_tx_no_srlut_limit() {
+        // size = 1114
+        check_increase_validator_stake_ixs_per_tx_limit(
+            MAX_INCREASE_VALIDATOR_STAKE_IX_PER_TX_NO_SRLUT,
+            &[],
+            false,
+            false,
+            false,
+        );
+    }
+
+    #[test]
+    fn check_max_increase_validator_stake_ixs_per_tx_nonce_limit() {
+        // size = 1225
+        check_increase_validator_stake_ixs_per_tx_limit(
+            MAX_INCREASE_VALIDATOR_STAKE_IX_PER_TX_NONCE,
+            &[mock_srlut()],
+            true,
+            false,
+            false,
+        );
+    }
+
+    #[test]
+    fn check_max_increase_validator_stake_ixs_per_tx_no_srlut_nonce_limit() {
         // size = 1114
-        assert_tx_with_cb_ixs_within_size_limits(&payer.pubkey(), ixs.into_iter(), &[]);
+        check_increase_validator_stake_ixs_per_tx_limit(
+            MAX_INCREASE_VALIDATOR_STAKE_IX_PER_TX_NO_SRLUT_NONCE,
+            &[],
+            true,
+            false,
+            false,
+        );
+    }
+
+    #[test]
+    fn check_max_increase_validator_stake_ixs_per_tx_pool_lut_limit() {
+        // size = 1163
+        check_increase_validator_stake_ixs_per_tx_limit(
+            MAX_INCREASE_VALIDATOR_STAKE_IX_PER_TX_POOL_LUT,
+            &[mock_srlut()],
+            false,
+            false,
+            true,
+        );
+    }
+
+    #[test]
+    fn check_max_increase_validator_stake_ixs_per_tx_pool_lut_nonce_limit() {
+        // size = 1210
+        check_increase_validator_stake_ixs_per_tx_limit(
+            MAX_INCREASE_VALIDATOR_STAKE_IX_PER_TX_POOL_LUT_NONCE,
+            &[mock_srlut()],
+            true,
+            false,
+            true,
+        );
+    }
+
+    #[test]
+    fn check_max_increase_validator_stake_ixs_per_tx_squads_limit() {
+        // size = 1097
+        check_increase_validator_stake_ixs_per_tx_limit(
+            MAX_INCREASE_VALIDATOR_STAKE_IX_PER_TX_SQUADS,
+            &[],
+            false,
+            true,
+            false,
+        );
+    }
+
+    #[test]
+    fn check_max_increase_validator_stake_ixs_per_tx_squads_nonce_limit() {
+        // size = 1096
+        check_increase_validator_stake_ixs_per_tx_limit(
+            MAX_INCREASE_VALIDATOR_STAKE_IX_PER_TX_SQUADS_NONCE,
+            &[],
+            true,
+            true,
+            false,
+        );
+    }
+
+    fn check_withdraw_stake_splits_per_tx_limit(max: usize, nonce: bool) {
+        let sp = StakePool {
+            total_lamports: 1_000_000_000_000,
+            pool_token_supply: 1_000_000_000_000,
+            // dont cares:
+            validator_list: Pubkey::new_unique(),
+            reserve_stake: Pubkey::new_unique(),
+            account_type: AccountType::StakePool,
+            manager: Pubkey::new_unique(),
+            staker: Pubkey::new_unique(),
+            stake_deposit_authority: Pubkey::new_unique(),
+            manager_fee_account: Pubkey::new_unique(),
+            epoch_fee: ZERO_FEE,
+            next_epoch_fee: FutureEpochFee::None,
+            stake_deposit_fee: ZERO_FEE,
+            stake_withdrawal_fee: ZERO_FEE,
+            next_stake_withdrawal_fee: FutureEpochFee::None,
+            stake_referral_fee: 0,
+            sol_deposit_authority: None,
+            sol_deposit_fee: ZERO_FEE,
+            sol_referral_fee: 0,
+            sol_withdraw_authority: None,
+            sol_withdrawal_fee: ZERO_FEE,
+            next_sol_withdrawal_fee: FutureEpochFee::None,
+            token_program: Pubkey::new_unique(),
+            pool_mint: Pubkey::new_unique(),
+            preferred_deposit_validator_vote_address: None,
+            preferred_withdraw_validator_vote_address: None,
+            lockup: Lockup {
+                unix_timestamp: 0,
+                epoch: 0,
+                custodian: Pubkey::default(),
+            },
+            last_update_epoch: 0,
+            stake_withdraw_bump_seed: 255,
+            last_epoch_pool_token_supply: 0,
+            last_epoch_total_lamports: 0,
+        };
+        let payer = Keypair::new();
+        // worst-case: authority != payer, so 2 signers
+        let authority = Keypair::new();
+        let config = WithdrawStakeSplitConfig {
+            program_id: Pubkey::new_unique(),
+            pool: Pubkey::new_unique(),
+            stake_pool: &sp,
+            payer: payer.pubkey(),
+            authority: authority.pubkey(),
+            burn_from: Pubkey::new_unique(),
+            beneficiary: Pubkey::new_unique(),
+            reserve_lamports: 0,
+            curr_epoch: 0,
+            rent: &Rent::default(),
+        };
+        let quoter = PoolQuoter::new(&sp, 0);
+        let ixs: Vec<Instruction> = (0..max)
+            .flat_map(|_| {
+                let split = WithdrawStakeSplit {
+                    src: WithdrawStakeSrc::Validator(ValidatorStakeInfo {
+                        vote_account_address: Pubkey::new_unique(),
+                        status: StakeStatus::Active,
+                        // dont care
+                        active_stake_lamports: 0,
+                        transient_stake_lamports: 0,
+                        last_update_epoch: 0,
+                        transient_seed_suffix: 0,
+                        unused: 0,
+                        validator_seed_suffix: 0,
+                    }),
+                    quote: quoter.withdraw_stake(u64::MAX, &config.burn_from),
+                };
+                // worst-case: longest u32 seed
+                let seed = u32::MAX.to_string();
+                let split_to =
+                    Pubkey::create_with_seed(&authority.pubkey(), &seed, &stake::program::ID)
+                        .unwrap();
+                config.split_ixs(&split, split_to, &seed, u64::MAX).unwrap()
+            })
+            .collect();
+        assert_eq!(ixs.len(), max * 2);
+        assert_within_size_limits(&payer.pubkey(), ixs, &[], nonce, None);
+    }
+
+    #[test]
+    fn check_max_withdraw_stake_splits_per_tx_limit() {
+        // size = 1078
+        check_withdraw_stake_splits_per_tx_limit(MAX_WITHDRAW_STAKE_SPLITS_PER_TX, false);
+    }
+
+    #[test]
+    fn check_max_withdraw_stake_splits_per_tx_nonce_limit() {
+        // size = 1043
+        check_withdraw_stake_splits_per_tx_limit(MAX_WITHDRAW_STAKE_SPLITS_PER_TX_NONCE, true);
+    }
+
+    fn mock_two_signer_tx(a: &Pubkey, b: &Pubkey) -> VersionedTransaction {
+        let ix = Instruction {
+            program_id: Pubkey::new_unique(),
+            accounts: vec![
+                solana_sdk::instruction::AccountMeta::new(*a, true),
+                solana_sdk::instruction::AccountMeta::new_readonly(*b, true),
+            ],
+            data: vec![],
+        };
+        let message =
+            VersionedMessage::V0(Message::try_compile(a, &[ix], &[], Hash::new_unique()).unwrap());
+        VersionedTransaction {
+            signatures: vec![Signature::default(); 2],
+            message,
+        }
+    }
+
+    #[test]
+    fn combine_partially_signed_txs() {
+        let [a, b] = [Keypair::new(), Keypair::new()];
+        let unsigned = mock_two_signer_tx(&a.pubkey(), &b.pubkey());
+        let msg_bytes = unsigned.message.serialize();
+
+        let mut signed_by_a = unsigned.clone();
+        signed_by_a.signatures[0] = a.sign_message(&msg_bytes);
+        let mut signed_by_b = unsigned.clone();
+        signed_by_b.signatures[1] = b.sign_message(&msg_bytes);
+
+        assert_eq!(missing_signers(&signed_by_a).as_slice(), &[b.pubkey()]);
+
+        // b's sig in the wrong slot should be ignored
+        let mut wrong_slot = unsigned.clone();
+        wrong_slot.signatures[0] = b.sign_message(&msg_bytes);
+
+        let combined = combine_tx_signatures([
+            decode_b64_tx(&encode_b64_tx(&signed_by_a)),
+            wrong_slot,
+            signed_by_b,
+        ]);
+        assert!(missing_signers(&combined).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Transactions are not all of the same message")]
+    fn combine_different_messages_fails() {
+        let [a, b] = [Pubkey::new_unique(), Pubkey::new_unique()];
+        combine_tx_signatures([mock_two_signer_tx(&a, &b), mock_two_signer_tx(&a, &b)]);
     }
 }
@@ -1,23 +1,50 @@
-use std::fmt::Display;
+use std::{fmt::Display, sync::Mutex};
 
 use borsh::BorshDeserialize;
 use clap::ValueEnum;
+use futures::{stream, StreamExt};
 use sanctum_solana_cli_utils::TxSendMode;
 use sanctum_spl_stake_pool_lib::account_resolvers::{
     CleanupRemovedValidatorEntries, UpdateStakePoolBalance, UpdateValidatorListBalance,
 };
 use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_readonly_account::keyed::Keyed;
-use solana_sdk::{account::Account, pubkey::Pubkey, signer::Signer};
+use solana_sdk::{
+    account::Account, address_lookup_table::AddressLookupTableAccount, instruction::Instruction,
+    pubkey::Pubkey, signer::Signer,
+};
 use spl_stake_pool_interface::{
     cleanup_removed_validator_entries_ix_with_program_id,
     update_stake_pool_balance_ix_with_program_id, StakePool, UpdateValidatorListBalanceIxArgs,
     ValidatorStakeInfo,
 };
 
-use crate::tx_utils::{handle_tx_full, with_auto_cb_ixs};
+use crate::{
+    has_pool_lut, has_srlut, pool_lut_addresses,
+    tx_utils::{
+        handle_tx_full, try_handle_tx_full, try_with_auto_cb_ixs, with_auto_cb_ixs, TxNonce,
+    },
+    update_journal::UpdateJournal,
+};
+
+/// For txs compiled against the SRLUT
+const MAX_VALIDATORS_TO_UPDATE_PER_TX: usize = 12;
+
+const MAX_VALIDATORS_TO_UPDATE_PER_TX_NO_SRLUT: usize = 11;
+
+/// For durable nonce txs compiled against the SRLUT
+const MAX_VALIDATORS_TO_UPDATE_PER_TX_NONCE: usize = 9;
+
+const MAX_VALIDATORS_TO_UPDATE_PER_TX_NO_SRLUT_NONCE: usize = 8;
 
-const MAX_VALIDATORS_TO_UPDATE_PER_TX: usize = 11;
+/// For txs compiled against a pool's lookup table, see [`has_pool_lut`].
+/// Tx size and account locks would allow more (see tests),
+/// this leaves headroom for the compute of merging every validator's transient stake.
+const MAX_VALIDATORS_TO_UPDATE_PER_TX_POOL_LUT: usize = 20;
+
+const MAX_CONCURRENT_UPDATE_VALIDATOR_LIST_TXS: usize = 8;
+
+const MAX_UPDATE_VALIDATOR_LIST_RETRIES: usize = 3;
 
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
 pub enum UpdateCtrl {
@@ -45,6 +72,8 @@ impl Display for UpdateCtrl {
 pub struct UpdatePoolArgs<'a> {
     pub rpc: &'a RpcClient,
     pub send_mode: TxSendMode,
+    pub nonce: Option<&'a TxNonce>,
+    pub luts: &'a [AddressLookupTableAccount],
     pub payer: &'a (dyn Signer + 'static),
     pub program_id: Pubkey,
     pub current_epoch: u64,
@@ -53,13 +82,40 @@ pub struct UpdatePoolArgs<'a> {
     pub fee_limit_cb: u64,
     pub ctrl: UpdateCtrl,
     pub no_merge: bool,
+
+    /// Skip validator list chunks recorded as landed in the [`UpdateJournal`] of a previous run
+    pub resume: bool,
+}
+
+async fn try_send_update_validator_list_tx(
+    rpc: &RpcClient,
+    send_mode: TxSendMode,
+    nonce: Option<&TxNonce>,
+    luts: &[AddressLookupTableAccount],
+    payer: &dyn Signer,
+    fee_limit_cb: u64,
+    ixs: Vec<Instruction>,
+) -> Result<(), String> {
+    let ixs = match send_mode {
+        TxSendMode::DumpMsg => ixs,
+        _ => try_with_auto_cb_ixs(rpc, &payer.pubkey(), ixs, luts, fee_limit_cb).await?,
+    };
+    try_handle_tx_full(rpc, send_mode, nonce, &ixs, luts, &mut [payer]).await
 }
 
-// ignores entries already updated for this epoch
+/// Ignores entries already updated for this epoch.
+///
+/// Validator list update txs are sent concurrently and failed ones retried.
+/// The pool is only updated after the entire validator list has been.
+///
+/// In send-actual mode, landed validator list update txs are recorded in an [`UpdateJournal`]
+/// that is deleted once the pool has been updated.
 pub async fn update_pool(
     UpdatePoolArgs {
         rpc,
         send_mode,
+        nonce,
+        luts,
         payer,
         program_id,
         current_epoch,
@@ -68,6 +124,7 @@ pub async fn update_pool(
         fee_limit_cb,
         ctrl,
         no_merge,
+        resume,
     }: UpdatePoolArgs<'_>,
 ) {
     let sp = StakePool::deserialize(&mut stake_pool.account.data.as_slice()).unwrap();
@@ -78,45 +135,126 @@ pub async fn update_pool(
     }
     eprintln!("Updating pool");
 
+    let is_journaled = matches!(send_mode, TxSendMode::SendActual);
+    let journal_path = UpdateJournal::default_path(&stake_pool.pubkey, current_epoch);
+
     // Update validator list:
     if !is_updated_for_curr_epoch || ctrl == UpdateCtrl::ForceAll {
         let uvlb = UpdateValidatorListBalance { stake_pool };
-        // just do the validator list sequentially
-        for (i, chunk) in validator_list_entries
-            .chunks(MAX_VALIDATORS_TO_UPDATE_PER_TX)
+        let pool_lut = has_pool_lut(
+            luts,
+            &pool_lut_addresses(&program_id, &stake_pool.pubkey, &sp, validator_list_entries),
+        );
+        let max_validators_per_tx = match (pool_lut, has_srlut(luts), nonce.is_some()) {
+            (true, _, _) => MAX_VALIDATORS_TO_UPDATE_PER_TX_POOL_LUT,
+            (false, true, false) => MAX_VALIDATORS_TO_UPDATE_PER_TX,
+            (false, false, false) => MAX_VALIDATORS_TO_UPDATE_PER_TX_NO_SRLUT,
+            (false, true, true) => MAX_VALIDATORS_TO_UPDATE_PER_TX_NONCE,
+            (false, false, true) => MAX_VALIDATORS_TO_UPDATE_PER_TX_NO_SRLUT_NONCE,
+        };
+        let journal = if resume {
+            UpdateJournal::load_or_new(
+                &journal_path,
+                &stake_pool.pubkey,
+                current_epoch,
+                max_validators_per_tx,
+            )
+        } else {
+            UpdateJournal::new(&stake_pool.pubkey, current_epoch, max_validators_per_tx)
+        };
+        let chunk_ixs: Vec<(usize, usize, Vec<Instruction>)> = validator_list_entries
+            .chunks(max_validators_per_tx)
             .enumerate()
-        {
-            if chunk
-                .iter()
-                .all(|vsi| vsi.last_update_epoch >= current_epoch)
-                && ctrl != UpdateCtrl::ForceAll
-            {
-                continue;
+            .filter(|(i, chunk)| {
+                // always recheck last_update_epoch so that chunks that were journaled as landed
+                // but have since changed e.g. removals by another cranker are redone
+                let is_chunk_updated = chunk
+                    .iter()
+                    .all(|vsi| vsi.last_update_epoch >= current_epoch);
+                match ctrl {
+                    UpdateCtrl::ForceAll => {
+                        !(is_chunk_updated && journal.is_landed(i * max_validators_per_tx))
+                    }
+                    UpdateCtrl::IfNeeded | UpdateCtrl::ForcePool => !is_chunk_updated,
+                }
+            })
+            .map(|(i, chunk)| {
+                let start_index = i * max_validators_per_tx;
+                let ixs = vec![uvlb
+                    .full_ix_from_validator_slice(
+                        program_id,
+                        chunk,
+                        UpdateValidatorListBalanceIxArgs {
+                            start_index: start_index.try_into().unwrap(),
+                            no_merge,
+                        },
+                    )
+                    .unwrap()];
+                (start_index, start_index + chunk.len(), ixs)
+            })
+            .collect();
+        // all txs use the same durable nonce, so only 1 can land per advance
+        let max_concurrent = if nonce.is_some() {
+            1
+        } else {
+            MAX_CONCURRENT_UPDATE_VALIDATOR_LIST_TXS
+        };
+        let skipped =
+            validator_list_entries.len().div_ceil(max_validators_per_tx) - chunk_ixs.len();
+        if skipped > 0 {
+            eprintln!("Skipping {skipped} already updated validator list chunks");
+        }
+        let journal = Mutex::new(journal);
+        let journal = &journal;
+        let journal_path = journal_path.as_path();
+        let mut pending = chunk_ixs;
+        for attempt in 0..=MAX_UPDATE_VALIDATOR_LIST_RETRIES {
+            if pending.is_empty() {
+                break;
             }
-            let start_index = i * MAX_VALIDATORS_TO_UPDATE_PER_TX;
-            let ixs = vec![uvlb
-                .full_ix_from_validator_slice(
-                    program_id,
-                    chunk,
-                    UpdateValidatorListBalanceIxArgs {
-                        start_index: start_index.try_into().unwrap(),
-                        no_merge,
-                    },
-                )
-                .unwrap()];
-            let ixs = match send_mode {
-                TxSendMode::DumpMsg => ixs,
-                _ => with_auto_cb_ixs(rpc, &payer.pubkey(), ixs, &[], fee_limit_cb).await,
-            };
-            eprintln!(
-                "Updating validator list [{}..{}]",
-                start_index,
-                std::cmp::min(
-                    start_index + MAX_VALIDATORS_TO_UPDATE_PER_TX,
-                    validator_list_entries.len()
-                )
+            if attempt > 0 {
+                eprintln!(
+                    "Retrying {} failed validator list update txs, attempt {attempt}/{MAX_UPDATE_VALIDATOR_LIST_RETRIES}",
+                    pending.len()
+                );
+            }
+            let results: Vec<_> = stream::iter(pending)
+                .map(|(start, end, ixs)| async move {
+                    eprintln!("Updating validator list [{start}..{end}]");
+                    let res = try_send_update_validator_list_tx(
+                        rpc,
+                        send_mode,
+                        nonce,
+                        luts,
+                        payer,
+                        fee_limit_cb,
+                        ixs.clone(),
+                    )
+                    .await;
+                    if res.is_ok() && is_journaled {
+                        journal.lock().unwrap().record_landed(journal_path, start);
+                    }
+                    (start, end, ixs, res)
+                })
+                .buffer_unordered(max_concurrent)
+                .collect()
+                .await;
+            pending = results
+                .into_iter()
+                .filter_map(|(start, end, ixs, res)| match res {
+                    Ok(()) => None,
+                    Err(e) => {
+                        eprintln!("Updating validator list [{start}..{end}] failed: {e}");
+                        Some((start, end, ixs))
+                    }
+                })
+                .collect();
+        }
+        if !pending.is_empty() {
+            panic!(
+                "{} validator list update txs still failing after {MAX_UPDATE_VALIDATOR_LIST_RETRIES} retries",
+                pending.len()
             );
-            handle_tx_full(rpc, send_mode, &ixs, &[], &mut [payer]).await;
         }
     }
 
@@ -139,10 +277,13 @@ pub async fn update_pool(
     ];
     let final_ixs = match send_mode {
         TxSendMode::DumpMsg => final_ixs,
-        _ => with_auto_cb_ixs(rpc, &payer.pubkey(), final_ixs, &[], fee_limit_cb).await,
+        _ => with_auto_cb_ixs(rpc, &payer.pubkey(), final_ixs, luts, fee_limit_cb).await,
     };
     eprintln!("Sending final update tx");
-    handle_tx_full(rpc, send_mode, &final_ixs, &[], &mut [payer]).await;
+    handle_tx_full(rpc, send_mode, nonce, &final_ixs, luts, &mut [payer]).await;
+    if is_journaled {
+        UpdateJournal::remove(&journal_path);
+    }
 }
 
 #[cfg(test)]
@@ -151,12 +292,14 @@ mod tests {
     use sanctum_solana_test_utils::assert_tx_with_cb_ixs_within_size_limits;
     use sanctum_spl_stake_pool_lib::{account_resolvers::UpdateValidatorListBalance, ZERO_FEE};
     use solana_readonly_account::ReadonlyAccountData;
-    use solana_sdk::pubkey::Pubkey;
+    use solana_sdk::{pubkey::Pubkey, system_instruction};
     use spl_stake_pool_interface::{
         AccountType, FutureEpochFee, Lockup, StakePool, StakeStatus,
         UpdateValidatorListBalanceIxArgs,
     };
 
+    use crate::{mock_pool_lut, mock_srlut};
+
     use super::*;
 
     struct AccountData(pub Vec<u8>);
@@ -177,8 +320,12 @@ mod tests {
         }
     }
 
-    #[test]
-    fn check_max_validators_to_update_ix_per_tx_limit() {
+    fn check_validators_to_update_ix_per_tx_limit(
+        max: usize,
+        luts: &[AddressLookupTableAccount],
+        nonce: bool,
+        pool_lut: bool,
+    ) {
         let program_id = Pubkey::new_unique();
         let pool = Pubkey::new_unique();
         let sp = StakePool {
@@ -224,7 +371,7 @@ mod tests {
             pubkey: pool,
             account: AccountData(sp.try_to_vec().unwrap()),
         };
-        let vsi_list: Vec<ValidatorStakeInfo> = (0..MAX_VALIDATORS_TO_UPDATE_PER_TX)
+        let vsi_list: Vec<ValidatorStakeInfo> = (0..max)
             .map(|_| ValidatorStakeInfo {
                 active_stake_lamports: 0,
                 transient_stake_lamports: 0,
@@ -247,7 +394,84 @@ mod tests {
                 },
             )
             .unwrap();
+        // worst-case: nonce authority != payer, so 1 more signer
+        let advance_nonce_ix = nonce.then(|| {
+            system_instruction::advance_nonce_account(&Pubkey::new_unique(), &Pubkey::new_unique())
+        });
+        let mut luts = luts.to_vec();
+        if pool_lut {
+            luts.push(mock_pool_lut(std::slice::from_ref(&ix), &[]));
+        }
+        assert_tx_with_cb_ixs_within_size_limits(
+            &Pubkey::new_unique(),
+            advance_nonce_ix.into_iter().chain([ix]),
+            &luts,
+        );
+    }
+
+    #[test]
+    fn check_max_validators_to_update_ix_per_tx_limit() {
+        // size = 1193
+        check_validators_to_update_ix_per_tx_limit(
+            MAX_VALIDATORS_TO_UPDATE_PER_TX,
+            &[mock_srlut()],
+            false,
+            false,
+        );
+    }
+
+    #[test]
+    fn check_max_validators_to_update_ix_per_tx_no_srlut_limit() {
         // size = 1186
-        assert_tx_with_cb_ixs_within_size_limits(&Pubkey::new_unique(), [ix].into_iter(), &[]);
+        check_validators_to_update_ix_per_tx_limit(
+            MAX_VALIDATORS_TO_UPDATE_PER_TX_NO_SRLUT,
+            &[],
+            false,
+            false,
+        );
+    }
+
+    #[test]
+    fn check_max_validators_to_update_ix_per_tx_nonce_limit() {
+        // size = 1197
+        check_validators_to_update_ix_per_tx_limit(
+            MAX_VALIDATORS_TO_UPDATE_PER_TX_NONCE,
+            &[mock_srlut()],
+            true,
+            false,
+        );
+    }
+
+    #[test]
+    fn check_max_validators_to_update_ix_per_tx_no_srlut_nonce_limit() {
+        // size = 1190
+        check_validators_to_update_ix_per_tx_limit(
+            MAX_VALIDATORS_TO_UPDATE_PER_TX_NO_SRLUT_NONCE,
+            &[],
+            true,
+            false,
+        );
+    }
+
+    #[test]
+    fn check_max_validators_to_update_ix_per_tx_pool_lut_limit() {
+        // size = 391
+        check_validators_to_update_ix_per_tx_limit(
+            MAX_VALIDATORS_TO_UPDATE_PER_TX_POOL_LUT,
+            &[mock_srlut()],
+            false,
+            true,
+        );
+    }
+
+    #[test]
+    fn check_max_validators_to_update_ix_per_tx_pool_lut_nonce_limit() {
+        // size = 593
+        check_validators_to_update_ix_per_tx_limit(
+            MAX_VALIDATORS_TO_UPDATE_PER_TX_POOL_LUT,
+            &[mock_srlut()],
+            true,
+            true,
+        );
     }
 }
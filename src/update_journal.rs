@@ -0,0 +1,146 @@
+use std::{
+    collections::BTreeSet,
+    fs::{create_dir_all, read_to_string, remove_file, write},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+
+/// Record of which validator list update chunks of a pool's update crank for an epoch have landed,
+/// so that an interrupted crank can be resumed with `update --resume`
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct UpdateJournal {
+    pub pool: String,
+    pub epoch: u64,
+
+    /// Chunk size the start indices were recorded with.
+    /// Start indices are invalid if the crank is resumed with a different chunk size.
+    pub validators_per_tx: usize,
+
+    /// `start_index`es of landed `UpdateValidatorListBalance` txs
+    pub landed: BTreeSet<usize>,
+}
+
+impl UpdateJournal {
+    pub fn new(pool: &Pubkey, epoch: u64, validators_per_tx: usize) -> Self {
+        Self {
+            pool: pool.to_string(),
+            epoch,
+            validators_per_tx,
+            landed: BTreeSet::new(),
+        }
+    }
+
+    /// `~/.config/splsp/update-journal/<pool>-<epoch>.toml`, or in the temp dir if no home dir
+    pub fn default_path(pool: &Pubkey, epoch: u64) -> PathBuf {
+        let dir = std::env::var_os("HOME").map_or_else(
+            || std::env::temp_dir().join("splsp-update-journal"),
+            |home| PathBuf::from(home).join(".config/splsp/update-journal"),
+        );
+        dir.join(format!("{pool}-{epoch}.toml"))
+    }
+
+    /// Returns the journal at `path` if it exists and was recorded with the same chunk size,
+    /// otherwise a new empty journal
+    pub fn load_or_new(path: &Path, pool: &Pubkey, epoch: u64, validators_per_tx: usize) -> Self {
+        let new = Self::new(pool, epoch, validators_per_tx);
+        let s = match read_to_string(path) {
+            Ok(s) => s,
+            Err(_) => return new,
+        };
+        let UpdateJournalTomlFile { journal } = match toml::from_str(&s) {
+            Ok(f) => f,
+            Err(e) => {
+                eprintln!("Ignoring invalid update journal {}: {e}", path.display());
+                return new;
+            }
+        };
+        if journal.pool != new.pool || journal.epoch != epoch {
+            eprintln!(
+                "Ignoring update journal {} of a different pool or epoch",
+                path.display()
+            );
+            return new;
+        }
+        if journal.validators_per_tx != validators_per_tx {
+            eprintln!(
+                "Ignoring update journal {} recorded with {} validators per tx, now {validators_per_tx}",
+                path.display(),
+                journal.validators_per_tx
+            );
+            return new;
+        }
+        journal
+    }
+
+    pub fn is_landed(&self, start_index: usize) -> bool {
+        self.landed.contains(&start_index)
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        if let Some(dir) = path.parent() {
+            create_dir_all(dir)?;
+        }
+        write(
+            path,
+            toml::to_string_pretty(&UpdateJournalTomlFile {
+                journal: self.clone(),
+            })
+            .map_err(std::io::Error::other)?,
+        )
+    }
+
+    /// Records `start_index` as landed and saves the journal to `path`.
+    /// Failing to save only prints a warning since the journal is only an optimization.
+    pub fn record_landed(&mut self, path: &Path, start_index: usize) {
+        self.landed.insert(start_index);
+        if let Err(e) = self.save(path) {
+            eprintln!("Failed to save update journal {}: {e}", path.display());
+        }
+    }
+
+    /// Deletes the journal at `path`, if any
+    pub fn remove(path: &Path) {
+        let _ = remove_file(path);
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct UpdateJournalTomlFile {
+    pub journal: UpdateJournal,
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn journal_round_trip() {
+        let dir = TempDir::new().unwrap();
+        let pool = Pubkey::new_unique();
+        let path = dir.path().join("nested").join(format!("{pool}-5.toml"));
+
+        let mut journal = UpdateJournal::load_or_new(&path, &pool, 5, 12);
+        assert_eq!(journal, UpdateJournal::new(&pool, 5, 12));
+        journal.record_landed(&path, 24);
+        journal.record_landed(&path, 0);
+
+        let loaded = UpdateJournal::load_or_new(&path, &pool, 5, 12);
+        assert_eq!(loaded, journal);
+        assert!(loaded.is_landed(0) && loaded.is_landed(24) && !loaded.is_landed(12));
+
+        // different chunk size invalidates start indices
+        assert!(UpdateJournal::load_or_new(&path, &pool, 5, 11)
+            .landed
+            .is_empty());
+
+        UpdateJournal::remove(&path);
+        assert!(UpdateJournal::load_or_new(&path, &pool, 5, 12)
+            .landed
+            .is_empty());
+    }
+}
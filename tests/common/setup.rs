@@ -40,9 +40,24 @@ pub async fn setup_init_manager_payer(
 }
 
 pub async fn setup(
-    mut pt: ProgramTest,
+    pt: ProgramTest,
     payer: &Keypair,
 ) -> (Command, TempCliConfig, BanksClient, Hash) {
+    let (cmd, cfg, ctx) = setup_with_ctx(pt, payer).await;
+    let ProgramTestContext {
+        banks_client,
+        last_blockhash,
+        payer: _rng_payer,
+        ..
+    } = ctx;
+    (cmd, cfg, banks_client, last_blockhash)
+}
+
+/// Same as [`setup`], but returns the [`ProgramTestContext`] for tests that need to modify sysvars
+pub async fn setup_with_ctx(
+    mut pt: ProgramTest,
+    payer: &Keypair,
+) -> (Command, TempCliConfig, ProgramTestContext) {
     // TODO: reenable this cursed feature when it actly goes live
     pt.deactivate_feature(stake_raise_minimum_delegation_to_1_sol::ID);
     let ctx = pt.start_with_context().await;
@@ -55,15 +70,8 @@ pub async fn setup(
         unix_timestamp: Default::default(),
     });
 
-    let ProgramTestContext {
-        banks_client,
-        last_blockhash,
-        payer: _rng_payer,
-        ..
-    } = ctx;
-
-    let (port, _jh) = BanksRpcServer::spawn_random_unused(banks_client.clone()).await;
+    let (port, _jh) = BanksRpcServer::spawn_random_unused(ctx.banks_client.clone()).await;
     let cfg = TempCliConfig::from_keypair_and_local_port(payer, port);
     let cmd = base_cmd(&cfg);
-    (cmd, cfg, banks_client, last_blockhash)
+    (cmd, cfg, ctx)
 }
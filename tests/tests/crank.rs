@@ -0,0 +1,134 @@
+use std::io::Write;
+
+use borsh::BorshDeserialize;
+use sanctum_solana_test_utils::{
+    cli::{assert_all_txs_success_nonempty, ExtendedCommand},
+    test_fixtures_dir, ExtendedBanksClient, ExtendedProgramTest,
+};
+use sanctum_spl_stake_pool_cli::{CrankConfigToml, CrankConfigTomlFile};
+use sanctum_spl_stake_pool_lib::{FindWithdrawAuthority, ZERO_FEE};
+use solana_program_test::ProgramTest;
+use solana_sdk::{clock::Clock, pubkey::Pubkey, signature::read_keypair_file, signer::Signer};
+use spl_stake_pool_interface::{
+    AccountType, FutureEpochFee, Lockup, StakePool, ValidatorList, ValidatorListHeader,
+};
+use tempfile::NamedTempFile;
+
+use crate::common::{
+    add_all_stake_pool_accounts, add_spl_stake_pool_prog, base_cmd, setup_with_ctx, PoolArgs,
+    PoolKeys, SPL_STAKE_POOL_LAST_UPGRADE_EPOCH, SPL_STAKE_POOL_LAST_UPGRADE_SLOT,
+};
+
+const SLOTS_PER_EPOCH: u64 = 432_000;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn crank_updates_pool_every_epoch() {
+    let payer = read_keypair_file(test_fixtures_dir().join("example-staker-keypair.json")).unwrap();
+    let PoolKeys {
+        pool,
+        validator_list,
+        reserve,
+        mint,
+    } = PoolKeys::gen();
+
+    let sp = StakePool {
+        account_type: AccountType::StakePool,
+        staker: payer.pubkey(),
+        validator_list,
+        pool_mint: mint,
+        reserve_stake: reserve,
+        token_program: spl_token_interface::ID,
+        total_lamports: 10_000_000_000,
+        pool_token_supply: 10_000_000_000,
+        last_update_epoch: SPL_STAKE_POOL_LAST_UPGRADE_EPOCH - 1,
+        stake_withdraw_bump_seed: FindWithdrawAuthority { pool }
+            .run_for_prog(&spl_stake_pool_interface::ID)
+            .1,
+        // dont cares
+        preferred_deposit_validator_vote_address: None,
+        preferred_withdraw_validator_vote_address: None,
+        stake_deposit_authority: Pubkey::default(),
+        manager_fee_account: Pubkey::default(),
+        lockup: Lockup {
+            unix_timestamp: 0,
+            epoch: 0,
+            custodian: Pubkey::default(),
+        },
+        epoch_fee: ZERO_FEE,
+        next_epoch_fee: FutureEpochFee::None,
+        stake_deposit_fee: ZERO_FEE,
+        stake_withdrawal_fee: ZERO_FEE,
+        next_stake_withdrawal_fee: FutureEpochFee::None,
+        stake_referral_fee: 0,
+        sol_deposit_authority: None,
+        sol_deposit_fee: ZERO_FEE,
+        sol_referral_fee: 0,
+        sol_withdraw_authority: None,
+        sol_withdrawal_fee: ZERO_FEE,
+        next_sol_withdrawal_fee: FutureEpochFee::None,
+        last_epoch_pool_token_supply: 0,
+        last_epoch_total_lamports: 0,
+        manager: Pubkey::default(),
+    };
+    let vl = ValidatorList {
+        header: ValidatorListHeader {
+            account_type: AccountType::ValidatorList,
+            max_validators: 1,
+        },
+        validators: vec![],
+    };
+    let va = [];
+    let pt = add_all_stake_pool_accounts(
+        ProgramTest::default(),
+        PoolArgs {
+            program: spl_stake_pool_interface::ID,
+            pool,
+            current_epoch: SPL_STAKE_POOL_LAST_UPGRADE_EPOCH,
+        },
+        &sp,
+        &vl,
+        &va,
+    );
+    let pt = add_spl_stake_pool_prog(pt);
+    let pt = pt.add_system_account(payer.pubkey(), 1_000_000_000);
+
+    let crank_cfg_file = NamedTempFile::new().unwrap();
+    let s = toml::to_string_pretty(&CrankConfigTomlFile {
+        crank: CrankConfigToml {
+            pools: vec![pool.to_string()],
+        },
+    })
+    .unwrap();
+    crank_cfg_file.as_file().write_all(s.as_bytes()).unwrap();
+
+    let (mut cmd, cfg, ctx) = setup_with_ctx(pt, &payer).await;
+    let mut bc = ctx.banks_client.clone();
+
+    for epoch_offset in 0..2 {
+        if epoch_offset > 0 {
+            cmd = base_cmd(&cfg);
+            ctx.set_sysvar(&Clock {
+                slot: SPL_STAKE_POOL_LAST_UPGRADE_SLOT + 1 + epoch_offset * SLOTS_PER_EPOCH,
+                epoch: SPL_STAKE_POOL_LAST_UPGRADE_EPOCH + epoch_offset,
+                epoch_start_timestamp: Default::default(),
+                leader_schedule_epoch: Default::default(),
+                unix_timestamp: Default::default(),
+            });
+        }
+        cmd.arg("crank")
+            .arg("--max-epochs")
+            .arg("1")
+            .arg(crank_cfg_file.path());
+
+        let exec_res = cmd.exec_b64_txs(&mut bc).await;
+        assert_all_txs_success_nonempty(&exec_res);
+
+        let StakePool {
+            last_update_epoch, ..
+        } = StakePool::deserialize(&mut bc.get_account_data(pool).await.as_slice()).unwrap();
+        assert_eq!(
+            last_update_epoch,
+            SPL_STAKE_POOL_LAST_UPGRADE_EPOCH + epoch_offset
+        );
+    }
+}
@@ -1,7 +1,9 @@
+mod crank;
 mod decrease_validator_stake;
 mod increase_validator_stake;
 mod init;
 mod set_staker;
+mod sync;
 mod sync_delegation;
 mod sync_pool;
 mod sync_validator_list;